@@ -0,0 +1,5 @@
+fn main() {
+    // Link the way Valve's toolchain does. lld starts the executable segment
+    // mid-page, which maps the same code bytes twice and is its own problem.
+    println!("cargo:rustc-cdylib-link-arg=-fuse-ld=bfd");
+}
@@ -0,0 +1,253 @@
+//! A tiny stand-in for a game's client library, used by `self-test`.
+//!
+//! It contains a hand-built `ClientClass` list, the tables hanging off it, a
+//! copy of the instruction sequence the dumper's signature looks for and a
+//! `CreateInterface` handing out a client interface that leads to the list,
+//! so the whole load, scan and walk path can be checked without a game
+//! install. The expected results live in the dumper's `selftest` module;
+//! keep the two in sync.
+
+#![allow(non_snake_case)]
+
+use std::os::raw::{c_char, c_void};
+
+#[repr(C)]
+struct RecvTable {
+    m_pProps: *const RecvProp,
+    m_nProps: i32,
+    m_pDecoder: *const c_void,
+    m_pNetTableName: *const c_char,
+    m_bInitialized: bool,
+    m_bInMainList: bool,
+}
+
+#[repr(C)]
+pub struct ClientClass {
+    m_pCreateFn: *const c_void,
+    m_pCreateEventFn: *const c_void,
+    m_pNetworkName: *const c_char,
+    m_pRecvTable: *const RecvTable,
+    m_pNext: *const ClientClass,
+    m_ClassID: i32,
+}
+
+#[repr(C)]
+struct RecvProp {
+    m_pVarName: *const c_char,
+    m_RecvType: i32,
+    m_Flags: i32,
+    m_StringBufferSize: i32,
+    m_bInsideArray: bool,
+    m_pExtraData: *const c_void,
+    m_pArrayProp: *const RecvProp,
+    m_ArrayLengthProxy: *const c_void,
+    m_ProxyFn: *const c_void,
+    m_DataTableProxyFn: *const c_void,
+    m_pDataTable: *const RecvTable,
+    m_Offset: i32,
+    m_ElementStride: i32,
+    m_nElements: i32,
+    m_pParentArrayPropName: *const c_char,
+}
+
+/// Lets the raw pointers above live in statics.
+#[repr(transparent)]
+pub struct Shared<T>(T);
+
+unsafe impl<T> Sync for Shared<T> {}
+
+const DPT_INT: i32 = 0;
+const DPT_FLOAT: i32 = 1;
+const DPT_VECTOR: i32 = 2;
+const DPT_STRING: i32 = 4;
+const DPT_DATATABLE: i32 = 6;
+
+const fn prop(name: &'static [u8], kind: i32, offset: i32) -> RecvProp {
+    RecvProp {
+        m_pVarName: name.as_ptr() as *const c_char,
+        m_RecvType: kind,
+        m_Flags: 0,
+        m_StringBufferSize: 0,
+        m_bInsideArray: false,
+        m_pExtraData: std::ptr::null(),
+        m_pArrayProp: std::ptr::null(),
+        m_ArrayLengthProxy: std::ptr::null(),
+        m_ProxyFn: std::ptr::null(),
+        m_DataTableProxyFn: std::ptr::null(),
+        m_pDataTable: std::ptr::null(),
+        m_Offset: offset,
+        m_ElementStride: 0,
+        m_nElements: 0,
+        m_pParentArrayPropName: std::ptr::null(),
+    }
+}
+
+const fn table_prop(
+    name: &'static [u8],
+    table: &'static Shared<RecvTable>,
+    offset: i32,
+) -> RecvProp {
+    RecvProp {
+        m_pDataTable: &table.0,
+        ..prop(name, DPT_DATATABLE, offset)
+    }
+}
+
+const fn string_prop(name: &'static [u8], size: i32, offset: i32) -> RecvProp {
+    RecvProp {
+        m_StringBufferSize: size,
+        ..prop(name, DPT_STRING, offset)
+    }
+}
+
+const fn table(name: &'static [u8], props: &'static [RecvProp]) -> RecvTable {
+    RecvTable {
+        m_pProps: props.as_ptr(),
+        m_nProps: props.len() as i32,
+        m_pDecoder: std::ptr::null(),
+        m_pNetTableName: name.as_ptr() as *const c_char,
+        m_bInitialized: true,
+        m_bInMainList: true,
+    }
+}
+
+static ENTITY_PROPS: Shared<[RecvProp; 2]> = Shared([
+    prop(b"m_iHealth\0", DPT_INT, 0x100),
+    prop(b"m_vecOrigin\0", DPT_VECTOR, 0x138),
+]);
+static DT_FIXTURE_ENTITY: Shared<RecvTable> = Shared(table(b"DT_FixtureEntity\0", &ENTITY_PROPS.0));
+
+static LOCAL_PROPS: Shared<[RecvProp; 2]> = Shared([
+    prop(b"m_nTickBase\0", DPT_INT, 0x4),
+    prop(b"m_flFallVelocity\0", DPT_FLOAT, 0x8),
+]);
+static DT_FIXTURE_LOCAL: Shared<RecvTable> = Shared(table(b"DT_FixtureLocal\0", &LOCAL_PROPS.0));
+
+static AMMO_PROPS: Shared<[RecvProp; 2]> =
+    Shared([prop(b"000\0", DPT_INT, 0x0), prop(b"001\0", DPT_INT, 0x4)]);
+static DT_FIXTURE_AMMO: Shared<RecvTable> = Shared(table(b"m_iAmmo\0", &AMMO_PROPS.0));
+
+static PLAYER_PROPS: Shared<[RecvProp; 4]> = Shared([
+    table_prop(b"baseclass\0", &DT_FIXTURE_ENTITY, 0x0),
+    table_prop(b"m_Local\0", &DT_FIXTURE_LOCAL, 0x300),
+    string_prop(b"m_szLastPlaceName\0", 18, 0x400),
+    table_prop(b"m_iAmmo\0", &DT_FIXTURE_AMMO, 0x500),
+]);
+static DT_FIXTURE_PLAYER: Shared<RecvTable> = Shared(table(b"DT_FixturePlayer\0", &PLAYER_PROPS.0));
+
+/// A proxy with the kind of name a real one has, so the dumper's demangling
+/// has something to work on.
+#[export_name = "_ZN14C_FixtureWorld20RecvProxy_WaveHeightEPKvPvS2_"]
+pub extern "C" fn recv_proxy_wave_height(
+    _data: *const c_void,
+    _struct: *mut c_void,
+    _out: *mut c_void,
+) {
+}
+
+static WORLD_PROPS: Shared<[RecvProp; 2]> = Shared([
+    RecvProp {
+        m_ProxyFn: recv_proxy_wave_height as *const c_void,
+        ..prop(b"m_flWaveHeight\0", DPT_FLOAT, 0x10)
+    },
+    prop(b"m_bColdWorld\0", DPT_INT, 0x14),
+]);
+static DT_FIXTURE_WORLD: Shared<RecvTable> = Shared(table(b"DT_FixtureWorld\0", &WORLD_PROPS.0));
+
+static WORLD: Shared<ClientClass> = Shared(ClientClass {
+    m_pCreateFn: std::ptr::null(),
+    m_pCreateEventFn: std::ptr::null(),
+    m_pNetworkName: b"CFixtureWorld\0".as_ptr() as *const c_char,
+    m_pRecvTable: &DT_FIXTURE_WORLD.0,
+    m_pNext: std::ptr::null(),
+    m_ClassID: 0,
+});
+
+static PLAYER: Shared<ClientClass> = Shared(ClientClass {
+    m_pCreateFn: std::ptr::null(),
+    m_pCreateEventFn: std::ptr::null(),
+    m_pNetworkName: b"CFixturePlayer\0".as_ptr() as *const c_char,
+    m_pRecvTable: &DT_FIXTURE_PLAYER.0,
+    m_pNext: &WORLD.0,
+    m_ClassID: 1,
+});
+
+static CLIENT_CLASS_HEAD: Shared<*const ClientClass> = Shared(&PLAYER.0);
+
+/// Stands in for the symbol an unstripped client library has. A separate
+/// copy, since the signature below can't reference an exported symbol
+/// without going through the GOT.
+#[export_name = "g_pClientClassHead"]
+pub static EXPORTED_CLASS_HEAD: Shared<*const ClientClass> = Shared(&PLAYER.0);
+
+/// The same bytes as the code the dumper's `g_pClientClassHead` signature is
+/// made for: `91 48 8B 05 <rel32> 8B 53 14`. Exported so the linker keeps it,
+/// never meant to be called.
+#[no_mangle]
+pub extern "C" fn netvars_fixture_signature() {
+    unsafe {
+        std::arch::asm!(
+            ".byte 0x91",
+            "mov rax, qword ptr [rip + {head}]",
+            ".byte 0x8B, 0x53, 0x14",
+            head = sym CLIENT_CLASS_HEAD,
+            out("rax") _,
+            out("rcx") _,
+            out("rdx") _,
+        );
+    }
+}
+
+/// Stands in for the client's `IBaseClientDLL`: nothing but a vtable, with
+/// `GetAllClasses` where the real one has it.
+#[repr(C)]
+pub struct FixtureClient {
+    vtable: *const [*const c_void; 9],
+}
+
+/// The entries before `GetAllClasses`, never called.
+extern "C" fn unused(_this: *const FixtureClient) {}
+
+/// Compiled like `CHLClient::GetAllClasses()`, so the dumper can read the
+/// slot off the code.
+#[unsafe(naked)]
+extern "C" fn get_all_classes(_this: *const FixtureClient) -> *const ClientClass {
+    std::arch::naked_asm!(
+        "mov rax, qword ptr [rip + {head}]",
+        "ret",
+        head = sym CLIENT_CLASS_HEAD,
+    )
+}
+
+static CLIENT_VTABLE: Shared<[*const c_void; 9]> = Shared([
+    unused as *const c_void,
+    unused as *const c_void,
+    unused as *const c_void,
+    unused as *const c_void,
+    unused as *const c_void,
+    unused as *const c_void,
+    unused as *const c_void,
+    unused as *const c_void,
+    get_all_classes as *const c_void,
+]);
+static CLIENT: Shared<FixtureClient> = Shared(FixtureClient {
+    vtable: &CLIENT_VTABLE.0,
+});
+
+/// Hands out [`CLIENT`] as `VClient018`, like the real client does.
+///
+/// # Safety
+///
+/// `name` is null or a C string, `status` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn CreateInterface(name: *const c_char, status: *mut i32) -> *mut c_void {
+    let found = !name.is_null() && std::ffi::CStr::from_ptr(name).to_bytes() == b"VClient018";
+    if !status.is_null() {
+        *status = if found { 0 } else { 1 };
+    }
+    if found {
+        &CLIENT.0 as *const FixtureClient as *mut c_void
+    } else {
+        std::ptr::null_mut()
+    }
+}
@@ -0,0 +1,111 @@
+//! Dumping several games in one go, e.g. after Steam updated all of them.
+//!
+//! Every game is dumped by a process of its own, running this executable
+//! as if it had been asked for that game alone: each needs a library path
+//! of its own, which the loader only reads on startup. What they print goes
+//! into one file per game, and `index.json` says how each of them went.
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub gamedir: PathBuf,
+    /// The dump, relative to the output directory.
+    pub output: String,
+    /// What the dump logged and warned about.
+    pub log: String,
+    pub exit_code: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Index {
+    pub games: Vec<Entry>,
+}
+
+/// The game directories listed in the file at `path`, one per line.
+/// Relative ones are relative to the file, and `#` starts a comment.
+pub fn load_list(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| base.join(line))
+        .collect())
+}
+
+/// Dumps each of `gamedirs` with `options` into `output_dir`, naming the
+/// dumps `<game>.<extension>`. Returns the index, or why there is none.
+pub fn run(
+    gamedirs: &[PathBuf],
+    options: &[String],
+    output_dir: &Path,
+    extension: &str,
+) -> Result<Index, String> {
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("failed to create {}: {}", output_dir.display(), e))?;
+    let exe = std::env::current_exe().map_err(|e| format!("can't tell where we are: {}", e))?;
+
+    let mut games: Vec<Entry> = Vec::new();
+    for gamedir in gamedirs {
+        let name = unique_name(gamedir, &games);
+        let output = format!("{}.{}", name, extension);
+        let log = format!("{}.log", name);
+        let create = |file: &str| {
+            let path = output_dir.join(file);
+            fs::File::create(&path)
+                .map_err(|e| format!("failed to create {}: {}", path.display(), e))
+        };
+        eprintln!("{}: dumping {}", name, gamedir.display());
+        let status = Command::new(&exe)
+            .args(options)
+            .arg(gamedir)
+            .stdout(create(&output)?)
+            .stderr(create(&log)?)
+            .status()
+            .map_err(|e| format!("failed to run {}: {}", exe.display(), e))?;
+        // Killed by a signal
+        let exit_code = status.code().unwrap_or(-1);
+        if exit_code != 0 {
+            eprintln!("{}: failed with exit code {}, see {}", name, exit_code, log);
+        }
+        games.push(Entry {
+            name,
+            gamedir: gamedir.clone(),
+            output,
+            log,
+            exit_code,
+        });
+    }
+
+    let index = Index { games };
+    let path = output_dir.join("index.json");
+    let json = serde_json::to_string_pretty(&index).expect("the index is always serializable");
+    fs::write(&path, json + "\n")
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(index)
+}
+
+/// The game directory's name, made unique among `games` with a number.
+fn unique_name(gamedir: &Path, games: &[Entry]) -> String {
+    let base = gamedir
+        .canonicalize()
+        .unwrap_or_else(|_| gamedir.to_path_buf())
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "game".to_string());
+    let taken = |name: &str| games.iter().any(|game| game.name == name);
+    let mut name = base.clone();
+    let mut number = 2;
+    while taken(&name) {
+        name = format!("{}-{}", base, number);
+        number += 1;
+    }
+    name
+}
@@ -0,0 +1,338 @@
+//! Which build of the game a dump is of, by more than a file hash.
+//!
+//! The engine prints its build number at startup, e.g. `Exe build: ...
+//! (9029)` for one compiled on Jul 14 2021: the days from Oct 24 1996 to
+//! the `__DATE__` it was compiled on.
+//! That date is a string in `engine_client.so`, so the number can be worked
+//! out without running anything. `steam.inf` next to the game's content
+//! holds the version Steam and the server browser go by, and Steam's own
+//! app manifest the build id it was installed as.
+//!
+//! The engine's version ConVars hold versions of their own as defaults.
+//! Those are only set by constructors once the engine is loaded, but the
+//! calls pass them as string literals, so they're read off the code.
+
+use crate::elf::ElfFile;
+use crate::gamedir::Game;
+use crate::metadata::File;
+use crate::steam::Manifest;
+use iced_x86::{Code as Opcode, Decoder, DecoderOptions, FlowControl, Register};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const ENGINE_NAME: &str = "engine_client.so";
+/// Relative to the game directory.
+const STEAM_INF: &[&str] = &["csgo/steam.inf", "steam.inf"];
+/// How far before a ConVar's name its default may be loaded, and the most
+/// instructions from there to the constructor call.
+const CONVAR_WINDOW: usize = 48;
+const MAX_CONVAR_INSTRUCTIONS: usize = 16;
+/// `lea rsi, [rip + disp32]`, the name being the constructor's second
+/// argument after `this`.
+const LEA_RSI: [u8; 3] = [0x48, 0x8D, 0x35];
+/// `lea rdx, [rip + disp32]`, for the default right after it.
+const LEA_RDX: [u8; 3] = [0x48, 0x8D, 0x15];
+/// Longer than any ConVar's name or default.
+const MAX_STRING_LEN: usize = 256;
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Build {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<File>,
+    /// The engine's `__DATE__`, e.g. `Jul 14 2021`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<i32>,
+    /// Everything in `steam.inf`, e.g. `PatchVersion` and `ClientVersion`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub steam_inf: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+    /// The defaults of the engine's ConVars with `version` in their name,
+    /// by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub convars: BTreeMap<String, String>,
+}
+
+impl Build {
+    /// Whatever can be found out about `game`'s build. Missing files just
+    /// leave their part out.
+    pub fn identify(game: &Game) -> Self {
+        let mut build = Build::default();
+        let engine = game
+            .library_dirs
+            .iter()
+            .map(|dir| dir.join(ENGINE_NAME))
+            .find(|path| path.is_file());
+        if let Some(engine) = engine {
+            if let Ok(elf) = ElfFile::open(&engine) {
+                if let Some((date, number)) = build_date(&elf.bytes) {
+                    build.date = Some(date);
+                    build.number = Some(number);
+                }
+                build.convars = version_convars(&elf);
+            }
+            build.engine = File::describe(&engine).ok();
+        }
+        if let Some(path) = find_steam_inf(&game.dir) {
+            build.steam_inf = read_steam_inf(&path);
+        }
+        build.manifest = Manifest::find(&game.dir);
+        build
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.engine.is_none()
+            && self.steam_inf.is_empty()
+            && self.manifest.is_none()
+            && self.convars.is_empty()
+    }
+}
+
+fn find_steam_inf(dir: &Path) -> Option<PathBuf> {
+    STEAM_INF
+        .iter()
+        .map(|sub| dir.join(sub))
+        .find(|path| path.is_file())
+}
+
+/// `Key=Value` lines.
+fn read_steam_inf(path: &Path) -> BTreeMap<String, String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// The latest `__DATE__` among the strings in `bytes`, and the build
+/// number the engine makes of it. Several files of the engine may embed
+/// one, and only the newest was compiled with the build.
+fn build_date(bytes: &[u8]) -> Option<(String, i32)> {
+    bytes
+        .split(|&b| b == 0)
+        .filter_map(|s| std::str::from_utf8(s).ok())
+        .filter_map(|s| Some((parse_date(s)?, s)))
+        .max_by_key(|&(date, _)| date)
+        .map(|((year, month, day), s)| (s.to_string(), build_number(year, month, day)))
+}
+
+/// The defaults of the version ConVars `elf` constructs.
+fn version_convars(elf: &ElfFile) -> BTreeMap<String, String> {
+    let string_at = |address| string_at(elf, address);
+    elf.code()
+        .flat_map(|(start, code)| version_convars_in(code, start, string_at))
+        .collect()
+}
+
+/// The NUL terminated string at `address` in `elf`, if it's printable.
+fn string_at(elf: &ElfFile, address: usize) -> Option<String> {
+    let bytes = elf.bytes_at(address)?;
+    let bytes = &bytes[..bytes.len().min(MAX_STRING_LEN)];
+    let end = bytes.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&bytes[..end]).ok()?;
+    Some(s.to_string()).filter(|s| s.chars().all(|c| c.is_ascii_graphic() || c == ' '))
+}
+
+fn is_version_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.to_ascii_lowercase().contains("version")
+}
+
+/// The version ConVars constructed in `code`, loaded at `ip`, as name and
+/// default.
+///
+/// `ConVar(name, default, flags, help)` on a global gets the name in `rsi`
+/// and the default in `rdx`, both `lea`s of string literals before the
+/// call, in whatever order the compiler liked. Each `lea` of the name is
+/// taken for one, and the code around it decoded from each `lea rdx` a
+/// little before that lines up with it, up to the call.
+fn version_convars_in(
+    code: &[u8],
+    ip: usize,
+    string_at: impl Fn(usize) -> Option<String>,
+) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    for site in leas(code, LEA_RSI) {
+        let name = string_at(lea_target(code, ip, site)).filter(|name| is_version_name(name));
+        let name = match name {
+            Some(name) => name,
+            None => continue,
+        };
+        let starts = leas(&code[site.saturating_sub(CONVAR_WINDOW)..site], LEA_RDX)
+            .map(|start| site.saturating_sub(CONVAR_WINDOW) + start)
+            .chain(std::iter::once(site));
+        let default = starts
+            .filter_map(|start| default_at(code, ip, start, site))
+            .find_map(&string_at);
+        if let Some(default) = default {
+            found.push((name, default));
+        }
+    }
+    found
+}
+
+/// Offsets of `lea`s starting with `prefix` in `code`.
+fn leas(code: &[u8], prefix: [u8; 3]) -> impl Iterator<Item = usize> + '_ {
+    code.windows(7)
+        .enumerate()
+        .filter(move |(_, window)| window[..3] == prefix)
+        .map(|(offset, _)| offset)
+}
+
+/// Where the 7 byte `lea` at `offset` in `code` points.
+fn lea_target(code: &[u8], ip: usize, offset: usize) -> usize {
+    let mut disp = [0; 4];
+    disp.copy_from_slice(&code[offset + 3..offset + 7]);
+    let disp = i32::from_le_bytes(disp);
+    (ip + offset + 7).wrapping_add(disp as isize as usize)
+}
+
+/// What `rdx` points to at the first call after the instruction at `site`,
+/// decoding from `start`, if the instructions line up with `site` and
+/// nothing but a call leaves the way.
+fn default_at(code: &[u8], ip: usize, start: usize, site: usize) -> Option<usize> {
+    let mut decoder = Decoder::with_ip(
+        64,
+        &code[start..],
+        (ip + start) as u64,
+        DecoderOptions::NONE,
+    );
+    let mut rdx = None;
+    let mut seen_site = false;
+    for instruction in decoder.iter().take(MAX_CONVAR_INSTRUCTIONS) {
+        seen_site |= instruction.ip() as usize == ip + site;
+        if instruction.code() == Opcode::Lea_r64_m
+            && instruction.op0_register() == Register::RDX
+            && instruction.is_ip_rel_memory_operand()
+        {
+            rdx = Some(instruction.ip_rel_memory_address() as usize);
+        }
+        match instruction.flow_control() {
+            FlowControl::Next => {}
+            FlowControl::Call if seen_site => return rdx,
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// `Mmm dd yyyy`, with a space before single digit days, into the year,
+/// the month counted from 0 and the day.
+fn parse_date(s: &str) -> Option<(i32, usize, i32)> {
+    if s.len() != 11 || !s.is_ascii() || &s[3..4] != " " || &s[6..7] != " " {
+        return None;
+    }
+    let month = MONTHS.iter().position(|&month| month == &s[..3])?;
+    let day: i32 = s[4..6].trim_start().parse().ok()?;
+    let year: i32 = s[7..].parse().ok()?;
+    Some((year, month, day)).filter(|_| (1..=31).contains(&day) && year >= 1996)
+}
+
+/// The engine's `build_number()`, down to using 365.25 days a year and
+/// the leap day rule the original gets away with.
+fn build_number(year: i32, month: usize, day: i32) -> i32 {
+    const DAYS: [i32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    let mut days: i32 = DAYS[..month].iter().sum::<i32>() + day - 1;
+    let years = year - 1900;
+    days += ((years - 1) as f64 * 365.25) as i32;
+    if years % 4 == 0 && month > 1 {
+        days += 1;
+    }
+    // Oct 24 1996
+    days - 34995
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 7 byte `lea` with `prefix` at `ip` of `target`.
+    fn lea(prefix: [u8; 3], ip: usize, target: usize) -> Vec<u8> {
+        let disp = (target as i64 - (ip + 7) as i64) as i32;
+        prefix.iter().copied().chain(disp.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn finds_version_convars() {
+        const LEA_RDI: [u8; 3] = [0x48, 0x8D, 0x3D];
+        let strings: BTreeMap<usize, &str> = vec![
+            (0x9000, "sv_version"),
+            (0x9100, "1.38.0.2"),
+            (0x9200, "ServerVersion"),
+            (0x9300, "13802"),
+            (0x9400, "sv_cheats"),
+            (0x9500, "0"),
+        ]
+        .into_iter()
+        .collect();
+        let string_at = |address| strings.get(&address).map(|s| s.to_string());
+
+        let ip = 0x1000;
+        let mut code = Vec::new();
+        let mut push = |bytes: Vec<u8>| code.extend(bytes);
+        // The default first
+        push(lea(LEA_RDI, ip, 0xA000));
+        push(lea(LEA_RDX, ip + 7, 0x9100));
+        push(lea(LEA_RSI, ip + 14, 0x9000));
+        push(vec![0xB9, 0, 0, 0, 0, 0xE8, 0, 0, 0, 0]);
+        // The name first, the previous default still around
+        push(lea(LEA_RSI, ip + 31, 0x9200));
+        push(lea(LEA_RDX, ip + 38, 0x9300));
+        push(vec![0x31, 0xC9, 0xE8, 0, 0, 0, 0]);
+        // Not a version
+        push(lea(LEA_RDX, ip + 52, 0x9500));
+        push(lea(LEA_RSI, ip + 59, 0x9400));
+        push(vec![0xE8, 0, 0, 0, 0]);
+
+        assert_eq!(
+            version_convars_in(&code, ip, string_at),
+            [
+                ("sv_version".to_string(), "1.38.0.2".to_string()),
+                ("ServerVersion".to_string(), "13802".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_numbers() {
+        assert_eq!(build_number(2021, 6, 14), 9029);
+        // The engine's epoch
+        assert_eq!(build_number(1996, 9, 24), 0);
+        // Across a leap day
+        assert_eq!(build_number(2020, 2, 1) - build_number(2020, 1, 28), 2);
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse_date("Jul 14 2021"), Some((2021, 6, 14)));
+        assert_eq!(parse_date("Mar  3 2015"), Some((2015, 2, 3)));
+        for invalid in [
+            "Jul 14 21",
+            "Jux 14 2021",
+            "Jul 32 2021",
+            "Jul 14 1995",
+            "Jul-14-2021",
+        ] {
+            assert_eq!(parse_date(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn takes_the_latest_date() {
+        let bytes = b"\0Jul 14 2021\0Exe build\0Jan  2 2022\0Dec 31 2021\0";
+        assert_eq!(
+            build_date(bytes),
+            Some(("Jan  2 2022".to_string(), build_number(2022, 0, 2)))
+        );
+        assert_eq!(build_date(b"no date here"), None);
+    }
+}
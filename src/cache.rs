@@ -0,0 +1,149 @@
+//! Dumps kept by the SHA-256 of everything that went into them, so dumping
+//! a build again costs a lookup instead of loading it.
+//!
+//! The key covers the libraries' contents and whatever decides what is
+//! looked for in them, plus our version, since a newer one may dump the
+//! same build differently. Only dumps that succeeded are kept.
+
+use crate::sha256::{self, Sha256};
+use crate::walk::Dump;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Builds a cache key from labelled parts. Every part is prefixed with its
+/// label and length, so different inputs can't run together into the same
+/// bytes.
+#[derive(Debug, Clone)]
+pub struct Key {
+    hasher: Sha256,
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        let mut key = Key {
+            hasher: Sha256::default(),
+        };
+        key.add("version", env!("CARGO_PKG_VERSION").as_bytes());
+        key
+    }
+}
+
+impl Key {
+    pub fn add(&mut self, label: &str, bytes: &[u8]) -> &mut Self {
+        for part in [label.as_bytes(), bytes] {
+            self.hasher.update(&(part.len() as u64).to_le_bytes());
+            self.hasher.update(part);
+        }
+        self
+    }
+
+    /// Adds the contents of the file at `path`, by their hash.
+    pub fn add_file(&mut self, label: &str, path: &Path) -> Result<&mut Self, String> {
+        let digest =
+            hash_file(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Ok(self.add(label, &digest))
+    }
+
+    pub fn finish(&self) -> String {
+        sha256::hex(&self.hasher.clone().finish())
+    }
+}
+
+/// The SHA-256 of the file at `path`, read a piece at a time since game
+/// libraries are big.
+pub fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::default();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hasher.finish()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME/netvars-rs`, or `~/.cache/netvars-rs`.
+pub fn default_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("netvars-rs"))
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub dir: PathBuf,
+}
+
+impl Cache {
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The dump stored under `key`. One that can't be read is as good as
+    /// none, it'll just be overwritten.
+    pub fn load(&self, key: &str) -> Option<Dump> {
+        let text = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub fn store(&self, key: &str, dump: &Dump) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("failed to create {}: {}", self.dir.display(), e))?;
+        let path = self.path(key);
+        // Written next to it and renamed, so a concurrent run never reads
+        // half a dump
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        let json = serde_json::to_string(dump).expect("dumps are always serializable");
+        fs::write(&partial, json)
+            .and_then(|()| fs::rename(&partial, &path))
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::private::TempDir;
+
+    fn key(parts: &[(&str, &[u8])]) -> String {
+        let mut key = Key::default();
+        for &(label, bytes) in parts {
+            key.add(label, bytes);
+        }
+        key.finish()
+    }
+
+    #[test]
+    fn keys_are_length_prefixed() {
+        assert_eq!(key(&[("a", b"bc")]), key(&[("a", b"bc")]));
+        assert_ne!(key(&[("a", b"bc")]), key(&[("ab", b"c")]));
+        assert_ne!(key(&[("a", b"bc")]), key(&[("a", b"b"), ("", b"c")]));
+        assert_ne!(key(&[("a", b"")]), key(&[("a", b""), ("", b"")]));
+
+        let mut hasher = Sha256::default();
+        let version = env!("CARGO_PKG_VERSION").as_bytes();
+        for part in [&b"version"[..], version, b"a", b"bc"] {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        assert_eq!(key(&[("a", b"bc")]), sha256::hex(&hasher.finish()));
+    }
+
+    #[test]
+    fn files_are_added_by_their_hash() {
+        let dir = TempDir::new("netvars-rs-test").unwrap();
+        let path = dir.path().join("libclient_client.so");
+        fs::write(&path, b"ELF").unwrap();
+        let digest = hash_file(&path).unwrap();
+        let mut with_file = Key::default();
+        with_file.add_file("client", &path).unwrap();
+        assert_eq!(with_file.finish(), key(&[("client", &digest)]));
+        assert!(Key::default()
+            .add_file("client", &dir.path().join("missing"))
+            .is_err());
+    }
+}
@@ -0,0 +1,715 @@
+//! Command line parsing.
+
+use netvars_rs::cache;
+use netvars_rs::compress::Compression;
+use netvars_rs::dumper::{Strategy, DEFAULT_STRATEGIES};
+use netvars_rs::environment::Policy;
+use netvars_rs::fetch::{self, Fetch, Tool};
+use netvars_rs::history;
+use netvars_rs::makesig::DEFAULT_MAX_LEN;
+use netvars_rs::output::Format;
+use netvars_rs::publish::Target;
+use netvars_rs::runtime;
+use netvars_rs::signature::Signature;
+use netvars_rs::steam;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+usage: csgobot [options] (<path to CS:GO> | --app <appid>)
+       csgobot self-test [--fixture <path>] [--timeout <seconds>]
+       csgobot doctor [--timeout <seconds>] (<path to CS:GO> | --app <appid>)
+       csgobot make-sig [--name <name>] [--max-length <bytes>] <library> <rva|symbol>
+       csgobot batch [--games <file>] [--output-dir <dir>] [--sign-key <key.pem>]
+                     [<path>...] [-- <options>]
+       csgobot history [--history-dir <dir>] <table | table.prop | prop | signature>
+       csgobot publish [--compress <gzip|zstd>] <target> <file>...
+       csgobot sign --key <key.pem> <file>...
+       csgobot verify-signature --key <key.pem> <file>...
+       csgobot verify [--anchors <file>]... [--no-builtin-anchors] <dump.json>
+       csgobot fetch-and-dump [--app <appid>] [--depot <depot>[=<manifest>]]...
+                              [--branch <branch>] [--username <name>] [--dir <dir>]
+                              [--steamcmd <program> | --depotdownloader <program>]
+                              [-- <options>]
+
+options:
+    --anchors <file>       also check the dump against the anchors in this
+                           file, like verify does, may be given more than
+                           once
+    --app <appid>          dump the game Steam installed as this app (730
+                           for CS:GO) instead of a directory, looking for it
+                           in every library in Steam's libraryfolders.vdf
+    --cache                keep successful dumps in ~/.cache/netvars-rs
+                           (or under $XDG_CACHE_HOME) and reuse them while
+                           the libraries and signatures stay the same
+    --cache-dir <dir>      like --cache, but keep them in <dir>
+    --compare <offsets>    check the offsets against those in this file or
+                           http(s) URL, in the format of hazedumper's
+                           csgo.json, or against hazedumper's own if given
+                           hazedumper, and tell which disagree. Those are
+                           for Windows, where layouts may differ
+    --config <config.json> also report the netvars listed in this hazedumper
+                           config, under the names it gives them, and look
+                           for its signatures
+    --compress <gzip|zstd> compress the dump with gzip or zstd. It's binary
+                           then, to redirect into a file. batch names the
+                           dumps <game>.json.gz or <game>.json.zst
+    --confine              forbid the client from executing programs,
+                           opening network sockets and writing anywhere but
+                           the temporary directory (Landlock and seccomp)
+    --format <format>      text (default), json, jsonl or 010. jsonl is a
+                           line for each class, signature and problem, then
+                           the netvars, offsets and metadata. It's written
+                           as classes are read, without holding on to them,
+                           unless --cache, --config, --anchors, --verify,
+                           --history, --git, --compress or --strict need all
+                           of them. 010 is an 010 Editor template with a
+                           struct for every class
+    --git <repo>           write the dump and offsets.json, every offset on a
+                           line of its own, into this git repository and
+                           commit them with a summary of what changed
+    --history              record the dump in ~/.local/share/netvars-rs/history
+                           (or under $XDG_DATA_HOME) for the history command
+    --history-dir <dir>    like --history, but record it in <dir>
+    --keep-env             don't unset LD_PRELOAD, LD_AUDIT and the like
+                           before loading the client
+    --minimal-env          unset everything but a few basics like HOME and
+                           PATH before loading the client
+    --signature <name>[@<region>]=<pattern>[ -> <step>]...
+                           also look for this pattern in the client library,
+                           may be given more than once. <region> confines
+                           the search to a section (.text) or a range of
+                           offsets (0x1000-0x2000) instead of all code.
+                           <pattern> is IDA style (48 8B 05 ? ? ? ?) or
+                           code and mask (\\x48\\x8B\\x05\\x00 xxx?).
+                           Signatures sharing a name are alternatives, the
+                           first that matches counts.
+                           Steps lead from the match to what's reported
+                           with it: rel32(<disp>, <len>) to where a RIP-
+                           relative operand <disp> bytes in points, with the
+                           instruction ending <len> bytes in, add(<bytes>)
+                           further along, read to where the pointer there
+                           points, read32 to the 32 bit number there and,
+                           last, value to report a number rather than an
+                           offset into the client
+    --signatures <signatures.toml>
+                           also look for the signatures in this file, may
+                           be given more than once. Entries are
+                           [[signature]] tables with a name, a pattern or
+                           patterns, and optionally a module, a region and
+                           steps, all strings but patterns and steps, which
+                           are arrays of them
+    --module <library>     also load this library, a path or a file name in
+                           the game's library directories, and look for
+                           the signatures for it. May be given more than
+                           once. Libraries that signatures name a module
+                           for are added by themselves
+    --locate <strategies>  comma separated ways of finding the class list,
+                           tried in order: symbols, signature, interface
+                           (the client's VClient interface's
+                           GetAllClasses), relocations (default: all of
+                           them in that order)
+    --probe-symbols        if the dump fails, load the client again and
+                           report every symbol it and its libraries need
+                           that nothing provides
+    --previous <library>   the client library of the last build the
+                           signatures matched in. A signature that doesn't
+                           match anymore is looked for by the code around
+                           its old match, and a replacement is suggested
+    --sandbox              load the client in new user, mount and network
+                           namespaces, without network access and with the
+                           game directory and home mounted read-only
+    --shim                 build stubs for libraries the client needs that
+                           are missing, and for symbols nothing defines, so
+                           archived builds still load (needs cc). Every
+                           stubbed symbol is listed in a warning
+    --steam-runtime <mode> use the Steam Linux Runtime: libraries puts
+                           scout's libraries before the system's, container
+                           runs the dump inside soldier or sniper
+    --steam-runtime-dir <dir>
+                           the runtime to use instead of the one found
+                           next to the game or in Steam's directory
+    --strict               fail with a distinct exit code and a JSON error
+                           report instead of dumping despite problems
+    --timeout <seconds>    give up on loading and walking after this long
+                           (default: 60, 0 waits forever)
+    --verify               check the dump against the built-in anchors,
+                           like verify does. Failed anchors are problems,
+                           and the dump isn't recorded by --history or
+                           --git
+    --yara <rules>         also match the YARA rules in this file against
+                           the client's code, may be given more than once
+                           (needs a build with --features yara)
+
+self-test loads the bundled fixture library (libnetvars_fixture.so next to
+the executable by default) and checks the dump against known values.
+
+doctor follows the client library's dependencies the way the loader would,
+reports missing libraries and undefined symbols, tries loading it and
+suggests fixes.
+
+make-sig prints a --signature for an address (0x-prefixed hex, relative to
+the library's base) or symbol in a known-good build of a library. For data,
+the signature is for code referencing it, with the rel32 that leads back.
+
+batch dumps every game directory given, and those listed one per line in the
+--games file, with the options after --. Each dump goes into the output
+directory (default: dumps) as <game>.txt or <game>.json, next to <game>.log,
+and index.json lists how each went. With --sign-key, the dumps and the
+index are signed like sign does.
+
+history prints how a table's props changed from build to build, or every
+offset a prop, in the given table or any, or a signature has had, from the
+builds recorded by --history, which keeps them in an SQLite database with
+sqlite3.
+
+publish uploads files with curl to a target: an http(s):// URL to PUT them
+to, put: or post: and a URL to choose, s3://<bucket>/<prefix> (credentials
+in AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, AWS_REGION and, for other
+services than Amazon's, AWS_ENDPOINT_URL), gist: for a new secret gist or
+gist:<id> to update one, or github-release:<owner>/<repo>@<tag>. URLs ending
+in / get the file's name appended. GitHub needs a token in GITHUB_TOKEN.
+With --compress, files are compressed first and uploaded as <file>.gz or
+<file>.zst, unless they already are.
+
+sign signs files with an Ed25519 key into <file>.sig next to each, and
+verify-signature checks them against the key, the public one being enough.
+openssl does the work: openssl genpkey -algorithm ed25519 -out key.pem makes
+a key, openssl pkey -in key.pem -pubout the public key to hand out.
+
+verify checks a JSON dump against anchors, tables every build has and props
+whose offsets can only be in some range, and fails if any doesn't hold; the
+dump is then likely read with a wrong layout. The built-in anchors are for
+CS:GO's players, files add more, one per line: <table> to require a table,
+<table>.<prop> a prop, and <table>.<prop> <min>..<max> its offset, at least
+<min> and below <max>. # starts a comment.
+
+fetch-and-dump downloads a build (default: the latest of app 730 into
+fetched/730) with SteamCMD, or DepotDownloader if given, and dumps it with
+the options after --. Only the given depots are downloaded, at the given
+manifest if there is one, to get at old builds. The user is anonymous
+unless --username is given, the tool asks for the password.";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Counter-Strike: Global Offensive's app id.
+const CSGO_APP: u32 = 730;
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Dump(Box<Options>),
+    SelfTest {
+        fixture: Option<PathBuf>,
+        timeout: Option<Duration>,
+    },
+    Doctor {
+        gamedir: PathBuf,
+        timeout: Option<Duration>,
+    },
+    MakeSig {
+        library: PathBuf,
+        target: String,
+        name: Option<String>,
+        max_len: usize,
+    },
+    Batch {
+        gamedirs: Vec<PathBuf>,
+        games: Option<PathBuf>,
+        output_dir: PathBuf,
+        sign_key: Option<PathBuf>,
+        /// Passed on to every dump.
+        options: Vec<String>,
+        format: Format,
+        compress: Option<Compression>,
+    },
+    History {
+        dir: PathBuf,
+        query: String,
+    },
+    Sign {
+        key: PathBuf,
+        files: Vec<PathBuf>,
+    },
+    VerifySignature {
+        key: PathBuf,
+        files: Vec<PathBuf>,
+    },
+    Verify {
+        dump: PathBuf,
+        builtin: bool,
+        anchors: Vec<PathBuf>,
+    },
+    Publish {
+        compress: Option<Compression>,
+        target: Target,
+        files: Vec<PathBuf>,
+    },
+    FetchAndDump {
+        fetch: Fetch,
+        dir: Option<PathBuf>,
+        /// Passed on to the dump.
+        options: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub gamedir: PathBuf,
+    pub anchors: Vec<PathBuf>,
+    pub cache: Option<PathBuf>,
+    pub compare: Option<String>,
+    pub compress: Option<Compression>,
+    pub config: Option<PathBuf>,
+    pub confine: bool,
+    pub environment: Policy,
+    pub format: Format,
+    pub git: Option<PathBuf>,
+    pub history: Option<PathBuf>,
+    pub modules: Vec<String>,
+    pub signatures: Vec<Signature>,
+    pub signature_files: Vec<PathBuf>,
+    pub previous: Option<PathBuf>,
+    pub probe_symbols: bool,
+    pub sandbox: bool,
+    pub shim: bool,
+    pub steam_runtime: Option<runtime::Mode>,
+    pub steam_runtime_dir: Option<PathBuf>,
+    pub strict: bool,
+    pub strategies: Vec<Strategy>,
+    pub timeout: Option<Duration>,
+    pub verify: bool,
+    pub yara: Vec<PathBuf>,
+}
+
+pub fn parse(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        Some("self-test") => {
+            args.next();
+            parse_self_test(args)
+        }
+        Some("doctor") => {
+            args.next();
+            parse_doctor(args)
+        }
+        Some("make-sig") => {
+            args.next();
+            parse_make_sig(args)
+        }
+        Some("batch") => {
+            args.next();
+            parse_batch(args)
+        }
+        Some("history") => {
+            args.next();
+            parse_history(args)
+        }
+        Some("sign") => {
+            args.next();
+            parse_keyed_files(args, "sign").map(|(key, files)| Command::Sign { key, files })
+        }
+        Some("verify-signature") => {
+            args.next();
+            parse_keyed_files(args, "verify-signature")
+                .map(|(key, files)| Command::VerifySignature { key, files })
+        }
+        Some("verify") => {
+            args.next();
+            parse_verify(args)
+        }
+        Some("publish") => {
+            args.next();
+            parse_publish(args)
+        }
+        Some("fetch-and-dump") => {
+            args.next();
+            parse_fetch_and_dump(args)
+        }
+        _ => parse_dump(args).map(|options| Command::Dump(Box::new(options))),
+    }
+}
+
+fn parse_dump(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut gamedir = None;
+    let mut anchors = Vec::new();
+    let mut app = None;
+    let mut cache = None;
+    let mut compare = None;
+    let mut compress = None;
+    let mut config = None;
+    let mut confine = false;
+    let mut environment = Policy::Scrub;
+    let mut format = Format::Text;
+    let mut git = None;
+    let mut history = None;
+    let mut modules = Vec::new();
+    let mut signatures = Vec::new();
+    let mut signature_files = Vec::new();
+    let mut previous = None;
+    let mut probe_symbols = false;
+    let mut sandbox = false;
+    let mut shim = false;
+    let mut steam_runtime = None;
+    let mut steam_runtime_dir = None;
+    let mut strict = false;
+    let mut strategies = DEFAULT_STRATEGIES.to_vec();
+    let mut timeout = Some(DEFAULT_TIMEOUT);
+    let mut verify = false;
+    let mut yara = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--anchors" => anchors.push(PathBuf::from(value(&mut args, &arg)?)),
+            "--app" => app = Some(parse_app(&value(&mut args, &arg)?)?),
+            "--cache" => {
+                cache = Some(
+                    cache::default_dir()
+                        .ok_or("no cache directory, set HOME or pass --cache-dir")?,
+                )
+            }
+            "--cache-dir" => cache = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--compare" => compare = Some(value(&mut args, &arg)?),
+            "--config" => config = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--compress" => compress = Some(value(&mut args, &arg)?.parse()?),
+            "--confine" => confine = true,
+            "--format" => format = value(&mut args, &arg)?.parse()?,
+            "--git" => git = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--history" => {
+                history = Some(
+                    history::default_dir()
+                        .ok_or("no history directory, set HOME or pass --history-dir")?,
+                )
+            }
+            "--history-dir" => history = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--keep-env" => environment = Policy::Keep,
+            "--minimal-env" => environment = Policy::Minimal,
+            "--signature" => signatures.push(value(&mut args, &arg)?.parse()?),
+            "--signatures" => signature_files.push(PathBuf::from(value(&mut args, &arg)?)),
+            "--locate" => {
+                strategies = value(&mut args, &arg)?
+                    .split(',')
+                    .map(|s| s.trim().parse())
+                    .collect::<Result<_, _>>()?
+            }
+            "--module" => modules.push(value(&mut args, &arg)?),
+            "--previous" => previous = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--probe-symbols" => probe_symbols = true,
+            "--sandbox" => sandbox = true,
+            "--shim" => shim = true,
+            "--steam-runtime" => steam_runtime = Some(value(&mut args, &arg)?.parse()?),
+            "--steam-runtime-dir" => {
+                steam_runtime_dir = Some(PathBuf::from(value(&mut args, &arg)?))
+            }
+            "--strict" => strict = true,
+            "--timeout" => timeout = parse_timeout(&value(&mut args, &arg)?)?,
+            "--verify" => verify = true,
+            "--yara" => yara.push(PathBuf::from(value(&mut args, &arg)?)),
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ if gamedir.is_none() => gamedir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(Options {
+        gamedir: resolve_gamedir(gamedir, app)?,
+        anchors,
+        cache,
+        compare,
+        compress,
+        config,
+        confine,
+        environment,
+        format,
+        git,
+        history,
+        modules,
+        signatures,
+        signature_files,
+        previous,
+        probe_symbols,
+        sandbox,
+        shim,
+        steam_runtime,
+        steam_runtime_dir,
+        strict,
+        strategies,
+        timeout,
+        verify,
+        yara,
+    })
+}
+
+fn parse_app(appid: &str) -> Result<u32, String> {
+    appid
+        .parse()
+        .map_err(|_| format!("invalid app id: {}", appid))
+}
+
+/// The game directory given, or the one Steam installed `app` to.
+fn resolve_gamedir(gamedir: Option<PathBuf>, app: Option<u32>) -> Result<PathBuf, String> {
+    match (gamedir, app) {
+        (Some(_), Some(_)) => Err("give either a game directory or --app, not both".to_string()),
+        (Some(gamedir), None) => Ok(gamedir),
+        (None, Some(app)) => steam::find_app(app),
+        (None, None) => Err("missing game directory".to_string()),
+    }
+}
+
+fn parse_self_test(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut fixture = None;
+    let mut timeout = Some(DEFAULT_TIMEOUT);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fixture" => fixture = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--timeout" => timeout = parse_timeout(&value(&mut args, &arg)?)?,
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(Command::SelfTest { fixture, timeout })
+}
+
+fn parse_doctor(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut gamedir = None;
+    let mut app = None;
+    let mut timeout = Some(DEFAULT_TIMEOUT);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--app" => app = Some(parse_app(&value(&mut args, &arg)?)?),
+            "--timeout" => timeout = parse_timeout(&value(&mut args, &arg)?)?,
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ if gamedir.is_none() => gamedir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(Command::Doctor {
+        gamedir: resolve_gamedir(gamedir, app)?,
+        timeout,
+    })
+}
+
+fn parse_make_sig(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut positional = Vec::new();
+    let mut name = None;
+    let mut max_len = DEFAULT_MAX_LEN;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => name = Some(value(&mut args, &arg)?),
+            "--max-length" => {
+                let length = value(&mut args, &arg)?;
+                max_len = length
+                    .parse()
+                    .ok()
+                    .filter(|&l| l > 0)
+                    .ok_or_else(|| format!("invalid length: {}", length))?
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ => positional.push(arg),
+        }
+    }
+
+    match <[String; 2]>::try_from(positional) {
+        Ok([library, target]) => Ok(Command::MakeSig {
+            library: PathBuf::from(library),
+            target,
+            name,
+            max_len,
+        }),
+        Err(_) => Err("make-sig takes a library and an address or symbol".to_string()),
+    }
+}
+
+fn parse_batch(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut gamedirs = Vec::new();
+    let mut games = None;
+    let mut output_dir = PathBuf::from("dumps");
+    let mut sign_key = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => break,
+            "--games" => games = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--output-dir" => output_dir = PathBuf::from(value(&mut args, &arg)?),
+            "--sign-key" => sign_key = Some(PathBuf::from(value(&mut args, &arg)?)),
+            flag if flag.starts_with("--") => {
+                return Err(format!(
+                    "unknown option: {}, dump options go after --",
+                    flag
+                ))
+            }
+            _ => gamedirs.push(PathBuf::from(arg)),
+        }
+    }
+
+    // Checked now rather than once per game
+    let options: Vec<String> = args.collect();
+    let parsed = parse_dump(std::iter::once(".".to_string()).chain(options.iter().cloned()))
+        .map_err(|e| format!("{}, the game directories go before --", e))?;
+    Ok(Command::Batch {
+        gamedirs,
+        games,
+        output_dir,
+        sign_key,
+        options,
+        format: parsed.format,
+        compress: parsed.compress,
+    })
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn parse_timeout(value: &str) -> Result<Option<Duration>, String> {
+    let seconds: f64 = value
+        .parse()
+        .ok()
+        .filter(|s: &f64| s.is_finite() && *s >= 0.0)
+        .ok_or_else(|| format!("invalid timeout: {}", value))?;
+    Ok(Some(Duration::from_secs_f64(seconds)).filter(|t| !t.is_zero()))
+}
+
+fn parse_history(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut dir = None;
+    let mut query = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--history-dir" => dir = Some(PathBuf::from(value(&mut args, &arg)?)),
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ if query.is_none() => query = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    let dir = match dir {
+        Some(dir) => dir,
+        None => {
+            history::default_dir().ok_or("no history directory, set HOME or pass --history-dir")?
+        }
+    };
+    Ok(Command::History {
+        dir,
+        query: query.ok_or("missing a table, prop or signature to look up")?,
+    })
+}
+
+/// `--key <key>` and files, for `sign` and `verify-signature`.
+fn parse_keyed_files(
+    mut args: impl Iterator<Item = String>,
+    command: &str,
+) -> Result<(PathBuf, Vec<PathBuf>), String> {
+    let mut key = None;
+    let mut files = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => key = Some(PathBuf::from(value(&mut args, &arg)?)),
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    let key = key.ok_or_else(|| format!("{} needs a --key", command))?;
+    if files.is_empty() {
+        return Err(format!("{} takes the files to check", command));
+    }
+    Ok((key, files))
+}
+
+fn parse_verify(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut dump = None;
+    let mut builtin = true;
+    let mut anchors = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--anchors" => anchors.push(PathBuf::from(value(&mut args, &arg)?)),
+            "--no-builtin-anchors" => builtin = false,
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ if dump.is_none() => dump = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    if !builtin && anchors.is_empty() {
+        return Err("--no-builtin-anchors leaves nothing to check without --anchors".to_string());
+    }
+    Ok(Command::Verify {
+        dump: dump.ok_or("missing the JSON dump to verify")?,
+        builtin,
+        anchors,
+    })
+}
+
+fn parse_publish(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut compress = None;
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--compress" => compress = Some(value(&mut args, &arg)?.parse()?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option: {}", flag)),
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    let target = positional
+        .next()
+        .ok_or("publish takes a target and files")?;
+    let files: Vec<PathBuf> = positional.map(PathBuf::from).collect();
+    if files.is_empty() {
+        return Err("publish takes a target and files".to_string());
+    }
+    Ok(Command::Publish {
+        compress,
+        target: target.parse()?,
+        files,
+    })
+}
+
+fn parse_fetch_and_dump(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut fetch = Fetch {
+        app: CSGO_APP,
+        depots: Vec::new(),
+        branch: None,
+        username: fetch::ANONYMOUS.to_string(),
+        tool: Tool::default(),
+    };
+    let mut dir = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => break,
+            "--app" => fetch.app = parse_app(&value(&mut args, &arg)?)?,
+            "--branch" => fetch.branch = Some(value(&mut args, &arg)?),
+            "--depot" => fetch.depots.push(value(&mut args, &arg)?.parse()?),
+            "--dir" => dir = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--username" => fetch.username = value(&mut args, &arg)?,
+            "--steamcmd" => fetch.tool = Tool::SteamCmd(PathBuf::from(value(&mut args, &arg)?)),
+            "--depotdownloader" => {
+                fetch.tool = Tool::DepotDownloader(PathBuf::from(value(&mut args, &arg)?))
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!(
+                    "unknown option: {}, dump options go after --",
+                    flag
+                ))
+            }
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    // Checked before spending a download on them
+    let options: Vec<String> = args.collect();
+    parse_dump(std::iter::once(".".to_string()).chain(options.iter().cloned()))
+        .map_err(|e| format!("{}, the build is given by --dir", e))?;
+    Ok(Command::FetchAndDump {
+        fetch,
+        dir,
+        options,
+    })
+}
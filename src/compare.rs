@@ -0,0 +1,117 @@
+//! Checking a dump against offsets someone else published, like
+//! hazedumper's `csgo.json`: where both name the same offset, they should
+//! agree, and if many don't the dump is likely reading a wrong layout.
+//!
+//! Names are matched as they are, so netvars need a `--config` naming them
+//! like the published file does.
+//!
+//! hazedumper dumps the Windows client, which is 32 bit, so only offsets of
+//! fields laid out the same on both can agree with it; a file published
+//! for Linux builds is the better reference where there is one.
+
+use crate::compress;
+use crate::walk::Dump;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+pub const HAZEDUMPER_URL: &str =
+    "https://raw.githubusercontent.com/frk1/hazedumper/master/csgo.json";
+
+/// A hazedumper-style `{"signatures": {...}, "netvars": {...}}`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Published {
+    #[serde(default)]
+    pub signatures: BTreeMap<String, i64>,
+    #[serde(default)]
+    pub netvars: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    pub agree: Vec<String>,
+    /// Names with our offset and the published one.
+    pub disagree: Vec<(String, i64, i64)>,
+    /// Published, but not in the dump.
+    pub missing: Vec<String>,
+}
+
+impl Published {
+    /// The offsets at `source`: `hazedumper` for its `csgo.json`, an
+    /// http(s) URL, which is downloaded with curl, or a file.
+    pub fn load(source: &str) -> Result<Self, String> {
+        let url = match source {
+            "hazedumper" => HAZEDUMPER_URL,
+            _ => source,
+        };
+        let text = if url.starts_with("http://") || url.starts_with("https://") {
+            let output = Command::new("curl")
+                .args(["--silent", "--show-error", "--fail", "--location", url])
+                .output()
+                .map_err(|e| format!("failed to run curl: {}", e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "failed to download {}: {}",
+                    url,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            output.stdout
+        } else {
+            compress::read(Path::new(url))?
+        };
+        serde_json::from_slice(&text).map_err(|e| format!("invalid offsets {}: {}", url, e))
+    }
+
+    pub fn compare(&self, dump: &Dump) -> Comparison {
+        let ours = dump
+            .offsets
+            .iter()
+            .map(|(name, &offset)| (name, offset as i64))
+            .chain(dump.netvars.iter().map(|(name, &offset)| (name, offset)));
+        let ours: BTreeMap<&String, i64> = ours.collect();
+        let mut comparison = Comparison::default();
+        for (name, &theirs) in self.signatures.iter().chain(&self.netvars) {
+            match ours.get(name) {
+                Some(&offset) if offset == theirs => comparison.agree.push(name.clone()),
+                Some(&offset) => comparison.disagree.push((name.clone(), offset, theirs)),
+                None => comparison.missing.push(name.clone()),
+            }
+        }
+        comparison
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn compares_offsets() {
+        let published: Published = serde_json::from_value(json!({
+            "signatures": { "dwEntityList": 0x4D5022C, "dwLocalPlayer": 0xD3DD14 },
+            "netvars": { "m_iHealth": 0x100, "m_fFlags": 0x104, "m_iTeamNum": 0xF4 },
+        }))
+        .unwrap();
+        let dump: Dump = serde_json::from_value(json!({
+            "classes": [],
+            "signatures": [],
+            "problems": [],
+            "offsets": { "dwEntityList": 0x4D5022C, "dwLocalPlayer": 0xD3DD18 },
+            "netvars": { "m_iHealth": 0x100, "m_fFlags": 0x13C, "m_bSpotted": 0x93D },
+        }))
+        .unwrap();
+        let comparison = published.compare(&dump);
+        assert_eq!(comparison.agree, ["dwEntityList", "m_iHealth"]);
+        assert_eq!(
+            comparison.disagree,
+            [
+                ("dwLocalPlayer".to_string(), 0xD3DD18, 0xD3DD14),
+                ("m_fFlags".to_string(), 0x13C, 0x104),
+            ]
+        );
+        assert_eq!(comparison.missing, ["m_iTeamNum"]);
+    }
+}
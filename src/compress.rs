@@ -0,0 +1,119 @@
+//! Compressing what `--compress` is given for with gzip or zstd, which get
+//! the data on their standard input. Full dumps with every array element
+//! expanded get large, and so do histories of them.
+//!
+//! Compressed files are told apart by their extension, `.gz` or `.zst`, and
+//! whatever reads dumps or records back decompresses those.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression: {}, expected gzip or zstd", s)),
+        }
+    }
+}
+
+impl Compression {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    /// How the file at `path` is compressed, going by its extension.
+    pub fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// `path` with the extension appended.
+    pub fn path(self, path: &Path) -> PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(".");
+        path.push(self.extension());
+        PathBuf::from(path)
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            // Without the name and time in the header, so the same dump
+            // compresses to the same bytes and signatures stay comparable
+            Compression::Gzip => pipe("gzip", &["-c", "-n"], data),
+            Compression::Zstd => pipe("zstd", &["-c", "-q"], data),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Compression::Gzip => pipe("gzip", &["-d", "-c"], data),
+            Compression::Zstd => pipe("zstd", &["-d", "-c", "-q"], data),
+        }
+    }
+}
+
+/// The contents of the file at `path`, decompressed if its extension says
+/// it's compressed.
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    match Compression::of(path) {
+        Some(compression) => compression
+            .decompress(&data)
+            .map_err(|e| format!("failed to decompress {}: {}", path.display(), e)),
+        None => Ok(data),
+    }
+}
+
+/// Runs `program` with `data` on its standard input, returning what it
+/// printed.
+pub fn pipe(program: &str, args: &[&str], data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Written from a thread of its own, the program may not read all of it
+    // before its output fills the pipe
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(data));
+        let output = child.wait_with_output();
+        (writer.join().expect("writing doesn't panic"), output)
+    });
+    let output = match output {
+        (_, Err(e)) => return Err(format!("failed to run {}: {}", program, e)),
+        (Err(e), Ok(output)) if output.status.success() => {
+            return Err(format!("failed to pipe into {}: {}", program, e))
+        }
+        (_, Ok(output)) => output,
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
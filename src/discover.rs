@@ -0,0 +1,104 @@
+//! Finding the class list without its signature, for when an update has
+//! changed the code around it.
+//!
+//! Every pointer in the data segments that the loader has to fix up has a
+//! dynamic relocation, and so does every GOT entry. `g_pClientClassHead` is
+//! either one of those pointers itself or is reached through one, so each
+//! relocated slot, and each slot such a pointer points to, is a candidate.
+//! The one holding the longest list of plausible classes wins.
+
+use crate::elf::ElfFile;
+use crate::memory::Memory;
+use crate::module::Module;
+use crate::sdk::ClientClass;
+use std::collections::HashSet;
+
+/// No client has anywhere near this many classes.
+const MAX_CLASSES: usize = 0x10000;
+/// Network names are identifiers like `CCSPlayer`.
+const MAX_NAME_LEN: usize = 256;
+
+/// A slot that holds what looks like the head of the class list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub slot: usize,
+    pub head: usize,
+    pub classes: usize,
+}
+
+/// Every candidate holding the longest plausible list, ordered by slot.
+pub fn from_relocations(memory: &Memory, module: &Module, elf: &ElfFile) -> Vec<Candidate> {
+    let mut slots = HashSet::new();
+    for &offset in &elf.relocations {
+        let slot = module.address + offset;
+        slots.insert(slot);
+        // One level of indirection covers GOT entries
+        if let Ok(target) = unsafe { memory.read::<usize>(slot) } {
+            if module.segment(target).is_some_and(|s| s.write) {
+                slots.insert(target);
+            }
+        }
+    }
+
+    let mut best: Vec<Candidate> = Vec::new();
+    for slot in slots {
+        let head = match unsafe { memory.read::<usize>(slot) } {
+            Ok(head) => head,
+            Err(_) => continue,
+        };
+        let classes = list_len(memory, module, head);
+        let longest = best.first().map_or(1, |c| c.classes);
+        if classes > longest {
+            best.clear();
+        }
+        if classes >= longest {
+            best.push(Candidate {
+                slot,
+                head,
+                classes,
+            });
+        }
+    }
+    best.sort_by_key(|c| c.slot);
+    best
+}
+
+/// How many classes the list at `head` has, or 0 if anything about it looks
+/// off.
+pub fn list_len(memory: &Memory, module: &Module, head: usize) -> usize {
+    let mut seen = HashSet::new();
+    let mut address = head;
+    while address != 0 {
+        if seen.len() == MAX_CLASSES || !seen.insert(address) || !plausible(memory, module, address)
+        {
+            return 0;
+        }
+        address = match unsafe { memory.read::<ClientClass>(address) } {
+            Ok(class) => class.m_pNext as usize,
+            Err(_) => return 0,
+        };
+    }
+    seen.len()
+}
+
+fn plausible(memory: &Memory, module: &Module, address: usize) -> bool {
+    if module.segment(address).is_none() {
+        return false;
+    }
+    let class = match unsafe { memory.read::<ClientClass>(address) } {
+        Ok(class) => class,
+        Err(_) => return false,
+    };
+    let table = class.m_pRecvTable as usize;
+    if table != 0 && module.segment(table).is_none() {
+        return false;
+    }
+    match memory.read_cstr(class.m_pNetworkName as usize) {
+        Ok(name) => {
+            !name.is_empty()
+                && name.len() <= MAX_NAME_LEN
+                && name.iter().all(|&b| b == b'_' || b.is_ascii_alphanumeric())
+        }
+        Err(_) => false,
+    }
+}
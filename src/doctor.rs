@@ -0,0 +1,415 @@
+//! `doctor`: works out why a client library won't load, without loading
+//! it first.
+//!
+//! `dlopen` either works or hands back a null pointer and one line of
+//! `dlerror`, which names the first missing library or symbol and nothing
+//! after it. Instead, the client's `DT_NEEDED` entries are followed the way
+//! the loader would, and then every library's undefined symbols are looked
+//! up in all the others. Only then is the client actually loaded, in the
+//! worker, to catch whatever the files don't show.
+
+use crate::dumper;
+use crate::elf::{ElfFile, Symbol};
+use crate::environment::{self, Policy};
+use crate::gamedir::Game;
+use crate::report;
+use crate::runtime::{Kind, Runtime};
+use crate::worker;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where the loader looks when nothing else says otherwise.
+const SYSTEM_DIRS: &[&str] = &[
+    "/lib/x86_64-linux-gnu",
+    "/usr/lib/x86_64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+];
+const LD_SO_CONF: &str = "/etc/ld.so.conf";
+/// Shipped with the game rather than the system, besides the `_client.so`
+/// ones.
+const GAME_LIBRARIES: &[&str] = &["libsteam_api.so"];
+/// Undefined symbols listed per library before the rest are only counted.
+const MAX_LISTED: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Game,
+    Runpath,
+    System,
+}
+
+impl Source {
+    fn name(self) -> &'static str {
+        match self {
+            Source::Game => "game",
+            Source::Runpath => "runpath",
+            Source::System => "system",
+        }
+    }
+}
+
+struct Search {
+    game: Game,
+    system: Vec<PathBuf>,
+}
+
+impl Search {
+    /// Where `name`, needed by `by`, would be loaded from.
+    fn find(&self, name: &str, by: &Path) -> Option<(PathBuf, Source)> {
+        if name.contains('/') {
+            return Some(PathBuf::from(name))
+                .filter(|p| is_loadable(p))
+                .map(|p| (p, Source::System));
+        }
+        let origin = by.parent().unwrap_or_else(|| Path::new("/"));
+        let runpath: Vec<PathBuf> = ElfFile::open(by)
+            .map(|elf| elf.runpath)
+            .unwrap_or_default()
+            .iter()
+            .map(|dir| PathBuf::from(dir.replace("$ORIGIN", &origin.to_string_lossy())))
+            .collect();
+        let game = self.game.library_dirs.iter().map(|d| (d, Source::Game));
+        let runpath = runpath.iter().map(|d| (d, Source::Runpath));
+        let system = self.system.iter().map(|d| (d, Source::System));
+        game.chain(runpath)
+            .chain(system)
+            .map(|(dir, source)| (dir.join(name), source))
+            .find(|(path, _)| is_loadable(path))
+    }
+}
+
+/// Whether `path` is a 64-bit ELF file, other ones are skipped by the
+/// loader too.
+fn is_loadable(path: &Path) -> bool {
+    let mut ident = [0u8; 5];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut ident))
+        .map(|_| ident == [0x7F, b'E', b'L', b'F', 2])
+        .unwrap_or(false)
+}
+
+/// The directories listed in `ld.so.conf` and the files it includes,
+/// followed by the defaults.
+fn system_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    read_ld_so_conf(Path::new(LD_SO_CONF), &mut dirs, 0);
+    dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+fn read_ld_so_conf(path: &Path, dirs: &mut Vec<PathBuf>, depth: usize) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) if depth < 8 => text,
+        _ => return,
+    };
+    for line in text
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+    {
+        match line.strip_prefix("include") {
+            Some(pattern) => {
+                for included in glob(pattern.trim()) {
+                    read_ld_so_conf(&included, dirs, depth + 1);
+                }
+            }
+            None if !line.is_empty() => dirs.push(PathBuf::from(line)),
+            None => {}
+        }
+    }
+}
+
+/// Expands a `dir/prefix*suffix` include, which is as far as `ld.so.conf`
+/// files go in practice.
+fn glob(pattern: &str) -> Vec<PathBuf> {
+    let path = Path::new(pattern);
+    let (dir, file) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(file)) => (dir, file.to_string_lossy()),
+        _ => return Vec::new(),
+    };
+    let dir = if dir.is_relative() {
+        Path::new("/etc").join(dir)
+    } else {
+        dir.to_path_buf()
+    };
+    let (prefix, suffix) = file.split_once('*').unwrap_or((&file, ""));
+    if !file.contains('*') {
+        return vec![dir.join(prefix)];
+    }
+    let mut found: Vec<_> = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// What loading the client would run into, as far as the files tell.
+pub struct Analysis {
+    /// Every library the client needs, directly or not, with what it
+    /// needs below it.
+    pub tree: Vec<String>,
+    /// Libraries that couldn't be found, with the first library needing
+    /// them.
+    pub missing: Vec<(String, PathBuf)>,
+    /// Libraries that couldn't be read.
+    pub damaged: Vec<(PathBuf, String)>,
+    /// Symbols each library needs that none of the others define.
+    pub unresolved: Vec<(PathBuf, Vec<Symbol>)>,
+}
+
+/// Follows the client's dependencies and checks that every symbol one of
+/// them needs is defined by another.
+pub fn analyze(game: &Game) -> Analysis {
+    let search = Search {
+        game: game.clone(),
+        system: system_dirs(),
+    };
+    let mut tree = Tree {
+        search: &search,
+        loaded: Vec::new(),
+        seen: HashMap::new(),
+        analysis: Analysis {
+            tree: Vec::new(),
+            missing: Vec::new(),
+            damaged: Vec::new(),
+            unresolved: Vec::new(),
+        },
+    };
+    tree.visit(game.client.clone(), 1);
+
+    let mut analysis = tree.analysis;
+    let defined: HashSet<&str> = tree
+        .loaded
+        .iter()
+        .flat_map(|(_, elf)| elf.symbols.iter().filter(|s| s.exported))
+        .map(|s| s.name.as_str())
+        .collect();
+    for (path, elf) in &tree.loaded {
+        let unresolved: Vec<_> = elf
+            .undefined
+            .iter()
+            .filter(|symbol| !defined.contains(symbol.name.as_str()))
+            .cloned()
+            .collect();
+        if !unresolved.is_empty() {
+            analysis.unresolved.push((path.clone(), unresolved));
+        }
+    }
+    analysis
+}
+
+/// The libraries the client needs, as they're found.
+struct Tree<'a> {
+    search: &'a Search,
+    loaded: Vec<(PathBuf, ElfFile)>,
+    /// Where each library name was found, if at all.
+    seen: HashMap<String, Option<PathBuf>>,
+    analysis: Analysis,
+}
+
+impl Tree<'_> {
+    /// Notes what `path` needs and where that is, then does the same for
+    /// each of those in turn.
+    fn visit(&mut self, path: PathBuf, depth: usize) {
+        let indent = depth * 2;
+        let elf = match ElfFile::open(&path) {
+            Ok(elf) => elf,
+            Err(e) => {
+                self.analysis
+                    .tree
+                    .push(format!("{:indent$}FAIL: {}", "", e, indent = indent));
+                self.analysis.damaged.push((path, e));
+                return;
+            }
+        };
+        let needed = elf.needed.clone();
+        self.loaded.push((path.clone(), elf));
+        for name in needed {
+            let line = match self.seen.get(&name) {
+                Some(Some(_)) => format!("{} (see above)", name),
+                Some(None) => format!("{} => not found", name),
+                None => match self.search.find(&name, &path) {
+                    Some((found, source)) => {
+                        let line = format!("{} => {} ({})", name, found.display(), source.name());
+                        self.analysis
+                            .tree
+                            .push(format!("{:indent$}{}", "", line, indent = indent));
+                        self.seen.insert(name, Some(found.clone()));
+                        self.visit(found, depth + 1);
+                        continue;
+                    }
+                    None => {
+                        self.seen.insert(name.clone(), None);
+                        let line = format!("{} => not found", name);
+                        self.analysis.missing.push((name, path.clone()));
+                        line
+                    }
+                },
+            };
+            self.analysis
+                .tree
+                .push(format!("{:indent$}{}", "", line, indent = indent));
+        }
+    }
+}
+
+/// Runs `doctor` and returns the exit code.
+pub fn run(dir: &Path, timeout: Option<Duration>) -> i32 {
+    let game = match Game::locate(dir) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("FAIL: {}", e);
+            println!("  point doctor at the directory holding csgo/ and bin/");
+            return report::EXIT_DOCTOR_FOUND_PROBLEMS;
+        }
+    };
+    // The real load below should see the same search path a dump does
+    if let Err(e) = environment::ensure(&game.library_dirs, Policy::Scrub) {
+        eprintln!("error: failed to restart with the library path set: {}", e);
+        return report::EXIT_FAILED;
+    }
+    println!("client: {}", game.client.display());
+    let dirs: Vec<_> = game
+        .library_dirs
+        .iter()
+        .map(|d| d.display().to_string())
+        .collect();
+    println!("library path: {}", dirs.join(":"));
+    let runtimes = Runtime::detect(dir);
+    for runtime in &runtimes {
+        println!(
+            "steam runtime: {} at {}",
+            runtime.kind.name(),
+            runtime.root.display()
+        );
+    }
+
+    let analysis = analyze(&game);
+    println!("libraries:");
+    for line in &analysis.tree {
+        println!("{}", line);
+    }
+    let mut suggestions = Vec::new();
+    for (path, _) in &analysis.damaged {
+        suggestions.push(format!(
+            "{} looks damaged, verify the game files",
+            path.display()
+        ));
+    }
+    for (name, by) in &analysis.missing {
+        suggestions.push(missing_library(name, by, &game));
+    }
+    if let (false, Some(runtime)) = (analysis.missing.is_empty(), runtimes.first()) {
+        let mode = match runtime.kind {
+            Kind::Scout => "libraries",
+            Kind::Soldier | Kind::Sniper => "container",
+        };
+        suggestions.push(format!(
+            "the {} Steam Runtime may have what's missing, try --steam-runtime {}",
+            runtime.kind.name(),
+            mode
+        ));
+    }
+
+    if !analysis.unresolved.is_empty() {
+        println!("undefined symbols:");
+    }
+    for (path, unresolved) in &analysis.unresolved {
+        let listed: Vec<_> = unresolved
+            .iter()
+            .take(MAX_LISTED)
+            .map(|s| s.name.as_str())
+            .collect();
+        let more = match unresolved.len().saturating_sub(MAX_LISTED) {
+            0 => String::new(),
+            n => format!(" and {} more", n),
+        };
+        println!("  {}: {}{}", path.display(), listed.join(", "), more);
+        // Missing libraries explain missing symbols well enough
+        if analysis.missing.is_empty() {
+            suggestions.push(format!(
+                "{} expects {} symbol(s) none of its libraries define, one of them is likely from \
+                 another build of the game",
+                file_name(path),
+                unresolved.len()
+            ));
+        }
+    }
+
+    // What the files don't show: constructors, versioned symbols, the lot
+    let dependencies = game.dependencies();
+    let path = game.client.to_string_lossy().into_owned();
+    let outcome = worker::run(timeout, |sender| {
+        dumper::preload(sender, &dependencies.clone()?)?;
+        dumper::load(sender, &path)
+    });
+    match outcome {
+        Ok(outcome) => match outcome.error {
+            None => println!("load: ok"),
+            Some(error) => {
+                println!("load: FAIL: {}", error);
+                if suggestions.is_empty() {
+                    suggestions.push(
+                        "loading fails even though every library and symbol is there, the \
+                         client's constructors may need more than this machine has"
+                            .to_string(),
+                    );
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("error: failed to start the worker process: {}", e);
+            return report::EXIT_FAILED;
+        }
+    }
+
+    if suggestions.is_empty() {
+        println!("ok: nothing wrong found");
+        return 0;
+    }
+    println!("suggestions:");
+    for suggestion in &suggestions {
+        println!("  - {}", suggestion);
+    }
+    report::EXIT_DOCTOR_FOUND_PROBLEMS
+}
+
+fn missing_library(name: &str, by: &Path, game: &Game) -> String {
+    if name.ends_with("_client.so") || GAME_LIBRARIES.contains(&name) {
+        format!(
+            "{} (needed by {}) should come with the game, verify the game files or copy it into {}",
+            name,
+            file_name(by),
+            game.library_dirs.first().map_or_else(
+                || "the game's bin directory".to_string(),
+                |d| d.display().to_string()
+            )
+        )
+    } else {
+        format!(
+            "{} (needed by {}) is a system library, install the package providing it, 64-bit",
+            name,
+            file_name(by)
+        )
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,753 @@
+//! The part of a dump that runs inside the worker: loading a library,
+//! finding its class list and walking it.
+
+use crate::discover;
+use crate::elf::ElfFile;
+use crate::interface;
+use crate::makesig;
+use crate::memory::Memory;
+use crate::module::{self, Module};
+use crate::pattern::Pattern;
+use crate::repair::{self, Repair};
+use crate::report::{Problem, ProblemKind};
+use crate::resolve::{self, Rel32, Step};
+use crate::sdk::ClientClass;
+use crate::signature::{Region, Signature, SignatureMatch};
+use crate::symbols::Symbolizer;
+use crate::walk::{self, Event};
+use crate::worker::Sender;
+use crate::yara_rules::RuleSet;
+use libc::c_void;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString};
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// `g_pClientClassHead`, referenced by `mov rax, [rip + rel32]`.
+pub const CLASS_HEAD_SIGNATURE: &str = "91 48 8B 05 ? ? ? ? 8B 53 14";
+pub const CLASS_HEAD_TARGET: Rel32 = Rel32 {
+    disp_offset: 4,
+    instr_len: 8,
+};
+
+pub fn load(sender: &mut Sender, library: &str) -> Result<(), String> {
+    let name = CString::new(library).map_err(|_| format!("invalid library name: {}", library))?;
+    let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_LAZY | libc::RTLD_GLOBAL) };
+    if handle.is_null() {
+        return Err(format!("failed to load {}: {}", library, dlerror()));
+    }
+    sender.log(format!("Client: {:?}", handle));
+    Ok(())
+}
+
+/// Loads `libraries` in order and reports every symbol they need that
+/// nothing provides.
+///
+/// Each one is first loaded with `RTLD_NOW`, whose error only names the
+/// first such symbol, and lazily if that fails. Once everything is in,
+/// `dlsym` is asked about every symbol each of them needs.
+pub fn probe_symbols(sender: &mut Sender, libraries: &[PathBuf]) -> Result<(), String> {
+    for library in libraries {
+        let name = CString::new(library.as_os_str().as_bytes())
+            .map_err(|_| format!("invalid library name: {}", library.display()))?;
+        let now = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL) };
+        if !now.is_null() {
+            sender.log(format!("RTLD_NOW {}: ok", library.display()));
+            continue;
+        }
+        sender.log(format!("RTLD_NOW {}: {}", library.display(), dlerror()));
+        let lazy = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_LAZY | libc::RTLD_GLOBAL) };
+        if lazy.is_null() {
+            return Err(format!(
+                "failed to load {}: {}",
+                library.display(),
+                dlerror()
+            ));
+        }
+    }
+
+    for library in libraries {
+        let elf = ElfFile::open(library)?;
+        let unresolved: Vec<_> = elf
+            .undefined
+            .iter()
+            .filter(|symbol| {
+                let name = match CString::new(symbol.name.as_str()) {
+                    Ok(name) => name,
+                    Err(_) => return false,
+                };
+                unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) }.is_null()
+            })
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        if !unresolved.is_empty() {
+            sender.event(Event::Problem(Problem {
+                kind: ProblemKind::UnresolvedSymbols,
+                location: library.display().to_string(),
+                reason: format!(
+                    "{} symbol(s) can't be resolved: {}",
+                    unresolved.len(),
+                    unresolved.join(", ")
+                ),
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// Why the last `dlopen` failed.
+fn dlerror() -> String {
+    let error = unsafe { libc::dlerror() };
+    if error.is_null() {
+        return "unknown error".to_string();
+    }
+    unsafe { CStr::from_ptr(error) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Loads `libraries` in order and keeps them loaded, so whatever comes
+/// next finds its dependencies already there.
+pub fn preload(sender: &mut Sender, libraries: &[PathBuf]) -> Result<(), String> {
+    open_all(sender, libraries, "preload", "Preloaded")
+}
+
+/// Loads the other modules to scan, after the client.
+pub fn load_modules(sender: &mut Sender, libraries: &[PathBuf]) -> Result<(), String> {
+    open_all(sender, libraries, "load", "Loaded")
+}
+
+fn open_all(
+    sender: &mut Sender,
+    libraries: &[PathBuf],
+    verb: &str,
+    done: &str,
+) -> Result<(), String> {
+    for library in libraries {
+        let name = CString::new(library.as_os_str().as_bytes())
+            .map_err(|_| format!("invalid library name: {}", library.display()))?;
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_LAZY | libc::RTLD_GLOBAL) };
+        if handle.is_null() {
+            return Err(format!(
+                "failed to {} {}: {}",
+                verb,
+                library.display(),
+                dlerror()
+            ));
+        }
+        sender.log(format!("{} {}: {:?}", done, library.display(), handle));
+    }
+    Ok(())
+}
+
+/// `g_pClientClassHead` itself, if the library wasn't stripped.
+pub const CLASS_HEAD_SYMBOL: &str = "g_pClientClassHead";
+/// `CHLClient::GetAllClasses()`, which does nothing but return
+/// `g_pClientClassHead`.
+pub const GET_ALL_CLASSES_SYMBOL: &str = "_ZN9CHLClient13GetAllClassesEv";
+
+/// A way of finding the class list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// [`CLASS_HEAD_SYMBOL`] or [`GET_ALL_CLASSES_SYMBOL`].
+    Symbols,
+    /// [`CLASS_HEAD_SIGNATURE`].
+    Signature,
+    /// `GetAllClasses()` of the client's interface, see [`interface`].
+    Interface,
+    /// Whatever relocated pointer leads to the longest class list, see
+    /// [`discover`].
+    Relocations,
+}
+
+/// Cheapest and most reliable first.
+pub const DEFAULT_STRATEGIES: &[Strategy] = &[
+    Strategy::Symbols,
+    Strategy::Signature,
+    Strategy::Interface,
+    Strategy::Relocations,
+];
+
+impl Strategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Symbols => "symbols",
+            Strategy::Signature => "signature",
+            Strategy::Interface => "interface",
+            Strategy::Relocations => "relocations",
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Strategy::Symbols,
+            Strategy::Signature,
+            Strategy::Interface,
+            Strategy::Relocations,
+        ]
+        .iter()
+        .copied()
+        .find(|strategy| strategy.name() == s)
+        .ok_or_else(|| {
+            format!(
+                "unknown strategy {:?}, expected symbols, signature, interface or relocations",
+                s
+            )
+        })
+    }
+}
+
+/// What to look for besides the class list, and how to find the list.
+#[derive(Clone, Copy)]
+pub struct Locators<'a> {
+    pub signatures: &'a [Signature],
+    pub rules: Option<&'a RuleSet>,
+    /// Tried in order until one finds the list.
+    pub strategies: &'a [Strategy],
+    /// The build the signatures last matched in, to repair them from.
+    pub previous: Option<&'a ElfFile>,
+}
+
+/// Finds the loaded module whose name ends with `module_name` and streams
+/// its classes to `sender`, along with whatever `locators` found.
+pub fn dump_module(
+    sender: &mut Sender,
+    module_name: &str,
+    locators: &Locators,
+) -> Result<(), String> {
+    sender.log(format!("Pagesize: {:#X}", module::pagesize()));
+
+    let memory = Memory::open().map_err(|e| format!("failed to open /proc/self/mem: {}", e))?;
+    let modules = module::modules();
+    let module = find_module(&modules, module_name)?;
+    let head_pattern: Pattern = CLASS_HEAD_SIGNATURE
+        .parse()
+        .map_err(|e| format!("invalid signature {:?}: {}", CLASS_HEAD_SIGNATURE, e))?;
+    let elf = open_elf(sender, module);
+
+    let (matches, found) = find(
+        sender,
+        &memory,
+        module,
+        &elf,
+        Some(head_pattern),
+        locators.signatures,
+    );
+    report_signatures(
+        sender,
+        &memory,
+        module,
+        &elf,
+        locators.signatures,
+        found,
+        locators.previous,
+    );
+
+    if let Some(rules) = locators.rules {
+        match_rules(sender, &memory, module, rules)?;
+    }
+
+    let mut head = None;
+    for &strategy in locators.strategies {
+        head = match (strategy, &elf) {
+            (Strategy::Symbols, Ok(elf)) => head_from_symbols(sender, &memory, module, elf),
+            (Strategy::Signature, _) => {
+                head_from_signature(sender, &memory, module, &matches, locators.previous, &elf)
+            }
+            (Strategy::Interface, Ok(elf)) => head_from_interface(sender, &memory, module, elf),
+            (Strategy::Relocations, Ok(elf)) => head_from_relocations(sender, &memory, module, elf),
+            (_, Err(_)) => None,
+        };
+        if head.is_some() {
+            break;
+        }
+        sender.log(format!(
+            "{} didn't lead to g_pClientClassHead",
+            strategy.name()
+        ));
+    }
+    let head = head.ok_or("couldn't find g_pClientClassHead")?;
+    let symbols = match &elf {
+        Ok(elf) => Symbolizer::new(module, elf),
+        Err(_) => Symbolizer::default(),
+    };
+    walk::walk(
+        &memory,
+        &modules,
+        &symbols,
+        head as *const ClientClass,
+        &mut |event| sender.event(event),
+    );
+    Ok(())
+}
+
+/// Looks for `signatures` in the loaded module whose name ends with
+/// `module_name`, one that has no class list of its own to dump.
+pub fn scan_module(
+    sender: &mut Sender,
+    module_name: &str,
+    signatures: &[Signature],
+) -> Result<(), String> {
+    let memory = Memory::open().map_err(|e| format!("failed to open /proc/self/mem: {}", e))?;
+    let modules = module::modules();
+    let module = find_module(&modules, module_name)?;
+    let elf = open_elf(sender, module);
+    let (_, found) = find(sender, &memory, module, &elf, None, signatures);
+    report_signatures(sender, &memory, module, &elf, signatures, found, None);
+    Ok(())
+}
+
+fn find_module<'a>(modules: &'a [Module], module_name: &str) -> Result<&'a Module, String> {
+    modules
+        .iter()
+        .find(|m| m.name.ends_with(module_name))
+        .ok_or_else(|| format!("{} isn't loaded", module_name))
+}
+
+/// Only needed for symbols and sections, so failing to read it is no
+/// reason to give up yet.
+fn open_elf(sender: &mut Sender, module: &Module) -> Result<ElfFile, String> {
+    let elf = ElfFile::open(&module.path());
+    if let Err(e) = &elf {
+        sender.log(e.clone());
+    }
+    elf
+}
+
+/// Reports what each of `signatures` `found`, repairing those that found
+/// nothing from `previous`.
+fn report_signatures(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    elf: &Result<ElfFile, String>,
+    signatures: &[Signature],
+    found: Vec<Option<Vec<usize>>>,
+    previous: Option<&ElfFile>,
+) {
+    // Of alternatives sharing a name, the first that matched counts, or
+    // the first at all if none did
+    let mut chosen: HashMap<&str, usize> = HashMap::new();
+    for (index, signature) in signatures.iter().enumerate() {
+        let matched = |index: usize| found[index].as_ref().is_some_and(|f| !f.is_empty());
+        let entry = chosen.entry(&signature.name).or_insert(index);
+        if !matched(*entry) && matched(index) {
+            *entry = index;
+        }
+    }
+    for (index, (signature, found)) in signatures.iter().zip(found).enumerate() {
+        if chosen[signature.name.as_str()] != index {
+            continue;
+        }
+        // Signatures that couldn't be searched for have been reported already
+        let searched = found.is_some();
+        let mut found = found.unwrap_or_default();
+        let mut resolve = signature.resolve;
+        let mut repaired = None;
+        if searched && found.is_empty() {
+            let region = match &signature.region {
+                Some(region) => format!(" in {}", region),
+                None => String::new(),
+            };
+            match try_repair(previous, elf, &signature.pattern, resolve) {
+                Some(Ok(repair)) => {
+                    // Keep the region unless the code moved out of it
+                    let kept = signature.region.clone().filter(|region| {
+                        matches!(resolve_region(module, region, elf), Ok(r) if r.contains(&repair.start))
+                    });
+                    let replacement = Signature {
+                        name: signature.name.clone(),
+                        module: signature.module.clone(),
+                        pattern: repair.pattern,
+                        region: kept,
+                        resolve: repair.resolve,
+                        steps: signature.steps.clone(),
+                    };
+                    sender.event(Event::Problem(Problem {
+                        kind: ProblemKind::SignatureRepaired,
+                        location: signature.name.clone(),
+                        reason: format!(
+                            "{} doesn't match anywhere{}, but the previous build places it at +{:#X} ({} of {} runs agree): {}",
+                            signature.pattern, region, repair.start, repair.votes.0, repair.votes.1, replacement
+                        ),
+                    }));
+                    found = vec![module.address + repair.start];
+                    resolve = replacement.resolve;
+                    repaired = Some(replacement.to_string());
+                }
+                failed => {
+                    let repair = match failed {
+                        Some(Err(e)) => format!(", and it couldn't be repaired: {}", e),
+                        _ => String::new(),
+                    };
+                    sender.event(Event::Problem(Problem {
+                        kind: ProblemKind::SignatureNotFound,
+                        location: signature.name.clone(),
+                        reason: format!(
+                            "{} doesn't match anywhere{}{}",
+                            signature.pattern, region, repair
+                        ),
+                    }));
+                }
+            }
+        }
+        check_unique(sender, module, &signature.name, &found);
+        let targets = if resolve.is_some() || !signature.steps.is_empty() {
+            found
+                .iter()
+                .filter_map(|&m| {
+                    let target = match resolve {
+                        Some(rel32) => rel32.resolve(memory, m),
+                        None => Ok(m),
+                    }
+                    .and_then(|address| resolve::follow(memory, address, &signature.steps));
+                    match target {
+                        Ok(value) if signature.steps.last() == Some(&Step::Value) => Some(value),
+                        Ok(target) => Some(target.wrapping_sub(module.address)),
+                        Err(e) => {
+                            sender.event(Event::Problem(Problem {
+                                kind: ProblemKind::UnreadableMemory,
+                                location: signature.name.clone(),
+                                reason: format!("failed to resolve the match at {:#X}: {}", m, e),
+                            }));
+                            None
+                        }
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        sender.event(Event::Signature(SignatureMatch {
+            name: signature.name.clone(),
+            module: module.name.clone(),
+            matches: found.iter().map(|m| m - module.address).collect(),
+            targets,
+            repaired,
+        }));
+    }
+}
+
+/// Looks up the class list through whichever of [`CLASS_HEAD_SYMBOL`] and
+/// [`GET_ALL_CLASSES_SYMBOL`] the library still has.
+fn head_from_symbols(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    elf: &ElfFile,
+) -> Option<usize> {
+    if let Some(symbol) = elf.symbol(CLASS_HEAD_SYMBOL).filter(|s| !s.function) {
+        let slot = module.address + symbol.address;
+        match unsafe { memory.read::<usize>(slot) } {
+            Ok(head) => {
+                sender.log(format!(
+                    "g_pClientClassHead: {:#X} (+{:#X}), from the symbol table",
+                    slot, symbol.address
+                ));
+                return Some(head);
+            }
+            Err(e) => sender.log(format!("failed to read {}: {}", CLASS_HEAD_SYMBOL, e)),
+        }
+    }
+    if let Some(symbol) = elf.symbol(GET_ALL_CLASSES_SYMBOL).filter(|s| s.function) {
+        let address = module.address + symbol.address;
+        sender.log(format!(
+            "CHLClient::GetAllClasses: {:#X} (+{:#X}), from the symbol table",
+            address, symbol.address
+        ));
+        // A member function, but one that never looks at `this`. Should it
+        // crash anyway, the worker takes the fall.
+        let get_all_classes: extern "C" fn(*const c_void) -> *const ClientClass =
+            unsafe { std::mem::transmute(address) };
+        return Some(get_all_classes(std::ptr::null()) as usize);
+    }
+    None
+}
+
+fn head_from_signature(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    matches: &[usize],
+    previous: Option<&ElfFile>,
+    elf: &Result<ElfFile, String>,
+) -> Option<usize> {
+    check_unique(sender, module, "g_pClientClassHead", matches);
+    let (client, target) = match matches.first() {
+        Some(&client) => (client, CLASS_HEAD_TARGET),
+        None => {
+            let pattern = CLASS_HEAD_SIGNATURE.parse().ok()?;
+            match try_repair(previous, elf, &pattern, Some(CLASS_HEAD_TARGET))? {
+                Ok(repair) => {
+                    let target = repair.resolve?;
+                    sender.event(Event::Problem(Problem {
+                        kind: ProblemKind::SignatureRepaired,
+                        location: "g_pClientClassHead".to_string(),
+                        reason: format!(
+                            "{} doesn't match anymore, but the previous build places it at +{:#X} ({} of {} runs agree): {} -> {}",
+                            CLASS_HEAD_SIGNATURE, repair.start, repair.votes.0, repair.votes.1, repair.pattern, target
+                        ),
+                    }));
+                    (module.address + repair.start, target)
+                }
+                Err(e) => {
+                    sender.log(format!(
+                        "failed to repair the g_pClientClassHead signature: {}",
+                        e
+                    ));
+                    return None;
+                }
+            }
+        }
+    };
+    let slot = match target.resolve(memory, client) {
+        Ok(slot) => slot,
+        Err(e) => {
+            sender.log(format!("failed to resolve g_pClientClassHead: {}", e));
+            return None;
+        }
+    };
+    sender.log(format!(
+        "g_pClientClassHead: {:#X} (+{:#X}), referenced at {:#X}",
+        slot,
+        slot.wrapping_sub(module.address),
+        client
+    ));
+    match unsafe { memory.read::<usize>(slot) } {
+        Ok(head) => Some(head),
+        Err(e) => {
+            sender.log(format!("failed to read g_pClientClassHead: {}", e));
+            None
+        }
+    }
+}
+
+fn head_from_interface(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    elf: &ElfFile,
+) -> Option<usize> {
+    let found = match interface::class_head(memory, module, elf) {
+        Ok(found) => found,
+        Err(e) => {
+            sender.log(e);
+            return None;
+        }
+    };
+    let from = match found.slot {
+        Some(slot) => format!(
+            "{:#X} (+{:#X}), read by",
+            slot,
+            slot.wrapping_sub(module.address)
+        ),
+        None => "returned by".to_string(),
+    };
+    sender.log(format!(
+        "g_pClientClassHead: {} {}::GetAllClasses at {:#X} (+{:#X})",
+        from,
+        found.name,
+        found.get_all_classes,
+        found.get_all_classes - module.address
+    ));
+    if discover::list_len(memory, module, found.head) == 0 {
+        sender.log(format!(
+            "{:#X} from {} isn't a list of classes",
+            found.head, found.name
+        ));
+        return None;
+    }
+    Some(found.head)
+}
+
+fn head_from_relocations(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    elf: &ElfFile,
+) -> Option<usize> {
+    let candidates = discover::from_relocations(memory, module, elf);
+    let first = *candidates.first()?;
+    let heads: HashSet<_> = candidates.iter().map(|c| c.head).collect();
+    if heads.len() > 1 {
+        let slots: Vec<_> = candidates
+            .iter()
+            .map(|c| format!("{:#X} (+{:#X})", c.slot, c.slot - module.address))
+            .collect();
+        sender.event(Event::Problem(Problem {
+            kind: ProblemKind::AmbiguousSignature,
+            location: "g_pClientClassHead".to_string(),
+            reason: format!(
+                "{} different lists of {} classes, using the first: {}",
+                heads.len(),
+                first.classes,
+                slots.join(", ")
+            ),
+        }));
+    }
+    sender.log(format!(
+        "g_pClientClassHead: {:#X} (+{:#X}), holds the longest list in relocated memory ({} classes)",
+        first.slot,
+        first.slot - module.address,
+        first.classes
+    ));
+    Some(first.head)
+}
+
+/// Finds `head`, if there is one, and every signature, returning the
+/// matches for `head` and those for the signatures in order, `None` for signatures
+/// whose region couldn't be found.
+///
+/// The head and every signature that may match anywhere in the code share
+/// one pass over the image. Signatures confined to a region get a pass per
+/// region, which is cheap since regions are small.
+fn find(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    elf: &Result<ElfFile, String>,
+    head: Option<Pattern>,
+    signatures: &[Signature],
+) -> (Vec<usize>, Vec<Option<Vec<usize>>>) {
+    let mut found = vec![None; signatures.len()];
+    let with_head = head.is_some();
+    let mut anywhere = (head.into_iter().collect::<Vec<_>>(), Vec::new());
+    let mut regions: HashMap<Range<usize>, (Vec<Pattern>, Vec<usize>)> = HashMap::new();
+
+    for (index, signature) in signatures.iter().enumerate() {
+        let (patterns, indices) = match &signature.region {
+            None => &mut anywhere,
+            Some(region) => match resolve_region(module, region, elf) {
+                Ok(range) => regions.entry(range).or_default(),
+                Err(reason) => {
+                    sender.event(Event::Problem(Problem {
+                        kind: ProblemKind::SignatureNotFound,
+                        location: signature.name.clone(),
+                        reason,
+                    }));
+                    continue;
+                }
+            },
+        };
+        patterns.push(signature.pattern.clone());
+        indices.push(index);
+    }
+
+    let mut results = module.scan(memory, &anywhere.0).into_iter();
+    let head = if with_head {
+        results.next().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    for (index, matches) in anywhere.1.into_iter().zip(results) {
+        found[index] = Some(matches);
+    }
+    for (range, (patterns, indices)) in regions {
+        let results = module.scan_range(memory, &patterns, range);
+        for (index, matches) in indices.into_iter().zip(results) {
+            found[index] = Some(matches);
+        }
+    }
+    (head, found)
+}
+
+/// Repairs `pattern` from `previous`, if there is one to repair it from.
+fn try_repair(
+    previous: Option<&ElfFile>,
+    elf: &Result<ElfFile, String>,
+    pattern: &Pattern,
+    resolve: Option<Rel32>,
+) -> Option<Result<Repair, String>> {
+    let previous = previous?;
+    Some(match elf {
+        Ok(elf) => repair::repair(previous, elf, pattern, resolve, makesig::DEFAULT_MAX_LEN),
+        Err(e) => Err(e.clone()),
+    })
+}
+
+/// Turns `region` into offsets from the module's base.
+fn resolve_region(
+    module: &Module,
+    region: &Region,
+    elf: &Result<ElfFile, String>,
+) -> Result<Range<usize>, String> {
+    let name = match region {
+        Region::Range(range) => {
+            return match module.absolute(range) {
+                Some(_) => Ok(range.clone()),
+                None => Err(format!("{} is past the end of {}", region, module.name)),
+            }
+        }
+        Region::Section(name) => name,
+    };
+    let elf = elf.as_ref().map_err(Clone::clone)?;
+    let section = elf
+        .section(name)
+        .ok_or_else(|| format!("{} has no section {}", module.name, name))?;
+    if section.range.is_empty() {
+        return Err(format!("section {} isn't loaded", name));
+    }
+    Ok(section.range.clone())
+}
+
+/// Runs `rules` over the module's code and reports each rule like a
+/// signature.
+fn match_rules(
+    sender: &mut Sender,
+    memory: &Memory,
+    module: &Module,
+    rules: &RuleSet,
+) -> Result<(), String> {
+    let mut found: Vec<_> = rules.names().into_iter().map(|n| (n, Vec::new())).collect();
+    for (start, bytes) in module.code(memory) {
+        for (name, offsets) in rules.scan(&bytes)? {
+            if let Some((_, all)) = found.iter_mut().find(|(n, _)| *n == name) {
+                all.extend(offsets.into_iter().map(|offset| start + offset));
+            }
+        }
+    }
+
+    for (name, matches) in found {
+        if matches.is_empty() {
+            sender.event(Event::Problem(Problem {
+                kind: ProblemKind::SignatureNotFound,
+                location: name.clone(),
+                reason: "YARA rule doesn't match anywhere".to_string(),
+            }));
+        }
+        check_unique(sender, module, &name, &matches);
+        sender.event(Event::Signature(SignatureMatch {
+            name,
+            module: module.name.clone(),
+            matches: matches.iter().map(|m| m - module.address).collect(),
+            targets: Vec::new(),
+            repaired: None,
+        }));
+    }
+    Ok(())
+}
+
+/// Reports every candidate if a signature matched more than once.
+fn check_unique(sender: &mut Sender, module: &Module, name: &str, matches: &[usize]) {
+    if matches.len() > 1 {
+        let candidates: Vec<_> = matches
+            .iter()
+            .map(|m| format!("{:#X} (+{:#X})", m, m - module.address))
+            .collect();
+        sender.event(Event::Problem(Problem {
+            kind: ProblemKind::AmbiguousSignature,
+            location: name.to_string(),
+            reason: format!(
+                "signature matched {} times, using the first: {}",
+                matches.len(),
+                candidates.join(", ")
+            ),
+        }));
+    }
+}
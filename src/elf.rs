@@ -0,0 +1,179 @@
+//! The parts of a module's ELF file we need that the loader doesn't keep
+//! around, like section headers and the full symbol table.
+
+use crate::mapping::Mapping;
+use goblin::elf::Elf;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    /// Relative to the module's base, empty for sections that aren't loaded.
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// As stored, i.e. still mangled.
+    pub name: String,
+    /// Relative to the module's base.
+    pub address: usize,
+    pub size: usize,
+    pub function: bool,
+    /// From `.dynsym`, so other modules can link against it.
+    pub exported: bool,
+}
+
+/// A `PT_LOAD` segment as stored in the file.
+#[derive(Debug, Clone)]
+pub struct FileSegment {
+    /// Relative to the module's base, only the part backed by the file.
+    pub range: Range<usize>,
+    pub offset: usize,
+    pub execute: bool,
+}
+
+#[derive(Debug)]
+pub struct ElfFile {
+    /// The whole file, mapped.
+    pub bytes: Mapping,
+    pub segments: Vec<FileSegment>,
+    pub sections: Vec<Section>,
+    /// Defined symbols from both `.dynsym` and `.symtab`, if the latter
+    /// survived stripping.
+    pub symbols: Vec<Symbol>,
+    /// Where the dynamic relocations write to, relative to the module's
+    /// base. Every one of these holds a pointer once the module is loaded.
+    pub relocations: Vec<usize>,
+    /// `DT_NEEDED` entries in the order the loader goes through them.
+    pub needed: Vec<String>,
+    /// `DT_RUNPATH` and `DT_RPATH` entries, `$ORIGIN` and all.
+    pub runpath: Vec<String>,
+    /// Symbols some other module has to provide. Weak ones are left out,
+    /// those are fine to go without.
+    pub undefined: Vec<Symbol>,
+}
+
+impl ElfFile {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let bytes = Mapping::open(path)?;
+        let elf =
+            Elf::parse(&bytes).map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+
+        let sections = elf
+            .section_headers
+            .iter()
+            .map(|header| {
+                let start = header.sh_addr as usize;
+                let end = if header.is_alloc() {
+                    start + header.sh_size as usize
+                } else {
+                    start
+                };
+                Section {
+                    name: elf
+                        .shdr_strtab
+                        .get_at(header.sh_name)
+                        .unwrap_or_default()
+                        .to_string(),
+                    range: start..end,
+                }
+            })
+            .collect();
+
+        let dynamic = elf.dynsyms.iter().map(|sym| (sym, &elf.dynstrtab, true));
+        let local = elf.syms.iter().map(|sym| (sym, &elf.strtab, false));
+        let symbols = dynamic
+            .chain(local)
+            .filter(|(sym, _, _)| sym.st_shndx != 0 && sym.st_value != 0)
+            .filter_map(|(sym, strtab, exported)| {
+                Some(Symbol {
+                    name: strtab.get_at(sym.st_name)?.to_string(),
+                    address: sym.st_value as usize,
+                    size: sym.st_size as usize,
+                    function: sym.is_function(),
+                    exported,
+                })
+            })
+            .filter(|sym| !sym.name.is_empty())
+            .collect();
+        let relocations = elf
+            .dynrelas
+            .iter()
+            .chain(elf.dynrels.iter())
+            .chain(elf.pltrelocs.iter())
+            .map(|reloc| reloc.r_offset as usize)
+            .collect();
+        let segments = elf
+            .program_headers
+            .iter()
+            .filter(|header| header.p_type == goblin::elf::program_header::PT_LOAD)
+            .filter(|header| header.p_offset + header.p_filesz <= bytes.len() as u64)
+            .map(|header| FileSegment {
+                range: header.p_vaddr as usize..(header.p_vaddr + header.p_filesz) as usize,
+                offset: header.p_offset as usize,
+                execute: header.is_executable(),
+            })
+            .collect();
+        let needed = elf.libraries.iter().map(|name| name.to_string()).collect();
+        let runpath = elf
+            .runpaths
+            .iter()
+            .chain(&elf.rpaths)
+            .flat_map(|path| path.split(':'))
+            .map(str::to_string)
+            .collect();
+        let undefined = elf
+            .dynsyms
+            .iter()
+            .filter(|sym| sym.st_shndx == 0 && sym.st_bind() != goblin::elf::sym::STB_WEAK)
+            .filter_map(|sym| {
+                Some(Symbol {
+                    name: elf.dynstrtab.get_at(sym.st_name)?.to_string(),
+                    address: 0,
+                    size: sym.st_size as usize,
+                    function: sym.st_type() != goblin::elf::sym::STT_OBJECT,
+                    exported: false,
+                })
+            })
+            .filter(|sym| !sym.name.is_empty())
+            .collect();
+        Ok(ElfFile {
+            bytes,
+            segments,
+            sections,
+            symbols,
+            relocations,
+            needed,
+            runpath,
+            undefined,
+        })
+    }
+
+    /// The file's bytes for the executable segments, with where they're
+    /// loaded relative to the module's base.
+    pub fn code(&self) -> impl Iterator<Item = (usize, &[u8])> {
+        self.segments.iter().filter(|s| s.execute).map(move |s| {
+            let len = s.range.end - s.range.start;
+            (s.range.start, &self.bytes[s.offset..s.offset + len])
+        })
+    }
+
+    /// The file's bytes from `address`, relative to the module's base, to
+    /// the end of the segment it's in.
+    pub fn bytes_at(&self, address: usize) -> Option<&[u8]> {
+        let segment = self.segments.iter().find(|s| s.range.contains(&address))?;
+        let offset = segment.offset + (address - segment.range.start);
+        let end = segment.offset + (segment.range.end - segment.range.start);
+        self.bytes.get(offset..end)
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+}
@@ -0,0 +1,101 @@
+//! The environment the game library is loaded in.
+//!
+//! Hooks like `LD_PRELOAD` and `LD_AUDIT` put someone else's code into our
+//! process before `main` even runs, where it can patch whatever the game
+//! library does on load. Overlays and injectors are what usually sets them,
+//! so they're dropped unless asked not to. The loader only reads its
+//! variables at startup, so changing any of them means executing ourselves
+//! again.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Variables that have the loader run extra code or change how it binds.
+const LOADER_HOOKS: &[&str] = &[
+    "LD_PRELOAD",
+    "LD_AUDIT",
+    "LD_PROFILE",
+    "LD_PROFILE_OUTPUT",
+    "LD_BIND_NOT",
+    "LD_DYNAMIC_WEAK",
+    "LD_ASSUME_KERNEL",
+    "LD_HWCAP_MASK",
+    "LD_ORIGIN_PATH",
+    "LD_USE_LOAD_BIAS",
+    "GLIBC_TUNABLES",
+    "MALLOC_CHECK_",
+    "MALLOC_PERTURB_",
+];
+/// All that's kept of the environment with [`Policy::Minimal`].
+const MINIMAL: &[&str] = &[
+    "HOME",
+    "PATH",
+    "USER",
+    "LOGNAME",
+    "TMPDIR",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "LD_LIBRARY_PATH",
+    "STEAM_RUNTIME",
+    "NETVARS_RS_IN_STEAM_RUNTIME",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Leave everything as it is, hooks included.
+    Keep,
+    /// Drop [`LOADER_HOOKS`].
+    Scrub,
+    /// Drop everything but [`MINIMAL`].
+    Minimal,
+}
+
+impl Policy {
+    fn drops(self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        match self {
+            Policy::Keep => false,
+            Policy::Scrub => LOADER_HOOKS.contains(&&*name),
+            Policy::Minimal => !MINIMAL.contains(&&*name),
+        }
+    }
+}
+
+/// Makes sure `LD_LIBRARY_PATH` starts with `library_dirs` and nothing
+/// `policy` drops is set, re-executing the current process if need be.
+/// Only returns on failure, or if there was nothing to do.
+pub fn ensure(library_dirs: &[PathBuf], policy: Policy) -> io::Result<()> {
+    let current: Vec<PathBuf> = env::var_os("LD_LIBRARY_PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_default();
+    let dropped: Vec<OsString> = env::vars_os()
+        .map(|(name, _)| name)
+        .filter(|name| policy.drops(name))
+        .collect();
+    if current.starts_with(library_dirs) && dropped.is_empty() {
+        return Ok(());
+    }
+
+    let path = env::join_paths(library_dirs.iter().chain(&current))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut args = env::args_os();
+    let arg0 = args.next().unwrap_or_else(|| OsString::from("netvars-rs"));
+    let mut command = Command::new("/proc/self/exe");
+    command.arg0(arg0).args(args).env("LD_LIBRARY_PATH", path);
+    if !dropped.is_empty() {
+        let names: Vec<_> = dropped.iter().map(|n| n.to_string_lossy()).collect();
+        // Only what could change the result is worth pointing out
+        if policy == Policy::Scrub {
+            eprintln!("note: unset {} before loading", names.join(", "));
+        }
+        for name in &dropped {
+            command.env_remove(name);
+        }
+    }
+    Err(command.exec())
+}
@@ -0,0 +1,191 @@
+//! Downloading a build with SteamCMD or DepotDownloader to dump it, for
+//! servers without a Steam client and for builds Steam no longer installs.
+//!
+//! Without depots the app is installed or updated like Steam would, to the
+//! latest build of its branch. Giving depots, with the manifest id of an
+//! old build or without to get the latest, downloads just those, which is
+//! how older builds are still to be had. SteamCMD puts every depot under
+//! `steamapps/content` in a directory of its own; they're moved together
+//! so the libraries end up where the game would have them.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Who logs in when no user is given. Only free apps can be had this way.
+pub const ANONYMOUS: &str = "anonymous";
+
+#[derive(Debug, Clone)]
+pub enum Tool {
+    SteamCmd(PathBuf),
+    DepotDownloader(PathBuf),
+}
+
+impl Default for Tool {
+    fn default() -> Self {
+        Tool::SteamCmd(PathBuf::from("steamcmd"))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Depot {
+    pub id: u32,
+    /// The latest if `None`.
+    pub manifest: Option<u64>,
+}
+
+impl FromStr for Depot {
+    type Err = String;
+
+    /// `<depot>` or `<depot>=<manifest>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid depot {:?}, expected <depot>[=<manifest>]", s);
+        let (id, manifest) = match s.split_once('=') {
+            Some((id, manifest)) => (id, Some(manifest.trim().parse().map_err(|_| error())?)),
+            None => (s, None),
+        };
+        Ok(Depot {
+            id: id.trim().parse().map_err(|_| error())?,
+            manifest,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fetch {
+    pub app: u32,
+    pub depots: Vec<Depot>,
+    pub branch: Option<String>,
+    pub username: String,
+    pub tool: Tool,
+}
+
+impl Fetch {
+    /// Where the build goes when no directory is given: `fetched/<app>`,
+    /// with the manifest ids asked for so older builds don't overwrite each
+    /// other.
+    pub fn default_dir(&self) -> PathBuf {
+        let mut name = self.app.to_string();
+        for manifest in self.depots.iter().filter_map(|depot| depot.manifest) {
+            name += &format!("-{}", manifest);
+        }
+        Path::new("fetched").join(name)
+    }
+
+    /// Downloads the build into `dir`. The tool's output goes to stderr,
+    /// leaving stdout to the dump.
+    pub fn run(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        // SteamCMD takes install directories relative to itself
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let commands = match &self.tool {
+            Tool::SteamCmd(program) => vec![self.steamcmd(program, &dir)],
+            Tool::DepotDownloader(program) => self.depotdownloader(program, &dir),
+        };
+        for mut command in commands {
+            let program = command.get_program().to_string_lossy().into_owned();
+            let status = command
+                .stdin(Stdio::inherit())
+                .stdout(io::stderr())
+                .status()
+                .map_err(|e| format!("failed to run {}: {}", program, e))?;
+            if !status.success() {
+                return Err(format!("{} failed with {}", program, status));
+            }
+        }
+        if let Tool::SteamCmd(_) = self.tool {
+            let content = dir
+                .join("steamapps/content")
+                .join(format!("app_{}", self.app));
+            for depot in &self.depots {
+                let from = content.join(format!("depot_{}", depot.id));
+                merge(&from, &dir)
+                    .map_err(|e| format!("failed to move {} into place: {}", from.display(), e))?;
+            }
+            // Only the emptied directories are left
+            fs::remove_dir_all(dir.join("steamapps/content")).ok();
+            fs::remove_dir(dir.join("steamapps")).ok();
+        }
+        Ok(())
+    }
+
+    fn steamcmd(&self, program: &Path, dir: &Path) -> Command {
+        let mut command = Command::new(program);
+        command
+            .args(["+@sSteamCmdForcePlatformType", "linux"])
+            .arg("+force_install_dir")
+            .arg(dir)
+            .args(["+login", &self.username]);
+        if self.depots.is_empty() {
+            command.args(["+app_update", &self.app.to_string()]);
+            if let Some(branch) = &self.branch {
+                command.args(["-beta", branch]);
+            }
+            command.arg("validate");
+        }
+        for depot in &self.depots {
+            command.args([
+                "+download_depot",
+                &self.app.to_string(),
+                &depot.id.to_string(),
+            ]);
+            command.args(depot.manifest.map(|manifest| manifest.to_string()));
+        }
+        command.arg("+quit");
+        command
+    }
+
+    /// One run per depot, or a single one for the whole app.
+    fn depotdownloader(&self, program: &Path, dir: &Path) -> Vec<Command> {
+        let depots: Vec<Option<&Depot>> = if self.depots.is_empty() {
+            vec![None]
+        } else {
+            self.depots.iter().map(Some).collect()
+        };
+        depots
+            .into_iter()
+            .map(|depot| {
+                let mut command = Command::new(program);
+                command
+                    .args(["-app", &self.app.to_string(), "-os", "linux", "-dir"])
+                    .arg(dir);
+                if let Some(depot) = depot {
+                    command.args(["-depot", &depot.id.to_string()]);
+                    if let Some(manifest) = depot.manifest {
+                        command.args(["-manifest", &manifest.to_string()]);
+                    }
+                }
+                if let Some(branch) = &self.branch {
+                    command.args(["-beta", branch]);
+                }
+                if self.username != ANONYMOUS {
+                    command.args(["-username", &self.username]);
+                }
+                command
+            })
+            .collect()
+    }
+}
+
+/// Moves everything in `from` into `to`, into the directories already
+/// there and over the files.
+fn merge(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() && target.is_dir() {
+            merge(&entry.path(), &target)?;
+        } else {
+            if target.is_dir() {
+                fs::remove_dir_all(&target)?;
+            }
+            fs::rename(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
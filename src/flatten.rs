@@ -0,0 +1,50 @@
+//! Flattening a class's nested tables into one list of props.
+//!
+//! Data table props (`baseclass`, embedded structs like `m_Local`) are just
+//! containers: their children live at the container's offset plus their own.
+//! Arrays sent as tables have props named `000`, `001`, …, which are named
+//! after their container instead so they stay distinguishable.
+
+use crate::walk::{Prop, Table};
+
+#[derive(Debug, Clone)]
+pub struct FlatProp<'a> {
+    pub name: String,
+    /// Offset from the start of the entity.
+    pub offset: i64,
+    pub prop: &'a Prop,
+}
+
+pub fn flatten(table: &Table) -> Vec<FlatProp<'_>> {
+    let mut props = Vec::new();
+    visit(table, 0, None, &mut props);
+    props
+}
+
+fn visit<'a>(table: &'a Table, base: i64, prefix: Option<&str>, out: &mut Vec<FlatProp<'a>>) {
+    for prop in &table.props {
+        // Described by the array prop that follows it
+        if prop.inside_array {
+            continue;
+        }
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, prop.name),
+            None => prop.name.clone(),
+        };
+        let offset = base + i64::from(prop.offset);
+
+        match &prop.table {
+            Some(child) if is_array(child) => visit(child, offset, Some(&name), out),
+            Some(child) => visit(child, offset, prefix, out),
+            None => out.push(FlatProp { name, offset, prop }),
+        }
+    }
+}
+
+fn is_array(table: &Table) -> bool {
+    !table.props.is_empty()
+        && table
+            .props
+            .iter()
+            .all(|p| !p.name.is_empty() && p.name.bytes().all(|b| b.is_ascii_digit()))
+}
@@ -0,0 +1,154 @@
+//! Finds the client library in a game installation, the other libraries to
+//! scan, and the directories the libraries they link against are in.
+
+use crate::elf::ElfFile;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Where clients live, relative to the game directory. The last entry is
+/// for being pointed at the directory holding the client itself.
+const CLIENT_DIRS: &[&str] = &["csgo/bin/linux64", "bin/linux64", ""];
+/// Newest name first: CS:GO's client was `client_panorama_client.so` for a
+/// while before turning back into `client_client.so`.
+const CLIENT_NAMES: &[&str] = &["client_panorama_client.so", "client_client.so"];
+/// Where the engine libraries the client needs are, relative to the game
+/// directory.
+const LIBRARY_DIRS: &[&str] = &["bin/linux64", "csgo/bin/linux64", "bin", ""];
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    /// Absolute path to the game directory.
+    pub dir: PathBuf,
+    /// Absolute path to the client library.
+    pub client: PathBuf,
+    /// Directories the client's dependencies are loaded from, in order.
+    pub library_dirs: Vec<PathBuf>,
+    /// Loaded before any of the client's dependencies, e.g. shims.
+    pub preload: Vec<PathBuf>,
+    /// Other libraries to scan, like the engine's, loaded after the client.
+    pub modules: Vec<PathBuf>,
+}
+
+impl Game {
+    /// Looks for a client library in `dir`.
+    pub fn locate(dir: &Path) -> Result<Self, String> {
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let root = &dir;
+        let client = CLIENT_DIRS
+            .iter()
+            .flat_map(|sub| {
+                CLIENT_NAMES
+                    .iter()
+                    .map(move |name| root.join(sub).join(name))
+            })
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                format!(
+                    "no client library in {}, looked for {} in {}",
+                    dir.display(),
+                    CLIENT_NAMES.join(" and "),
+                    CLIENT_DIRS
+                        .iter()
+                        .map(|sub| if sub.is_empty() { "." } else { sub })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+
+        let mut library_dirs: Vec<PathBuf> = Vec::new();
+        let candidates = std::iter::once(client.parent().map(Path::to_path_buf))
+            .flatten()
+            .chain(LIBRARY_DIRS.iter().map(|sub| dir.join(sub)));
+        for candidate in candidates {
+            if candidate.is_dir() && !library_dirs.contains(&candidate) {
+                library_dirs.push(candidate);
+            }
+        }
+        Ok(Game {
+            dir: dir.clone(),
+            client,
+            library_dirs,
+            preload: Vec::new(),
+            modules: Vec::new(),
+        })
+    }
+
+    /// Adds `library` to [`Game::modules`]: a path, or a file name to look
+    /// for in [`Game::library_dirs`].
+    pub fn add_module(&mut self, library: &str) -> Result<(), String> {
+        let path = if library.contains('/') {
+            Some(PathBuf::from(library)).filter(|path| path.is_file())
+        } else {
+            self.library_dirs
+                .iter()
+                .map(|dir| dir.join(library))
+                .find(|path| path.is_file())
+        };
+        let path = path
+            .and_then(|path| path.canonicalize().ok())
+            .ok_or_else(|| format!("no library {} in the game's library directories", library))?;
+        if path != self.client && !self.modules.contains(&path) {
+            self.modules.push(path);
+        }
+        Ok(())
+    }
+
+    /// The libraries the client and [`Game::modules`] need that come with
+    /// the game, each after the ones it needs itself, so they can be loaded
+    /// in this order. [`Game::preload`] goes first.
+    ///
+    /// Anything not found in [`Game::library_dirs`] is left to the loader,
+    /// that's the system's libraries.
+    pub fn dependencies(&self) -> Result<Vec<PathBuf>, String> {
+        let mut order = self.preload.clone();
+        let mut seen = HashSet::new();
+        seen.insert(self.client.clone());
+        seen.extend(self.modules.iter().cloned());
+        self.visit(&self.client, &mut order, &mut seen)?;
+        for module in &self.modules {
+            self.visit(module, &mut order, &mut seen)?;
+        }
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        library: &Path,
+        order: &mut Vec<PathBuf>,
+        seen: &mut HashSet<PathBuf>,
+    ) -> Result<(), String> {
+        for name in ElfFile::open(library)?.needed {
+            let found = self
+                .library_dirs
+                .iter()
+                .map(|dir| dir.join(&name))
+                .find(|path| path.is_file());
+            if let Some(found) = found {
+                if seen.insert(found.clone()) {
+                    self.visit(&found, order, seen)?;
+                    order.push(found);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The client's file name, which is how it shows up among the loaded
+    /// modules.
+    pub fn client_name(&self) -> String {
+        file_name(&self.client)
+    }
+
+    /// The file names of [`Game::modules`].
+    pub fn module_names(&self) -> Vec<String> {
+        self.modules.iter().map(|path| file_name(path)).collect()
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,173 @@
+//! Keeping dumps in a git repository, one commit per build, so the history
+//! of the offsets can be browsed and diffed with the usual tools.
+//!
+//! Next to the dump goes `offsets.json`, every prop and signature with its
+//! offset on a line of its own and nothing that changes between two dumps
+//! of the same build. That's what the diffs are worth looking at, and what
+//! the commit message sums up.
+
+use crate::history::Record;
+use crate::output::{self, Format};
+use crate::walk::Dump;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const OFFSETS: &str = "offsets.json";
+/// Changes listed in a commit message before the rest are only counted.
+const MAX_LISTED: usize = 50;
+
+/// Writes `dump` into the repository at `repo`, creating it if need be,
+/// and commits it. Returns the commit's subject, or `None` if nothing
+/// changed since the last one.
+pub fn commit(repo: &Path, dump: &Dump, format: Format) -> Result<Option<String>, String> {
+    let record = Record::of(dump).ok_or("the dump has no metadata")?;
+    fs::create_dir_all(repo).map_err(|e| format!("failed to create {}: {}", repo.display(), e))?;
+    if !repo.join(".git").exists() {
+        git(repo, &["init", "--quiet"])?;
+    }
+
+    // What was committed last, whatever was left in the working tree
+    let before: BTreeMap<String, i64> = git(repo, &["show", &format!("HEAD:{}", OFFSETS)])
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default();
+    let path = repo.join(OFFSETS);
+    let after = record.flat();
+    let json = serde_json::to_string_pretty(&after).expect("offsets are always serializable");
+    fs::write(&path, json + "\n")
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+
+    let name = match format {
+        Format::Text => "netvars.txt",
+        Format::Json => "netvars.json",
+        Format::Jsonl => "netvars.jsonl",
+        Format::Template => "netvars.bt",
+    };
+    let mut contents = Vec::new();
+    output::write(&mut contents, dump, format).expect("writing to memory can't fail");
+    let path = repo.join(name);
+    fs::write(&path, contents).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+
+    git(repo, &["add", "--", OFFSETS, name])?;
+    let unchanged = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["diff", "--cached", "--quiet"])
+        .status()
+        .map_err(|e| format!("failed to run git: {}", e))?
+        .success();
+    if unchanged {
+        return Ok(None);
+    }
+    let subject = format!("Dump {} (client {})", record.label(), &record.client[..12]);
+    let message = format!("{}\n\n{}", subject, summary(&before, &after));
+    git(repo, &["commit", "--quiet", "-m", &message])?;
+    Ok(Some(subject))
+}
+
+/// What changed from `before` to `after`, one line each.
+fn summary(before: &BTreeMap<String, i64>, after: &BTreeMap<String, i64>) -> String {
+    if before.is_empty() {
+        return format!("First dump, {} offsets.\n", after.len());
+    }
+    let mut changes = Vec::new();
+    for (name, offset) in after {
+        match before.get(name) {
+            None => changes.push(format!("+{} {:#x}", name, offset)),
+            Some(old) if old != offset => {
+                changes.push(format!("{} {:#x} -> {:#x}", name, old, offset))
+            }
+            Some(_) => {}
+        }
+    }
+    changes.extend(
+        before
+            .keys()
+            .filter(|name| !after.contains_key(*name))
+            .map(|name| format!("-{}", name)),
+    );
+    if changes.is_empty() {
+        return "No offsets changed.\n".to_string();
+    }
+    let plural = if changes.len() == 1 { "" } else { "s" };
+    let mut summary = format!("{} offset{} changed:\n", changes.len(), plural);
+    for change in changes.iter().take(MAX_LISTED) {
+        summary += change;
+        summary.push('\n');
+    }
+    if changes.len() > MAX_LISTED {
+        summary += &format!("and {} more\n", changes.len() - MAX_LISTED);
+    }
+    summary
+}
+
+/// Runs git in `repo`, returning what it printed.
+fn git(repo: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(offsets: &[(&str, i64)]) -> BTreeMap<String, i64> {
+        offsets
+            .iter()
+            .map(|&(name, offset)| (name.to_string(), offset))
+            .collect()
+    }
+
+    #[test]
+    fn summarizes_changes() {
+        let before = offsets(&[("dwEntityList", 0x100), ("m_iHealth", 0x138), ("m_old", 8)]);
+        let after = offsets(&[("dwEntityList", 0x100), ("m_iHealth", 0x13C), ("m_new", 4)]);
+        assert_eq!(
+            summary(&before, &after),
+            "3 offsets changed:\nm_iHealth 0x138 -> 0x13c\n+m_new 0x4\n-m_old\n"
+        );
+        assert_eq!(summary(&after, &after), "No offsets changed.\n");
+        assert_eq!(
+            summary(&BTreeMap::new(), &after),
+            "First dump, 3 offsets.\n"
+        );
+
+        let one = offsets(&[("dwEntityList", 0x104), ("m_iHealth", 0x13C), ("m_new", 4)]);
+        assert_eq!(
+            summary(&after, &one),
+            "1 offset changed:\ndwEntityList 0x100 -> 0x104\n"
+        );
+    }
+
+    #[test]
+    fn summarizes_many_changes() {
+        let before = offsets(&[("m_a", 0)]);
+        let names: Vec<String> = (0..MAX_LISTED + 2).map(|i| format!("m_{:03}", i)).collect();
+        let after = offsets(
+            &names
+                .iter()
+                .map(|name| (name.as_str(), 0))
+                .collect::<Vec<_>>(),
+        );
+        let summary = summary(&before, &after);
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], format!("{} offsets changed:", MAX_LISTED + 3));
+        assert_eq!(lines[1], "+m_000 0x0");
+        assert_eq!(lines.len(), MAX_LISTED + 2);
+        assert_eq!(lines[MAX_LISTED + 1], "and 3 more");
+    }
+}
@@ -0,0 +1,241 @@
+//! hazedumper's `config.json`, read the way hazedumper reads it.
+//!
+//! Every entry of the `netvars` section names a prop in a class's table,
+//! and its offset is reported under the entry's name, with the entry's
+//! adjustment added. The lookup works like hazedumper's: the table is one
+//! of the classes' tables, and the prop is the first one by that name found
+//! depth-first through the tables it embeds, so configs resolve to the same
+//! offsets they always did.
+//!
+//! Entries of the `signatures` section become [`Signature`]s: each of
+//! `offsets` is added and the 32 bit number there read, like hazedumper
+//! does, then `extra` is added. Unless `relative` is set, the result is a
+//! number rather than an offset from the module's base. Their modules are
+//! Windows', so `engine.dll` is taken to mean `engine_client.so` and
+//! `client.dll` the client. Their patterns are still Windows code though,
+//! and only match once they've been rewritten for the Linux libraries.
+
+use crate::report::{Problem, ProblemKind};
+use crate::resolve::Step;
+use crate::signature::Signature;
+use crate::walk::Dump;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub netvars: Vec<Netvar>,
+    #[serde(default)]
+    pub signatures: Vec<ConfigSignature>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Netvar {
+    /// What the offset is reported as.
+    pub name: String,
+    pub table: String,
+    pub prop: String,
+    /// Added to the prop's offset, for fields next to a networked one.
+    #[serde(default)]
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfigSignature {
+    pub name: String,
+    pub pattern: String,
+    /// File name of the library to search, empty for the client.
+    #[serde(default)]
+    pub module: String,
+    #[serde(default)]
+    pub offsets: Vec<i64>,
+    #[serde(default)]
+    pub extra: i64,
+    #[serde(default)]
+    pub relative: bool,
+}
+
+impl ConfigSignature {
+    pub fn signature(&self) -> Result<Signature, String> {
+        let mut steps: Vec<Step> = self
+            .offsets
+            .iter()
+            .flat_map(|&offset| vec![Step::Add(offset), Step::Read32])
+            .collect();
+        if self.extra != 0 {
+            steps.push(Step::Add(self.extra));
+        }
+        if !self.relative {
+            steps.push(Step::Value);
+        }
+        let mut signature = Signature::new(&self.name, &self.pattern, None, steps)?;
+        signature.module = linux_module(&self.module);
+        Ok(signature)
+    }
+}
+
+/// The library that's called `module` on Windows, `None` for the client.
+fn linux_module(module: &str) -> Option<String> {
+    let lowercase = module.to_ascii_lowercase();
+    let stem = match lowercase.strip_suffix(".dll") {
+        Some(stem) => stem,
+        None => return Some(module.to_string()).filter(|module| !module.is_empty()),
+    };
+    match stem {
+        "client" | "client_panorama" => None,
+        "tier0" | "vstdlib" => Some(format!("lib{}_client.so", stem)),
+        _ => Some(format!("{}_client.so", stem)),
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))
+    }
+
+    /// The offset of every netvar found in `dump` by its name, and a
+    /// problem for every one that wasn't.
+    pub fn netvars(&self, dump: &Dump) -> (BTreeMap<String, i64>, Vec<Problem>) {
+        let mut offsets = BTreeMap::new();
+        let mut problems = Vec::new();
+        for netvar in &self.netvars {
+            let table = dump
+                .classes
+                .iter()
+                .filter_map(|class| class.table.as_ref())
+                .find(|table| table.name == netvar.table);
+            let reason = match table.map(|table| table.find(&netvar.prop)) {
+                Some(Some(offset)) => {
+                    offsets.insert(netvar.name.clone(), offset + netvar.offset);
+                    continue;
+                }
+                Some(None) => format!("{} has no prop {}", netvar.table, netvar.prop),
+                None => format!("no class has the table {}", netvar.table),
+            };
+            problems.push(Problem {
+                kind: ProblemKind::NetvarNotFound,
+                location: netvar.name.clone(),
+                reason,
+            });
+        }
+        (offsets, problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn config(json: &str) -> Config {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn dump() -> Dump {
+        let prop = |name: &str, offset: i32, table: Value| {
+            json!({
+                "name": name,
+                "offset": offset,
+                "kind": "Int",
+                "size": 4,
+                "inside_array": false,
+                "table": table,
+            })
+        };
+        let local = json!({
+            "name": "DT_Local",
+            "props": [prop("m_nTickBase", 4, Value::Null)],
+        });
+        serde_json::from_value(json!({
+            "classes": [{
+                "name": "CCSPlayer",
+                "id": 40,
+                "table": {
+                    "name": "DT_CSPlayer",
+                    "props": [
+                        prop("m_Local", 0x100, local),
+                        prop("m_iHealth", 0x138, Value::Null),
+                        prop("m_nTickBase", 0x3000, Value::Null),
+                    ],
+                },
+                "confidence": 1.0,
+            }],
+            "signatures": [],
+            "problems": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn finds_netvars_like_hazedumper() {
+        let config = config(
+            r#"{ "netvars": [
+                { "name": "m_iHealth", "table": "DT_CSPlayer", "prop": "m_iHealth" },
+                { "name": "m_iHealthAfter", "table": "DT_CSPlayer", "prop": "m_iHealth", "offset": 4 },
+                { "name": "m_nTickBase", "table": "DT_CSPlayer", "prop": "m_nTickBase" },
+                { "name": "m_localTickBase", "table": "DT_Local", "prop": "m_nTickBase" },
+                { "name": "m_missing", "table": "DT_CSPlayer", "prop": "m_missing" },
+                { "name": "m_nowhere", "table": "DT_Nowhere", "prop": "m_iHealth" }
+            ] }"#,
+        );
+        let (offsets, problems) = config.netvars(&dump());
+        let offsets: Vec<_> = offsets.into_iter().collect();
+        assert_eq!(
+            offsets,
+            [
+                ("m_iHealth".to_string(), 0x138),
+                ("m_iHealthAfter".to_string(), 0x13C),
+                // Depth-first, so the one in m_Local comes first, with
+                // m_Local's offset added
+                ("m_nTickBase".to_string(), 0x104),
+            ]
+        );
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|p| (p.location.as_str(), p.reason.as_str()))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                ("m_localTickBase", "no class has the table DT_Local"),
+                ("m_missing", "DT_CSPlayer has no prop m_missing"),
+                ("m_nowhere", "no class has the table DT_Nowhere"),
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_signatures() {
+        let config = config(
+            r#"{ "signatures": [
+                { "name": "dwEntityList", "pattern": "48 8B 05", "module": "client.dll",
+                  "offsets": [3], "extra": 16, "relative": true },
+                { "name": "m_bDormant", "pattern": "8A 81", "module": "engine.dll",
+                  "offsets": [2] },
+                { "name": "dwTier0", "pattern": "48", "module": "tier0.dll", "relative": true },
+                { "name": "dwOther", "pattern": "48", "module": "libother.so", "relative": true }
+            ] }"#,
+        );
+        let signatures: Vec<Signature> = config
+            .signatures
+            .iter()
+            .map(|entry| entry.signature().unwrap())
+            .collect();
+        assert_eq!(signatures[0].module, None);
+        assert_eq!(
+            signatures[0].steps,
+            [Step::Add(3), Step::Read32, Step::Add(16)]
+        );
+        assert_eq!(signatures[1].module.as_deref(), Some("engine_client.so"));
+        assert_eq!(
+            signatures[1].steps,
+            [Step::Add(2), Step::Read32, Step::Value]
+        );
+        assert_eq!(signatures[2].module.as_deref(), Some("libtier0_client.so"));
+        assert_eq!(signatures[3].module.as_deref(), Some("libother.so"));
+    }
+}
@@ -0,0 +1,505 @@
+//! Every build dumped so far, to look up how an offset or a table changed
+//! over time.
+//!
+//! The builds are kept in an SQLite database in the history directory,
+//! with the `sqlite3` program. Each is keyed by its client's SHA-256 and
+//! holds what its dump says about every table and signature, so dumping a
+//! build again only replaces its own. Queries only read the rows they're
+//! about. Builds are ordered by Steam's build id, those Steam didn't install
+//! coming first, ordered by the engine's build number.
+
+use crate::compress;
+use crate::report;
+use crate::walk::{Dump, Table};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DATABASE: &str = "history.sqlite3";
+/// Run before every statement, foreign keys are off unless asked for
+/// again.
+const SCHEMA: &str = "\
+PRAGMA foreign_keys = ON;
+CREATE TABLE IF NOT EXISTS builds (
+    sha256 TEXT PRIMARY KEY,
+    buildid TEXT,
+    build_number INTEGER,
+    timestamp TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS builds_by_buildid ON builds (buildid);
+CREATE TABLE IF NOT EXISTS tables (
+    build TEXT NOT NULL REFERENCES builds ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    PRIMARY KEY (build, name)
+) WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS props (
+    build TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY (build, table_name, name),
+    FOREIGN KEY (build, table_name) REFERENCES tables ON DELETE CASCADE
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS props_by_name ON props (name);
+CREATE TABLE IF NOT EXISTS signatures (
+    build TEXT NOT NULL REFERENCES builds ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY (build, name)
+) WITHOUT ROWID;
+";
+/// NULLs first, so builds without a build id come first.
+const BUILD_ORDER: &str = "CAST(buildid AS INTEGER), build_number, timestamp";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// When the build was dumped.
+    pub timestamp: String,
+    /// SHA-256 of the client library.
+    pub client: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buildid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_number: Option<i32>,
+    /// Every table, also those only reached through a prop, with the
+    /// offsets of its own props.
+    pub tables: BTreeMap<String, BTreeMap<String, i32>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub offsets: BTreeMap<String, usize>,
+}
+
+impl Record {
+    /// What's worth keeping of `dump`. Dumps without metadata can't be
+    /// told apart from other builds.
+    pub fn of(dump: &Dump) -> Option<Self> {
+        let metadata = dump.metadata.as_ref()?;
+        let build = metadata.build.as_ref();
+        let mut tables = BTreeMap::new();
+        for table in dump.classes.iter().filter_map(|class| class.table.as_ref()) {
+            collect(table, &mut tables);
+        }
+        Some(Record {
+            timestamp: metadata.timestamp.clone(),
+            client: metadata.client.sha256.clone(),
+            buildid: build
+                .and_then(|build| build.manifest.as_ref())
+                .map(|manifest| manifest.buildid.clone()),
+            build_number: build.and_then(|build| build.number),
+            tables,
+            offsets: dump.offsets.clone(),
+        })
+    }
+
+    /// How the build is referred to in query results.
+    pub fn label(&self) -> String {
+        label(&self.client, self.buildid.as_deref(), self.build_number)
+    }
+
+    /// Every prop as `DT_Table.m_prop` and every signature by its name,
+    /// with their offsets.
+    pub fn flat(&self) -> BTreeMap<String, i64> {
+        let props = self.tables.iter().flat_map(|(table, props)| {
+            props
+                .iter()
+                .map(move |(prop, &offset)| (format!("{}.{}", table, prop), offset as i64))
+        });
+        let signatures = self
+            .offsets
+            .iter()
+            .map(|(name, &offset)| (name.clone(), offset as i64));
+        props.chain(signatures).collect()
+    }
+}
+
+fn label(sha256: &str, buildid: Option<&str>, build_number: Option<i32>) -> String {
+    match (buildid, build_number) {
+        (Some(buildid), _) => format!("buildid {}", buildid),
+        (None, Some(number)) => format!("build {}", number),
+        (None, None) => format!("sha256 {}", &sha256[..12.min(sha256.len())]),
+    }
+}
+
+/// Adds `table` and the tables below it to `tables`, the first one of a
+/// name winning.
+fn collect(table: &Table, tables: &mut BTreeMap<String, BTreeMap<String, i32>>) {
+    if tables.contains_key(&table.name) {
+        return;
+    }
+    let props = table
+        .props
+        .iter()
+        .filter(|prop| !prop.inside_array)
+        // So the first of a name is inserted last
+        .rev()
+        .map(|prop| (prop.name.clone(), prop.offset))
+        .collect();
+    tables.insert(table.name.clone(), props);
+    for prop in &table.props {
+        if let Some(table) = &prop.table {
+            collect(table, tables);
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME/netvars-rs/history`, or `~/.local/share/netvars-rs/history`.
+pub fn default_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(base.join("netvars-rs/history"))
+}
+
+/// A recorded build, without what was dumped of it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Build {
+    pub sha256: String,
+    pub buildid: Option<String>,
+    pub build_number: Option<i32>,
+    pub timestamp: String,
+}
+
+impl Build {
+    pub fn label(&self) -> String {
+        label(&self.sha256, self.buildid.as_deref(), self.build_number)
+    }
+}
+
+/// A prop's or signature's offset in a build, `table_name` being `None`
+/// for signatures and `name` for tables without props.
+#[derive(Debug, Clone, Deserialize)]
+struct Row {
+    build: String,
+    table_name: Option<String>,
+    name: Option<String>,
+    value: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    pub dir: PathBuf,
+}
+
+impl History {
+    /// Replaces what's recorded of the build `dump` is of.
+    pub fn record(&self, dump: &Dump) -> Result<(), String> {
+        let record = Record::of(dump).ok_or("the dump has no metadata")?;
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("failed to create {}: {}", self.dir.display(), e))?;
+        let build = quote(&record.client);
+        let mut sql = String::from("BEGIN;\n");
+        let _ = writeln!(sql, "DELETE FROM builds WHERE sha256 = {};", build);
+        let _ = writeln!(
+            sql,
+            "INSERT INTO builds VALUES ({}, {}, {}, {});",
+            build,
+            record.buildid.as_deref().map_or("NULL".into(), quote),
+            record
+                .build_number
+                .map_or("NULL".into(), |number| number.to_string()),
+            quote(&record.timestamp)
+        );
+        for (table, props) in &record.tables {
+            let table = quote(table);
+            let _ = writeln!(sql, "INSERT INTO tables VALUES ({}, {});", build, table);
+            for (prop, offset) in props {
+                let _ = writeln!(
+                    sql,
+                    "INSERT INTO props VALUES ({}, {}, {}, {});",
+                    build,
+                    table,
+                    quote(prop),
+                    offset
+                );
+            }
+        }
+        for (name, offset) in &record.offsets {
+            let _ = writeln!(
+                sql,
+                "INSERT INTO signatures VALUES ({}, {}, {});",
+                build,
+                quote(name),
+                offset
+            );
+        }
+        sql += "COMMIT;\n";
+        self.query::<Row>(&sql).map(drop)
+    }
+
+    /// Every recorded build, oldest first.
+    pub fn builds(&self) -> Result<Vec<Build>, String> {
+        self.query(&format!("SELECT * FROM builds ORDER BY {};", BUILD_ORDER))
+    }
+
+    /// Runs `sql`, returning the rows the last statement of it selected.
+    fn query<T: DeserializeOwned>(&self, sql: &str) -> Result<Vec<T>, String> {
+        let database = self.dir.join(DATABASE);
+        let script = format!("{}{}", SCHEMA, sql);
+        let output = compress::pipe(
+            "sqlite3",
+            &["-batch", "-bail", "-json", &database.to_string_lossy()],
+            script.as_bytes(),
+        )
+        .map_err(|e| format!("{}: {}", database.display(), e))?;
+        if output.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Vec::new());
+        }
+        serde_json::from_slice(&output)
+            .map_err(|e| format!("unexpected answer from sqlite3: {}", e))
+    }
+}
+
+/// `s` as an SQL string literal.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Prints what the builds recorded in `dir` say about `query`: how a
+/// table's props changed for `DT_Table`, and every offset a prop or
+/// signature has had for `DT_Table.m_prop`, `m_prop` in any table, or a
+/// signature's name.
+pub fn run(dir: &Path, query: &str) -> i32 {
+    let history = History {
+        dir: dir.to_path_buf(),
+    };
+    match answer(&history, query) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            report::EXIT_FAILED
+        }
+    }
+}
+
+fn answer(history: &History, query: &str) -> Result<(), String> {
+    // Rather than an empty database to ask next time
+    let builds = if history.dir.join(DATABASE).is_file() {
+        history.builds()?
+    } else {
+        Vec::new()
+    };
+    if builds.is_empty() {
+        return Err(format!("no builds recorded in {}", history.dir.display()));
+    }
+
+    let table: Vec<Row> = history.query(&format!(
+        "SELECT tables.build, props.name, props.value FROM tables \
+         LEFT JOIN props ON props.build = tables.build AND props.table_name = tables.name \
+         WHERE tables.name = {};",
+        quote(query)
+    ))?;
+    if !table.is_empty() {
+        let mut tables: BTreeMap<String, BTreeMap<String, i64>> = BTreeMap::new();
+        for row in table {
+            let props = tables.entry(row.build).or_default();
+            if let (Some(name), Some(value)) = (row.name, row.value) {
+                props.insert(name, value);
+            }
+        }
+        println!("{}", query);
+        for line in table_changes(&builds, &tables) {
+            println!("    {}", line);
+        }
+        return Ok(());
+    }
+
+    let in_table = match query.split_once('.') {
+        Some((table, prop)) => {
+            let known: Vec<Row> = history.query(&format!(
+                "SELECT build FROM tables WHERE name = {} LIMIT 1;",
+                quote(table)
+            ))?;
+            Some((table, prop)).filter(|_| !known.is_empty())
+        }
+        None => None,
+    };
+    let rows: Vec<Row> = match in_table {
+        Some((table, prop)) => history.query(&format!(
+            "SELECT build, table_name, value FROM props WHERE table_name = {} AND name = {};",
+            quote(table),
+            quote(prop)
+        ))?,
+        None => history.query(&format!(
+            "SELECT build, table_name, value FROM props WHERE name = {};",
+            quote(query)
+        ))?,
+    };
+    // By table, then by build
+    let mut props: BTreeMap<String, BTreeMap<String, i64>> = BTreeMap::new();
+    for row in rows {
+        if let (Some(table), Some(value)) = (row.table_name, row.value) {
+            props.entry(table).or_default().insert(row.build, value);
+        }
+    }
+    if let Some((table, _)) = in_table {
+        props.entry(table.to_string()).or_default();
+    }
+    let prop = in_table.map_or(query, |(_, prop)| prop);
+    for (table, values) in &props {
+        println!("{}.{}", table, prop);
+        for line in runs(&builds, |build| values.get(&build.sha256).copied()) {
+            println!("    {}", line);
+        }
+    }
+    if !props.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<Row> = history.query(&format!(
+        "SELECT build, value FROM signatures WHERE name = {};",
+        quote(query)
+    ))?;
+    if rows.is_empty() {
+        return Err(format!(
+            "no table, prop or signature {} in {} recorded builds",
+            query,
+            builds.len()
+        ));
+    }
+    let values: BTreeMap<String, i64> = rows
+        .into_iter()
+        .filter_map(|row| Some((row.build, row.value?)))
+        .collect();
+    println!("{}", query);
+    for line in runs(&builds, |build| values.get(&build.sha256).copied()) {
+        println!("    {}", line);
+    }
+    Ok(())
+}
+
+/// Each stretch of builds `value` stayed the same over, a line each.
+fn runs(builds: &[Build], value: impl Fn(&Build) -> Option<i64>) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for end in 1..=builds.len() {
+        if end < builds.len() && value(&builds[end]) == value(&builds[start]) {
+            continue;
+        }
+        let shown = match value(&builds[start]) {
+            Some(offset) => format!("{:#x}", offset),
+            None => "missing".to_string(),
+        };
+        let builds = match end - start {
+            1 => builds[start].label(),
+            count => format!(
+                "{} .. {} ({} builds)",
+                builds[start].label(),
+                builds[end - 1].label(),
+                count
+            ),
+        };
+        lines.push(format!("{:<10} {}", shown, builds));
+        start = end;
+    }
+    lines
+}
+
+/// The builds a table changed in, and how, a line each, from its props in
+/// each build that has it.
+fn table_changes(
+    builds: &[Build],
+    tables: &BTreeMap<String, BTreeMap<String, i64>>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut previous: Option<&BTreeMap<String, i64>> = None;
+    let mut seen = false;
+    for build in builds {
+        let current = tables.get(&build.sha256);
+        let changes = match (previous, current) {
+            (None, None) => continue,
+            (None, Some(props)) if seen => format!("back, {} props", props.len()),
+            (None, Some(props)) => format!("first seen, {} props", props.len()),
+            (Some(_), None) => "gone".to_string(),
+            (Some(before), Some(after)) => {
+                let mut changes = Vec::new();
+                for (name, offset) in after {
+                    match before.get(name) {
+                        None => changes.push(format!("+{} {:#x}", name, offset)),
+                        Some(old) if old != offset => {
+                            changes.push(format!("{} {:#x} -> {:#x}", name, old, offset))
+                        }
+                        Some(_) => {}
+                    }
+                }
+                changes.extend(
+                    before
+                        .keys()
+                        .filter(|name| !after.contains_key(*name))
+                        .map(|name| format!("-{}", name)),
+                );
+                if changes.is_empty() {
+                    continue;
+                }
+                changes.join(", ")
+            }
+        };
+        lines.push(format!("{:<24} {}", build.label(), changes));
+        previous = current;
+        seen = true;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builds(count: usize) -> Vec<Build> {
+        (0..count)
+            .map(|i| Build {
+                sha256: format!("{:064x}", i),
+                buildid: Some((100 + i).to_string()),
+                build_number: None,
+                timestamp: "2021-07-14T00:00:00Z".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn runs_of_the_same_value() {
+        let builds = builds(5);
+        let values = [Some(0x10), Some(0x10), Some(0x10), None, Some(0x18)];
+        let value = |build: &Build| values[builds.iter().position(|b| b.sha256 == build.sha256)?];
+        assert_eq!(
+            runs(&builds, value),
+            [
+                "0x10       buildid 100 .. buildid 102 (3 builds)",
+                "missing    buildid 103",
+                "0x18       buildid 104",
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_of_a_table() {
+        let builds = builds(6);
+        let props = |props: &[(&str, i64)]| -> BTreeMap<String, i64> {
+            props
+                .iter()
+                .map(|&(name, offset)| (name.to_string(), offset))
+                .collect()
+        };
+        let tables: BTreeMap<String, BTreeMap<String, i64>> = vec![
+            (1, props(&[("m_iHealth", 0x100), ("m_iTeamNum", 0xF4)])),
+            (2, props(&[("m_iHealth", 0x100), ("m_iTeamNum", 0xF4)])),
+            (3, props(&[("m_iHealth", 0x104), ("m_fFlags", 0x108)])),
+            (5, props(&[("m_iHealth", 0x104)])),
+        ]
+        .into_iter()
+        .map(|(i, props)| (builds[i].sha256.clone(), props))
+        .collect();
+        assert_eq!(
+            table_changes(&builds, &tables),
+            [
+                "buildid 101              first seen, 2 props",
+                "buildid 103              +m_fFlags 0x108, m_iHealth 0x100 -> 0x104, -m_iTeamNum",
+                "buildid 104              gone",
+                "buildid 105              back, 1 props",
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Finding the class list through the client's interface, which outlives
+//! both the symbols and the code the signature is made for.
+//!
+//! The client exports `CreateInterface`, handing out its `IBaseClientDLL`
+//! under a versioned name like `VClient018`. One of the entries in its
+//! vtable is `GetAllClasses()`, which does nothing but return
+//! `g_pClientClassHead`. That's `mov rax, [rip + g_pClientClassHead]` and a
+//! `ret`, maybe in a frame, so the slot is read off the code where it looks
+//! like that, and the getter is only called where it doesn't.
+
+use crate::elf::ElfFile;
+use crate::memory::Memory;
+use crate::module::Module;
+use iced_x86::{Code as Opcode, Decoder, DecoderOptions, Register};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+
+pub const CREATE_INTERFACE_SYMBOL: &str = "CreateInterface";
+pub const INTERFACE_PREFIX: &str = "VClient";
+/// Versions asked for, newest first. CS:GO's client is at 018.
+const MAX_VERSION: u32 = 30;
+/// In CS:GO's `VClient018`, `GetAllClasses` comes after `Connect`,
+/// `Disconnect`, `Init`, `PostInit`, `Shutdown`, `LevelInitPreEntity`,
+/// `LevelInitPostEntity` and `LevelShutdown`.
+pub const GET_ALL_CLASSES_INDEX: usize = 8;
+/// More than a getter takes up, frame and all.
+const MAX_GETTER_LEN: usize = 32;
+
+type CreateInterfaceFn = unsafe extern "C" fn(*const c_char, *mut c_int) -> *mut c_void;
+type GetAllClassesFn = unsafe extern "C" fn(*const c_void) -> usize;
+
+#[derive(Debug, Clone)]
+pub struct Found {
+    /// The interface's name, e.g. `VClient018`.
+    pub name: String,
+    pub get_all_classes: usize,
+    /// Where `GetAllClasses` reads the head from, if its code said.
+    pub slot: Option<usize>,
+    pub head: usize,
+}
+
+/// Asks the client for its interface and gets the class list's head from
+/// it.
+pub fn class_head(memory: &Memory, module: &Module, elf: &ElfFile) -> Result<Found, String> {
+    let symbol = elf
+        .symbol(CREATE_INTERFACE_SYMBOL)
+        .filter(|symbol| symbol.function && symbol.exported)
+        .ok_or("the client doesn't export CreateInterface")?;
+    let create_interface: CreateInterfaceFn =
+        unsafe { std::mem::transmute(module.address + symbol.address) };
+
+    let (name, interface) = (0..=MAX_VERSION)
+        .rev()
+        .find_map(|version| {
+            let name = format!("{}{:03}", INTERFACE_PREFIX, version);
+            let c_name = CString::new(name.as_str()).expect("no NUL in the name");
+            let mut status = 0;
+            let interface = unsafe { create_interface(c_name.as_ptr(), &mut status) };
+            Some((name, interface as usize)).filter(|_| !interface.is_null())
+        })
+        .ok_or_else(|| {
+            format!(
+                "CreateInterface knows no {}000 to {}{:03}",
+                INTERFACE_PREFIX, INTERFACE_PREFIX, MAX_VERSION
+            )
+        })?;
+
+    let read = |address| unsafe { memory.read::<usize>(address) };
+    let error = |e| format!("failed to read {}'s vtable: {}", name, e);
+    let vtable = read(interface).map_err(error)?;
+    let get_all_classes =
+        read(vtable + GET_ALL_CLASSES_INDEX * std::mem::size_of::<usize>()).map_err(error)?;
+    let segment = module
+        .segment(get_all_classes)
+        .filter(|segment| segment.execute)
+        .ok_or_else(|| {
+            format!(
+                "{}'s GetAllClasses at {:#X} isn't code of the client",
+                name, get_all_classes
+            )
+        })?;
+
+    let mut code = vec![0; MAX_GETTER_LEN.min(segment.range.end - get_all_classes)];
+    memory
+        .read_bytes(get_all_classes, &mut code)
+        .map_err(|e| format!("failed to read GetAllClasses: {}", e))?;
+    let slot = getter_slot(&code, get_all_classes);
+    let head = match slot {
+        Some(slot) => {
+            read(slot).map_err(|e| format!("failed to read g_pClientClassHead: {}", e))?
+        }
+        None => {
+            // Should it crash after all, the worker takes the fall
+            let get_all_classes: GetAllClassesFn = unsafe { std::mem::transmute(get_all_classes) };
+            unsafe { get_all_classes(interface as *const c_void) }
+        }
+    };
+    Ok(Found {
+        name,
+        get_all_classes,
+        slot,
+        head,
+    })
+}
+
+/// Where the getter whose code is `code`, at `ip`, loads what it returns
+/// from, if it does nothing else.
+fn getter_slot(code: &[u8], ip: usize) -> Option<usize> {
+    let mut decoder = Decoder::with_ip(64, code, ip as u64, DecoderOptions::NONE);
+    let mut slot = None;
+    for instruction in &mut decoder {
+        let operands = (instruction.op0_register(), instruction.op1_register());
+        match instruction.code() {
+            Opcode::Endbr64 => {}
+            Opcode::Push_r64 | Opcode::Pop_r64 if operands.0 == Register::RBP => {}
+            Opcode::Mov_r64_rm64 | Opcode::Mov_rm64_r64
+                if operands == (Register::RBP, Register::RSP) => {}
+            Opcode::Mov_r64_rm64
+                if operands.0 == Register::RAX
+                    && instruction.is_ip_rel_memory_operand()
+                    && slot.is_none() =>
+            {
+                slot = Some(instruction.ip_rel_memory_address() as usize)
+            }
+            Opcode::Retnq => return slot,
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_getter_slots() {
+        // mov rax, [rip + 0x100]; ret
+        let plain = [0x48, 0x8B, 0x05, 0x00, 0x01, 0x00, 0x00, 0xC3];
+        assert_eq!(getter_slot(&plain, 0x1000), Some(0x1107));
+        // endbr64; push rbp; mov rbp, rsp; mov rax, [rip - 0x10]; pop rbp; ret
+        let framed = [
+            0xF3, 0x0F, 0x1E, 0xFA, 0x55, 0x48, 0x89, 0xE5, 0x48, 0x8B, 0x05, 0xF0, 0xFF, 0xFF,
+            0xFF, 0x5D, 0xC3,
+        ];
+        assert_eq!(getter_slot(&framed, 0x2000), Some(0x1FFF));
+    }
+
+    #[test]
+    fn calls_getters_that_do_more() {
+        // mov rax, [rdi]; ret
+        assert_eq!(getter_slot(&[0x48, 0x8B, 0x07, 0xC3], 0x1000), None);
+        // ret
+        assert_eq!(getter_slot(&[0xC3], 0x1000), None);
+        // mov rax, [rip]; mov rax, [rip]; ret
+        let twice = [
+            0x48, 0x8B, 0x05, 0, 0, 0, 0, 0x48, 0x8B, 0x05, 0, 0, 0, 0, 0xC3,
+        ];
+        assert_eq!(getter_slot(&twice, 0x1000), None);
+        // call rel32; mov rax, [rip]; ret
+        let call = [0xE8, 0, 0, 0, 0, 0x48, 0x8B, 0x05, 0, 0, 0, 0, 0xC3];
+        assert_eq!(getter_slot(&call, 0x1000), None);
+        // mov rax, [rip] without its ret, cut off
+        assert_eq!(getter_slot(&[0x48, 0x8B, 0x05, 0, 0, 0, 0], 0x1000), None);
+    }
+}
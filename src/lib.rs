@@ -0,0 +1,56 @@
+//! Dumps the netvars of a Source engine client library by loading it and
+//! walking its `ClientClass` list, without the game running.
+//!
+//! The `netvars-rs` binary is a thin shell around this: [`worker::run`]
+//! forks a process to do the dangerous part in, [`dumper`] loads the library
+//! and finds the list, and [`walk`] reads it into a [`walk::Dump`].
+
+extern crate libc;
+
+pub mod batch;
+pub mod build;
+pub mod cache;
+pub mod compare;
+pub mod compress;
+pub mod discover;
+pub mod doctor;
+pub mod dumper;
+pub mod elf;
+pub mod environment;
+pub mod fetch;
+pub mod flatten;
+pub mod gamedir;
+pub mod git;
+pub mod hazedumper;
+pub mod history;
+pub mod interface;
+pub mod makesig;
+pub mod mapping;
+pub mod memory;
+pub mod metadata;
+pub mod module;
+pub mod output;
+pub mod pattern;
+pub mod private;
+pub mod publish;
+pub mod repair;
+pub mod report;
+pub mod resolve;
+pub mod runtime;
+pub mod sandbox;
+pub mod sdk;
+pub mod selftest;
+pub mod sha256;
+pub mod shim;
+pub mod signature;
+pub mod signature_file;
+pub mod signing;
+pub mod steam;
+pub mod symbols;
+pub mod template;
+pub mod validate;
+pub mod vdf;
+pub mod verify;
+pub mod walk;
+pub mod worker;
+pub mod yara_rules;
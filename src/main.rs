@@ -1,230 +1,671 @@
-extern crate libc;
-
-use libc::{c_void, dl_iterate_phdr, dl_phdr_info, dlopen};
-use std::convert::TryInto;
-use std::ffi::CStr;
-use std::fmt::{Debug, Error, Formatter};
-use std::ops::Deref;
-use std::os::raw::c_char;
-
-#[derive(Debug, Clone)]
-struct Module {
-    address: usize,
-    size: usize,
-    name: String,
-}
+mod cli;
+
+use crate::cli::{Command, Options};
+use netvars_rs::cache::{Cache, Key};
+use netvars_rs::compare::{Comparison, Published};
+use netvars_rs::compress::{self, Compression};
+use netvars_rs::dumper::{self, Locators};
+use netvars_rs::elf::ElfFile;
+use netvars_rs::fetch::Fetch;
+use netvars_rs::gamedir::Game;
+use netvars_rs::hazedumper::Config;
+use netvars_rs::history::{self, History};
+use netvars_rs::metadata::Metadata;
+use netvars_rs::output::{self, Format, Line};
+use netvars_rs::private::TempDir;
+use netvars_rs::publish::Target;
+use netvars_rs::report::{self, ErrorReport};
+use netvars_rs::runtime::{Mode, Runtime};
+use netvars_rs::sandbox::Sandbox;
+use netvars_rs::signature::Signature;
+use netvars_rs::worker::{self, Outcome, Sender};
+use netvars_rs::yara_rules::RuleSet;
+use netvars_rs::{
+    batch, doctor, environment, git, makesig, selftest, shim, signature_file, signing, validate,
+    verify,
+};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Loads the client library and walks its class list. Runs inside the
+/// worker process.
+fn dump(
+    sender: &mut Sender,
+    game: &Game,
+    options: &Options,
+    rules: Option<&RuleSet>,
+    previous: Option<&ElfFile>,
+    sandbox: Option<&Sandbox>,
+) -> Result<(), String> {
+    if let Some(sandbox) = sandbox {
+        sandbox.enter()?;
+        sender.log("Sandboxed");
+    }
+    dumper::preload(sender, &game.dependencies()?)?;
+    dumper::load(sender, &game.client.to_string_lossy())?;
+    dumper::load_modules(sender, &game.modules)?;
 
-#[derive(Debug, Clone)]
-struct CallbackContext {
-    modules: Vec<Module>,
-    pagesize: u64,
+    let client_name = game.client_name();
+    let (client, others): (Vec<Signature>, Vec<Signature>) =
+        options.signatures.iter().cloned().partition(|signature| {
+            signature
+                .module
+                .as_ref()
+                .is_none_or(|module| *module == client_name)
+        });
+    let locators = Locators {
+        signatures: &client,
+        rules,
+        strategies: &options.strategies,
+        previous,
+    };
+    // The other modules are worth scanning even if the client's list
+    // can't be found
+    let dumped = dumper::dump_module(sender, &client_name, &locators);
+    for name in game.module_names() {
+        let signatures: Vec<Signature> = others
+            .iter()
+            .filter(|signature| signature.module.as_deref() == Some(name.as_str()))
+            .cloned()
+            .collect();
+        dumper::scan_module(sender, &name, &signatures)?;
+    }
+    dumped
 }
 
-type CreateClientClassFn = fn(i32, i32) -> *mut c_void;
-type CreateEventFn = fn() -> *mut c_void;
-
-#[allow(non_snake_case)]
-#[repr(C)]
-struct RecvTable {
-    m_pProps: *const RecvProp,
-    m_nProps: i32,
-    m_pDecoder: *const c_void,
-    m_pNetTableName: *const c_char,
-    m_bInitialized: bool,
-    m_bInMainList: bool,
+/// Loads the client library again and reports what it can't resolve. Runs
+/// inside a worker process of its own, in case the first one crashed.
+fn probe(sender: &mut Sender, game: &Game) -> Result<(), String> {
+    let mut libraries = game.dependencies()?;
+    libraries.push(game.client.clone());
+    dumper::probe_symbols(sender, &libraries)
 }
 
-impl Debug for RecvTable {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        write!(
-            f,
-            "{:#?}",
-            (0..self.m_nProps)
-                .filter_map(|i| unsafe { self.m_pProps.add(i as usize).as_ref() })
-                .collect::<Vec<_>>()
-        )
+/// Everything the dump depends on: the libraries that get loaded and what
+/// is looked for in them.
+fn cache_key(game: &Game, options: &Options) -> Result<String, String> {
+    let mut key = Key::default();
+    key.add_file("client", &game.client)?;
+    for library in game.dependencies()? {
+        key.add_file("dependency", &library)?;
+    }
+    for module in &game.modules {
+        key.add_file("module", module)?;
+    }
+    for signature in &options.signatures {
+        let module = signature.module.clone().unwrap_or_default();
+        key.add("signature", format!("{}:{}", module, signature).as_bytes());
+    }
+    for strategy in &options.strategies {
+        key.add("strategy", strategy.name().as_bytes());
+    }
+    for rules in &options.yara {
+        key.add_file("yara", rules)?;
+    }
+    if let Some(previous) = &options.previous {
+        key.add_file("previous", previous)?;
     }
+    Ok(key.finish())
 }
 
-impl Debug for RecvProp {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        let propname = unsafe { CStr::from_ptr(self.m_pVarName) };
-        if let Some(table) = unsafe { self.m_pDataTable.as_ref() } {
-            let name = unsafe { CStr::from_ptr(table.m_pNetTableName) };
-            write!(f, "{:?} @ {:#X} -> {:?} {:#?}",propname, self.m_Offset,name,table)
-        } else {
-
-            write!(f, "{:?} -> {:#X}", propname, self.m_Offset)
+fn batch(
+    gamedirs: &mut Vec<PathBuf>,
+    games: Option<PathBuf>,
+    output_dir: &Path,
+    sign_key: Option<&Path>,
+    options: &[String],
+    format: Format,
+    compress: Option<Compression>,
+) -> i32 {
+    if let Some(games) = games {
+        match batch::load_list(&games) {
+            Ok(listed) => gamedirs.extend(listed),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return report::EXIT_USAGE;
+            }
+        }
+    }
+    if gamedirs.is_empty() {
+        eprintln!("error: no game directories to dump\n\n{}", cli::USAGE);
+        return report::EXIT_USAGE;
+    }
+    let mut extension = match format {
+        Format::Text => "txt",
+        Format::Json => "json",
+        Format::Jsonl => "jsonl",
+        Format::Template => "bt",
+    }
+    .to_string();
+    if let Some(compression) = compress {
+        extension = format!("{}.{}", extension, compression.extension());
+    }
+    let index = match batch::run(gamedirs, options, output_dir, &extension) {
+        Ok(index) => index,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return report::EXIT_FAILED;
+        }
+    };
+    if let Some(key) = sign_key {
+        let files = index
+            .games
+            .iter()
+            .map(|game| output_dir.join(&game.output))
+            .chain(std::iter::once(output_dir.join("index.json")));
+        for file in files {
+            if let Err(e) = signing::sign(key, &file) {
+                eprintln!("error: {}", e);
+                return report::EXIT_FAILED;
+            }
         }
     }
+    if index.games.iter().all(|game| game.exit_code == 0) {
+        0
+    } else {
+        report::EXIT_BATCH_FAILED
+    }
 }
 
-#[allow(non_snake_case)]
-#[repr(C)]
-#[derive(Debug)]
-struct ClientClass {
-    m_pCreateFn: CreateClientClassFn,
-    m_pCreateEventFn: CreateEventFn,
-    m_pNetworkName: *const c_char,
-    m_pRecvTable: *const RecvTable,
-    m_pNext: *const ClientClass,
-    m_ClassID: i32,
+/// Uploads `files`, compressed first if they aren't yet and `compress` is
+/// given, and prints where they ended up.
+fn publish(target: &Target, files: &[PathBuf], compress: Option<Compression>) -> i32 {
+    if let (Target::Gist { .. }, Some(_)) = (target, compress) {
+        eprintln!("error: gists only hold text, they can't be compressed");
+        return report::EXIT_USAGE;
+    }
+    // Only made if something is compressed, and removed when dropped
+    let mut dir = None;
+    let compressed = files
+        .iter()
+        .map(|file| match compress {
+            Some(compression) if Compression::of(file).is_none() => {
+                let dir = match &mut dir {
+                    Some(dir) => dir,
+                    None => dir.insert(TempDir::new("netvars-rs")?),
+                };
+                let name = compression.path(Path::new(file.file_name().unwrap_or_default()));
+                let path = dir.path().join(name);
+                let data = compress::read(file).and_then(|data| compression.compress(&data))?;
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .and_then(|mut out| out.write_all(&data))
+                    .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+                Ok(path)
+            }
+            _ => Ok(file.clone()),
+        })
+        .collect::<Result<Vec<PathBuf>, String>>();
+    let published = compressed.and_then(|files| target.publish(&files));
+    drop(dir);
+    match published {
+        Ok(urls) => {
+            for url in urls {
+                println!("{}", url);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            report::EXIT_FAILED
+        }
+    }
 }
 
-struct ClientClassIterator {
-    current: *const ClientClass,
+fn report_comparison(comparison: &Comparison, source: &str) {
+    eprintln!(
+        "compare: {} agree with {}, {} disagree, {} not in the dump",
+        comparison.agree.len(),
+        source,
+        comparison.disagree.len(),
+        comparison.missing.len()
+    );
+    for (name, ours, theirs) in &comparison.disagree {
+        eprintln!("    {} is {:#x}, published {:#x}", name, ours, theirs);
+    }
 }
 
-impl<'a> Iterator for ClientClassIterator {
-    type Item = *const ClientClass;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        unsafe { self.current.as_ref() }.and_then(|v| {
-            self.current = v.m_pNext;
-            Some(v as *const _)
-        })
+/// Checks every file's signature, telling how each went.
+fn verify_signatures(key: &Path, files: &[PathBuf]) -> i32 {
+    let mut exit_code = 0;
+    for file in files {
+        match signing::verify(key, file) {
+            Ok(true) => println!("{}: OK", file.display()),
+            Ok(false) => {
+                println!("{}: BAD SIGNATURE", file.display());
+                exit_code = report::EXIT_BAD_SIGNATURE;
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return report::EXIT_FAILED;
+            }
+        }
     }
+    exit_code
 }
 
-#[allow(non_snake_case)]
-#[repr(C)]
-struct RecvProp {
-    m_pVarName: *const c_char,
-    m_RecvType: i32,
-    m_Flags: i32,
-    m_StringBufferSize: i32,
-    m_bInsideArray: bool,
-    m_pExtraData: *const c_void,
-    m_pArrayProp: *const RecvProp,
-    m_ArrayLengthProxy: *const c_void,
-    m_ProxyFn: *const c_void, /* RecvVarProxyFn */
-    m_DataTableProxyFn: *const c_void,
-    m_pDataTable: *const RecvTable,
-    m_Offset: i32,
-    m_ElementStride: i32,
-    m_nElements: i32,
-    m_pParentArrayPropName: *const c_char,
+/// Downloads the build, then dumps it in a process of its own, which picks
+/// up the libraries' directories on startup.
+fn fetch_and_dump(fetch: &Fetch, dir: Option<PathBuf>, options: &[String]) -> i32 {
+    let dir = dir.unwrap_or_else(|| fetch.default_dir());
+    if let Err(e) = fetch.run(&dir) {
+        eprintln!("error: {}", e);
+        return report::EXIT_FETCH_FAILED;
+    }
+    let status = std::env::current_exe()
+        .map_err(|e| format!("can't tell where we are: {}", e))
+        .and_then(|exe| {
+            std::process::Command::new(&exe)
+                .args(options)
+                .arg(&dir)
+                .status()
+                .map_err(|e| format!("failed to run {}: {}", exe.display(), e))
+        });
+    match status {
+        // Killed by a signal
+        Ok(status) => status.code().unwrap_or(report::EXIT_CRASHED),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            report::EXIT_FAILED
+        }
+    }
 }
 
-impl CallbackContext {
-    pub fn new() -> Self {
-        let pagesize: u64 = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }
-            .try_into()
-            .expect("page size doesn't fit into u64! This should *never* happen.");
+fn main() {
+    let mut options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Dump(options)) => *options,
+        Ok(Command::SelfTest { fixture, timeout }) => {
+            std::process::exit(selftest::run(fixture.as_deref(), timeout))
+        }
+        Ok(Command::Doctor { gamedir, timeout }) => {
+            std::process::exit(doctor::run(&gamedir, timeout))
+        }
+        Ok(Command::MakeSig {
+            library,
+            target,
+            name,
+            max_len,
+        }) => std::process::exit(makesig::run(&library, &target, name.as_deref(), max_len)),
+        Ok(Command::Batch {
+            mut gamedirs,
+            games,
+            output_dir,
+            sign_key,
+            options,
+            format,
+            compress,
+        }) => std::process::exit(batch(
+            &mut gamedirs,
+            games,
+            &output_dir,
+            sign_key.as_deref(),
+            &options,
+            format,
+            compress,
+        )),
+        Ok(Command::Sign { key, files }) => {
+            for file in &files {
+                match signing::sign(&key, file) {
+                    Ok(signature) => println!("{}", signature.display()),
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        std::process::exit(report::EXIT_FAILED)
+                    }
+                }
+            }
+            std::process::exit(0)
+        }
+        Ok(Command::VerifySignature { key, files }) => {
+            std::process::exit(verify_signatures(&key, &files))
+        }
+        Ok(Command::Verify {
+            dump,
+            builtin,
+            anchors,
+        }) => match verify::anchors(builtin, &anchors) {
+            Ok(anchors) => std::process::exit(verify::run(&dump, &anchors)),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(report::EXIT_USAGE)
+            }
+        },
+        Ok(Command::History { dir, query }) => std::process::exit(history::run(&dir, &query)),
+        Ok(Command::Publish {
+            compress,
+            target,
+            files,
+        }) => std::process::exit(publish(&target, &files, compress)),
+        Ok(Command::FetchAndDump {
+            fetch,
+            dir,
+            options,
+        }) => std::process::exit(fetch_and_dump(&fetch, dir, &options)),
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(report::EXIT_USAGE);
+        }
+    };
 
-        CallbackContext {
-            modules: Vec::new(),
-            pagesize,
+    let mut game = match Game::locate(&options.gamedir) {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(report::EXIT_USAGE);
+        }
+    };
+    if let Some(mode) = options.steam_runtime {
+        let found = Runtime::find(mode, options.steam_runtime_dir.as_deref(), &options.gamedir);
+        let result = found.and_then(|runtime| match mode {
+            Mode::Libraries => {
+                game.library_dirs.extend(runtime.library_dirs());
+                Ok(())
+            }
+            Mode::Container => runtime.enter(),
+        });
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+            std::process::exit(report::EXIT_USAGE);
         }
     }
-}
 
-impl Module {
-    pub fn new(info: &dl_phdr_info, pagesize: u64) -> Option<Self> {
-        // TODO: Linux shared modules have gaps in their allocations.
-        // This usually isn't a problem if the signature is valid, but it may cause a segfault if it is invalid.
-
-        let name = unsafe { CStr::from_ptr(info.dlpi_name) }.to_str().ok()?;
-        let size: u64 = (0..info.dlpi_phnum)
-            .filter_map(|i| unsafe { info.dlpi_phdr.add(i as usize).as_ref() })
-            // https://github.com/lattera/glibc/blob/master/elf/dl-load.c#L1085
-            .map(|e| e.p_vaddr + e.p_memsz)
-            // Align to pagesize
-            // https://github.com/lattera/glibc/blob/master/elf/dl-load.c#L1085
-            .map(|a| (a + pagesize - 1) & !(pagesize - 1))
-            .max()?;
-        Some(Module {
-            address: info.dlpi_addr as usize,
-            size: size as usize,
-            name: name.to_string(),
-        })
+    // Last, so stubs only stand in for what's really missing
+    let shims = match options.shim.then(shim::dir).transpose() {
+        Ok(shims) => shims,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(report::EXIT_FAILED);
+        }
+    };
+    game.library_dirs.extend(shims.clone());
+    if let Err(e) = environment::ensure(&game.library_dirs, options.environment) {
+        eprintln!("error: failed to restart with the library path set: {}", e);
+        std::process::exit(report::EXIT_FAILED);
     }
-
-    pub unsafe fn find_pattern(&self, pattern: &str) -> Option<usize> {
-        use regex::bytes::Regex;
-        use std::iter::once;
-        // Credits: https://github.com/frk1/hazedumper-rs/blob/master/src/memlib/findpattern.rs
-        let res = once("(?s-u)".to_string())
-            .chain(pattern.split_whitespace().map(|x| match &x {
-                &"?" | &"??" => ".".to_string(),
-                x => format!("\\x{}", x),
-            }))
-            .collect::<Vec<_>>()
-            .join("");
-        let base = self.address as *const u8;
-        let slice = std::slice::from_raw_parts(base, self.size);
-        let offset = Regex::new(&res).ok()?.find(slice)?.start();
-        Some(base.add(offset) as usize)
+    if let Some(shims) = &shims {
+        match shim::generate(&mut game, shims) {
+            Ok(stubs) => {
+                for stub in stubs {
+                    match &stub.symbols[..] {
+                        [] => eprintln!("warning: stubbed {}", stub.library),
+                        symbols => eprintln!(
+                            "warning: stubbed {} with {} symbol(s), stubbed functions abort when called: {}",
+                            stub.library,
+                            symbols.len(),
+                            symbols.join(", ")
+                        ),
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(report::EXIT_FAILED);
+            }
+        }
     }
-}
 
-extern "C" fn callback(info: *mut dl_phdr_info, size: usize, data: *mut c_void) -> i32 {
-    let context =
-        unsafe { (data as *mut CallbackContext).as_mut() }.expect("Modulelist was invalid!");
-    let pagesize = context.pagesize;
+    // Compiled up front so a broken rule file is a usage error, not a
+    // failed dump
+    let rules = match &options.yara[..] {
+        [] => None,
+        paths => match RuleSet::load(paths) {
+            Ok(rules) => Some(rules),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(report::EXIT_USAGE);
+            }
+        },
+    };
 
-    // (In)sanity check: Assert that the page size is a power of 2
-    // i.e. 8: 1000 & 0111 = 0000
-    debug_assert_eq!((pagesize & (pagesize - 1)), 0);
-    // (In)sanity check: Have the bindings been generated properly?
-    debug_assert_eq!(std::mem::size_of::<dl_phdr_info>(), size);
+    let config = match &options.config {
+        None => None,
+        Some(path) => match Config::load(path) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(report::EXIT_USAGE);
+            }
+        },
+    };
 
-    let info = unsafe { info.as_ref() }.expect("Invalid module pointer passed!");
+    let anchors = match verify::anchors(options.verify, &options.anchors) {
+        Ok(anchors) => anchors,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(report::EXIT_USAGE);
+        }
+    };
 
-    // Non-zero return values cause dl_iterate_phdr to abort
-    Module::new(info, pagesize)
-        .and_then(|module| {
-            context.modules.insert(0, module);
-            Some(0)
-        })
-        .unwrap_or(1)
-}
+    let mut signatures = Vec::new();
+    let loaded = config
+        .iter()
+        .flat_map(|config| &config.signatures)
+        .map(|entry| entry.signature().map(|signature| vec![signature]))
+        .chain(
+            options
+                .signature_files
+                .iter()
+                .map(|path| signature_file::load(path)),
+        );
+    for result in loaded {
+        match result {
+            Ok(found) => signatures.extend(found),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(report::EXIT_USAGE);
+            }
+        }
+    }
+    for module in &options.modules {
+        if let Err(e) = game.add_module(module) {
+            eprintln!("error: {}", e);
+            std::process::exit(report::EXIT_USAGE);
+        }
+    }
+    let client_name = game.client_name();
+    for signature in signatures {
+        match &signature.module {
+            Some(module) if *module != client_name => match game.add_module(module) {
+                Ok(()) => options.signatures.push(signature),
+                Err(e) => {
+                    eprintln!("error: {}: {}", signature.name, e);
+                    std::process::exit(report::EXIT_USAGE);
+                }
+            },
+            _ => options.signatures.push(signature),
+        }
+    }
 
-fn main() {
-    if let Some(gamedir) = &std::env::args().nth(1) {
-        let library: *mut c_void = unsafe {
-            dlopen(
-                "client_panorama_client.so\0".as_ptr() as *const c_char,
-                libc::RTLD_LAZY | libc::RTLD_GLOBAL,
-            )
-        };
-        println!("Client: {:?}", library);
-        let mut context = CallbackContext::new();
+    let previous = match &options.previous {
+        None => None,
+        Some(path) => match ElfFile::open(path) {
+            Ok(elf) => Some(elf),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(report::EXIT_USAGE);
+            }
+        },
+    };
 
-        unsafe {
-            dl_iterate_phdr(Some(callback), &mut context as *mut _ as *mut c_void);
+    let sandbox = if options.sandbox || options.confine {
+        let read_only = std::iter::once(options.gamedir.clone())
+            .chain(std::env::var_os("HOME").map(PathBuf::from))
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+        Some(Sandbox {
+            namespaces: options.sandbox,
+            read_only,
+            confine: options.confine,
+            writable: vec![std::env::temp_dir()],
+        })
+    } else {
+        None
+    };
+
+    let cache = options.cache.clone().map(|dir| Cache { dir });
+    let key = match cache
+        .as_ref()
+        .map(|_| cache_key(&game, &options))
+        .transpose()
+    {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("warning: not caching: {}", e);
+            None
+        }
+    };
+    let cached = match (&cache, &key) {
+        (Some(cache), Some(key)) => cache.load(key),
+        _ => None,
+    };
+    // Classes are written as they're read unless something needs all of
+    // them at once
+    let streaming = options.format == Format::Jsonl
+        && cache.is_none()
+        && config.is_none()
+        && anchors.is_empty()
+        && options.history.is_none()
+        && options.git.is_none()
+        && options.compress.is_none()
+        && !options.strict;
+    let mut streamed_conflicts = Vec::new();
+    let mut outcome = match cached {
+        Some(dump) => {
+            eprintln!("note: reusing the cached dump {}", key.unwrap_or_default());
+            Outcome { dump, error: None }
         }
+        None => {
+            let job = |sender: &mut Sender| {
+                dump(
+                    sender,
+                    &game,
+                    &options,
+                    rules.as_ref(),
+                    previous.as_ref(),
+                    sandbox.as_ref(),
+                )
+            };
+            let outcome = if streaming {
+                worker::stream(options.timeout, job, &mut |class| {
+                    streamed_conflicts.extend(validate::class_conflicts(&class));
+                    output::write_line(&mut std::io::stdout().lock(), &Line::Class(&class))
+                        .expect("failed to write the dump");
+                })
+            } else {
+                worker::run(options.timeout, job)
+            }
+            .expect("failed to start the worker process");
+            if let (Some(cache), Some(key), None) = (&cache, &key, &outcome.error) {
+                if let Err(e) = cache.store(key, &outcome.dump) {
+                    eprintln!("warning: {}", e);
+                }
+            }
+            outcome
+        }
+    };
+    if options.probe_symbols && outcome.error.is_some() {
+        match worker::run(options.timeout, |sender| probe(sender, &game)) {
+            Ok(probed) => {
+                outcome.dump.problems.extend(probed.dump.problems);
+                if let Some(error) = probed.error {
+                    eprintln!("warning: probing symbols failed: {}", error);
+                }
+            }
+            Err(e) => eprintln!("warning: failed to start the worker process: {}", e),
+        }
+    }
+    if let Some(config) = &config {
+        let (netvars, missing) = config.netvars(&outcome.dump);
+        outcome.dump.netvars = netvars;
+        outcome.dump.problems.extend(missing);
+    }
+    // A dump read with a wrong layout isn't worth recording
+    let failed = verify::check(&outcome.dump, &anchors);
+    let anchors_held = failed.is_empty();
+    if !anchors_held && (options.history.is_some() || options.git.is_some()) {
+        eprintln!("warning: anchors failed, not recorded");
+    }
+    outcome.dump.problems.extend(failed);
+    if let Some(source) = &options.compare {
+        match Published::load(source) {
+            Ok(published) => report_comparison(&published.compare(&outcome.dump), source),
+            Err(e) => eprintln!("warning: not compared: {}", e),
+        }
+    }
+    match Metadata::collect(&game, &options.signatures) {
+        Ok(metadata) => outcome.dump.metadata = Some(metadata),
+        Err(e) => eprintln!("warning: no metadata: {}", e),
+    }
+    if let (Some(dir), None, true) = (&options.history, &outcome.error, anchors_held) {
+        let history = History { dir: dir.clone() };
+        if let Err(e) = history.record(&outcome.dump) {
+            eprintln!("warning: not recorded in the history: {}", e);
+        }
+    }
+    if let (Some(repo), None, true) = (&options.git, &outcome.error, anchors_held) {
+        match git::commit(repo, &outcome.dump, options.format) {
+            Ok(Some(subject)) => eprintln!("note: committed {}", subject),
+            Ok(None) => eprintln!("note: nothing changed, not committed"),
+            Err(e) => eprintln!("warning: not committed: {}", e),
+        }
+    }
+    let conflicts = validate::conflicts(&outcome.dump);
+    outcome.dump.problems.extend(conflicts);
+    outcome.dump.problems.extend(streamed_conflicts);
+    let problems = &outcome.dump.problems;
 
-        let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
-        println!("Pagesize: {:#X}", pagesize);
+    if options.strict {
+        let failure = match (&outcome.error, problems.first()) {
+            (Some(error), _) => Some((error.name(), error.to_string(), error.exit_code())),
+            (None, Some(problem)) => Some((
+                problem.kind.name(),
+                problem.to_string(),
+                problem.kind.exit_code(),
+            )),
+            (None, None) => None,
+        };
+        if let Some((error, message, exit_code)) = failure {
+            let report = ErrorReport {
+                error,
+                message,
+                exit_code,
+                problems,
+            };
+            eprintln!("{}", serde_json::to_string(&report).unwrap());
+            std::process::exit(exit_code);
+        }
+    }
 
-        let client = context
-            .modules
-            .iter()
-            .find(|m| m.deref().name.ends_with("panorama_client.so"))
-            .and_then(|module| unsafe { module.find_pattern("91 48 8B 05 ? ? ? ? 8B 53 14") })
-            .unwrap();
-        // g_pClientClassHead
-        // 91 48 8B 05 ? ? ? ? 8B 53 14
-        println!("{:#X?}", client);
-        let off_client = unsafe { ((client + 4) as *const u32).read() };
-        println!("{:#X?}", off_client);
-        println!("{:#X?}", off_client as usize + client + 8);
-
-        let client = (off_client as usize + client + 8) as *const *const *const ClientClass;
-        if let Some(client) = unsafe { client.as_ref() } {
-            let class = unsafe { client.read() };
-
-            let iter = ClientClassIterator { current: class };
-            let classes: Vec<_> = iter
-                .filter_map(|c| unsafe { c.as_ref() })
-                .map(|c| (c, unsafe { c.m_pRecvTable.as_ref() }))
-                .filter_map(|(c, r)| r.and_then(|t| Some((c, t))))
-                .collect();
-
-            println!("{:#?}", classes);
+    match options.compress {
+        None if streaming => output::write_rest(&mut std::io::stdout().lock(), &outcome.dump)
+            .expect("failed to write the dump"),
+        None => output::write(&mut std::io::stdout().lock(), &outcome.dump, options.format)
+            .expect("failed to write the dump"),
+        Some(compression) => {
+            let mut dump = Vec::new();
+            output::write(&mut dump, &outcome.dump, options.format)
+                .expect("writing into memory doesn't fail");
+            match compression.compress(&dump) {
+                Ok(compressed) => std::io::stdout()
+                    .lock()
+                    .write_all(&compressed)
+                    .expect("failed to write the dump"),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(report::EXIT_FAILED);
+                }
+            }
         }
-    } else {
-        eprintln!("usage: csgobot <path to CS:GO>");
+    }
+    if options.format == Format::Text {
+        for problem in problems {
+            eprintln!("warning: {}", problem);
+        }
+    }
+    if let Some(error) = outcome.error {
+        eprintln!("error: {}", error);
+        std::process::exit(error.exit_code());
     }
 }
@@ -0,0 +1,356 @@
+//! `make-sig`: generates a signature for an address in a known-good build,
+//! so a broken one can be replaced without working it out by hand.
+//!
+//! Patterns are made of whole instructions starting at the address, with
+//! the bytes that change between builds wildcarded: RIP-relative
+//! displacements, branch offsets and anything a relocation writes to. The
+//! pattern grows an instruction at a time until it matches exactly once in
+//! the module's code.
+//!
+//! Data has no bytes of its own worth matching, so for an address outside
+//! the code the pattern is made for an instruction referencing it instead,
+//! along with the `rel32` that leads back to the address.
+
+use crate::elf::ElfFile;
+use crate::pattern::{Pattern, Scanner};
+use crate::resolve::Rel32;
+use crate::signature::Signature;
+use crate::symbols::demangle;
+use iced_x86::{Code as Opcode, Decoder, DecoderOptions};
+use std::path::Path;
+
+/// Longest pattern worth producing, in bytes.
+pub const DEFAULT_MAX_LEN: usize = 64;
+/// References to a data address that get a pattern attempted.
+const MAX_REFERENCES: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct Generated {
+    /// Where the pattern matches, relative to the module's base.
+    pub start: usize,
+    pub pattern: Pattern,
+    /// How to get back to the requested address, if the pattern is for code
+    /// referencing it.
+    pub resolve: Option<Rel32>,
+}
+
+/// One decoded instruction, with the bytes that change between builds
+/// wildcarded.
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    /// Relative to the module's base.
+    pub start: usize,
+    pub bytes: Vec<Option<u8>>,
+    /// Where a RIP-relative displacement starts within `bytes`, and what
+    /// it points to.
+    pub rip_relative: Option<(usize, usize)>,
+}
+
+/// A module's executable segments, as stored in its file.
+pub struct Code<'a> {
+    /// Relative to the module's base.
+    segments: Vec<(usize, &'a [u8])>,
+    /// Sorted.
+    relocations: Vec<usize>,
+}
+
+impl<'a> Code<'a> {
+    pub fn new(elf: &'a ElfFile) -> Self {
+        Code::from_segments(elf.code().collect(), elf.relocations.clone())
+    }
+
+    /// `segments` as the executable segments with their starts, and
+    /// `relocations` in any order.
+    pub fn from_segments(segments: Vec<(usize, &'a [u8])>, mut relocations: Vec<usize>) -> Self {
+        relocations.sort_unstable();
+        Code {
+            segments,
+            relocations,
+        }
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.segment(address).is_some()
+    }
+
+    fn segment(&self, address: usize) -> Option<(usize, &'a [u8])> {
+        self.segments
+            .iter()
+            .copied()
+            .find(|(start, bytes)| (*start..start + bytes.len()).contains(&address))
+    }
+
+    /// Decodes the instructions from `start` on, up to the first invalid
+    /// one or the end of the segment.
+    pub fn instructions(&self, start: usize) -> impl Iterator<Item = Instruction> + '_ {
+        let (segment, bytes) = self.segment(start).unwrap_or((start, &[]));
+        let mut decoder = Decoder::with_ip(
+            64,
+            &bytes[start - segment..],
+            start as u64,
+            DecoderOptions::NONE,
+        );
+        std::iter::from_fn(move || {
+            if !decoder.can_decode() {
+                return None;
+            }
+            let instruction = decoder.decode();
+            if instruction.code() == Opcode::INVALID {
+                return None;
+            }
+            let offsets = decoder.get_constant_offsets(&instruction);
+            let mut variable = Vec::new();
+            let mut rip_relative = None;
+            if instruction.is_ip_rel_memory_operand() && offsets.has_displacement() {
+                let offset = offsets.displacement_offset();
+                variable.push(offset..offset + offsets.displacement_size());
+                rip_relative = Some((offset, instruction.ip_rel_memory_address() as usize));
+            }
+            if instruction.near_branch_target() != 0 && offsets.has_immediate() {
+                let offset = offsets.immediate_offset();
+                variable.push(offset..offset + offsets.immediate_size());
+            }
+
+            let at = instruction.ip() as usize;
+            let bytes = (0..instruction.len())
+                .map(|i| {
+                    let wildcard =
+                        variable.iter().any(|r| r.contains(&i)) || self.relocated(at + i);
+                    Some(bytes[at - segment + i]).filter(|_| !wildcard)
+                })
+                .collect();
+            Some(Instruction {
+                start: at,
+                bytes,
+                rip_relative,
+            })
+        })
+    }
+
+    fn relocated(&self, address: usize) -> bool {
+        let index = self.relocations.partition_point(|&r| r <= address);
+        index > 0 && address < self.relocations[index - 1] + 8
+    }
+
+    /// Where `pattern` matches, relative to the module's base, stopping
+    /// after `limit` matches.
+    pub fn find(&self, pattern: &Pattern, limit: usize) -> Vec<usize> {
+        self.segments
+            .iter()
+            .flat_map(|(start, bytes)| pattern.find_iter(bytes).map(move |m| start + m))
+            .take(limit)
+            .collect()
+    }
+
+    /// Finds every match of each of `patterns` in a single pass, relative to
+    /// the module's base.
+    pub fn scan(&self, patterns: &[Pattern]) -> Vec<Vec<usize>> {
+        let scanner = Scanner::new(patterns);
+        let mut matches = vec![Vec::new(); patterns.len()];
+        for (start, bytes) in &self.segments {
+            for (all, found) in matches.iter_mut().zip(scanner.scan(bytes)) {
+                all.extend(found.into_iter().map(|offset| start + offset));
+            }
+        }
+        matches
+    }
+
+    /// Instructions with a RIP-relative operand pointing at `target`, and
+    /// how to resolve it from the start of each.
+    pub fn references(&self, target: usize) -> Vec<(usize, Rel32)> {
+        self.segments
+            .iter()
+            .flat_map(|&(start, _)| self.instructions(start))
+            .filter_map(|instruction| match instruction.rip_relative {
+                Some((disp_offset, to)) if to == target => Some((
+                    instruction.start,
+                    Rel32 {
+                        disp_offset,
+                        instr_len: instruction.bytes.len(),
+                    },
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Makes a unique pattern of at most `max_len` bytes for `rva`.
+pub fn make_sig(elf: &ElfFile, rva: usize, max_len: usize) -> Result<Generated, String> {
+    let code = Code::new(elf);
+    if code.contains(rva) {
+        return Ok(Generated {
+            start: rva,
+            pattern: grow(&code, rva, max_len)?,
+            resolve: None,
+        });
+    }
+
+    let references = code.references(rva);
+    if references.is_empty() {
+        return Err(format!("{:#X} isn't code and no code references it", rva));
+    }
+    references
+        .iter()
+        .take(MAX_REFERENCES)
+        .filter_map(|&(start, resolve)| {
+            Some(Generated {
+                start,
+                pattern: grow(&code, start, max_len).ok()?,
+                resolve: Some(resolve),
+            })
+        })
+        .min_by_key(|generated| generated.pattern.len())
+        .ok_or_else(|| {
+            format!(
+                "none of the {} references to {:#X} has a unique pattern within {} bytes",
+                references.len(),
+                rva,
+                max_len
+            )
+        })
+}
+
+/// The shortest run of whole instructions from `start` that only matches
+/// there.
+pub fn grow(code: &Code, start: usize, max_len: usize) -> Result<Pattern, String> {
+    let mut pattern: Vec<Option<u8>> = Vec::new();
+    for instruction in code.instructions(start) {
+        pattern.extend(instruction.bytes);
+        if pattern.len() > max_len {
+            break;
+        }
+        // Trailing wildcards don't narrow anything down
+        let end = pattern
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1);
+        if let Ok(candidate) = Pattern::new(pattern[..end].to_vec()) {
+            if code.find(&candidate, 2).len() == 1 {
+                return Ok(candidate);
+            }
+        }
+    }
+    Err(format!(
+        "no unique pattern for {:#X} within {} bytes",
+        start, max_len
+    ))
+}
+
+/// Runs `make-sig` and returns the exit code.
+pub fn run(library: &Path, target: &str, name: Option<&str>, max_len: usize) -> i32 {
+    let elf = match ElfFile::open(library) {
+        Ok(elf) => elf,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return crate::report::EXIT_FAILED;
+        }
+    };
+    let rva = match parse_rva(target) {
+        Some(rva) => rva,
+        None => match elf
+            .symbol(target)
+            .or_else(|| elf.symbols.iter().find(|s| demangle(&s.name) == target))
+        {
+            Some(symbol) => symbol.address,
+            None => {
+                eprintln!("error: {} has no symbol {}", library.display(), target);
+                return crate::report::EXIT_USAGE;
+            }
+        },
+    };
+
+    match make_sig(&elf, rva, max_len) {
+        Ok(generated) => {
+            let signature = Signature {
+                name: name.map_or_else(|| format!("sig_{:X}", rva), str::to_string),
+                module: None,
+                pattern: generated.pattern.clone(),
+                region: None,
+                resolve: generated.resolve,
+                steps: Vec::new(),
+            };
+            println!("{}", signature);
+            eprintln!(
+                "unique in {} bytes, matching at +{:#X}",
+                generated.pattern.len(),
+                generated.start
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            crate::report::EXIT_SIGNATURE_NOT_FOUND
+        }
+    }
+}
+
+/// `0x`-prefixed hex or decimal.
+fn parse_rva(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two runs of `mov rax, [rip + 0x10]; call; mov reg, rax; ret` that
+    /// only differ in the register.
+    const CODE: [u8; 32] = [
+        0x48, 0x8B, 0x05, 0x10, 0x00, 0x00, 0x00, 0xE8, 0x00, 0x00, 0x00, 0x00, 0x48, 0x89, 0xC7,
+        0xC3, 0x48, 0x8B, 0x05, 0x10, 0x00, 0x00, 0x00, 0xE8, 0x00, 0x00, 0x00, 0x00, 0x48, 0x89,
+        0xC6, 0xC3,
+    ];
+
+    #[test]
+    fn wildcards_what_changes_between_builds() {
+        let code = Code::from_segments(vec![(0x1000, &CODE[..])], Vec::new());
+        let instructions: Vec<_> = code.instructions(0x1000).take(2).collect();
+        assert_eq!(instructions[0].start, 0x1000);
+        assert_eq!(
+            instructions[0].bytes,
+            [Some(0x48), Some(0x8B), Some(0x05), None, None, None, None]
+        );
+        assert_eq!(instructions[0].rip_relative, Some((3, 0x1017)));
+        assert_eq!(instructions[1].bytes, [Some(0xE8), None, None, None, None]);
+
+        // mov rax, imm64 with a pointer relocated into it
+        let absolute = [0x48, 0xB8, 1, 2, 3, 4, 5, 6, 7, 8, 0xC3];
+        let code = Code::from_segments(vec![(0x2000, &absolute[..])], vec![0x2002]);
+        let instruction = code.instructions(0x2000).next().unwrap();
+        assert_eq!(instruction.bytes[..2], [Some(0x48), Some(0xB8)]);
+        assert!(instruction.bytes[2..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn grows_until_unique() {
+        let code = Code::from_segments(vec![(0x1000, &CODE[..])], Vec::new());
+        let pattern = grow(&code, 0x1000, DEFAULT_MAX_LEN).unwrap();
+        assert_eq!(pattern.to_string(), "48 8B 05 ? ? ? ? E8 ? ? ? ? 48 89 C7");
+        assert_eq!(code.find(&pattern, 2), [0x1000]);
+        assert!(grow(&code, 0x1000, 8).is_err());
+    }
+
+    #[test]
+    fn finds_references() {
+        let code = Code::from_segments(vec![(0x1000, &CODE[..])], Vec::new());
+        let rel32 = Rel32 {
+            disp_offset: 3,
+            instr_len: 7,
+        };
+        assert_eq!(code.references(0x1017), [(0x1000, rel32)]);
+        assert_eq!(code.references(0x1027), [(0x1010, rel32)]);
+        assert!(code.references(0x1020).is_empty());
+    }
+
+    #[test]
+    fn parses_rvas() {
+        assert_eq!(parse_rva("0x1F00"), Some(0x1F00));
+        assert_eq!(parse_rva("0X1f00"), Some(0x1F00));
+        assert_eq!(parse_rva("4096"), Some(4096));
+        assert_eq!(parse_rva("_ZN7CGlobal4initEv"), None);
+    }
+}
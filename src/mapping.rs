@@ -0,0 +1,82 @@
+//! Files mapped into memory rather than read into it, for the libraries we
+//! only parse and scan. The kernel pages in what's looked at and can drop it
+//! again, so batch-dumping a pile of historical builds doesn't keep a copy
+//! of every library around.
+//!
+//! The mapping is private and read-only. A file truncated while it's mapped
+//! faults on access, which nothing guards against: the libraries are a
+//! game's, which nobody should be updating while it's dumped.
+
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr::NonNull;
+
+pub struct Mapping {
+    /// Dangling for empty files, which can't be mapped.
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// Nothing ever writes through the mapping
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("failed to read {}: {}", path.display(), e);
+        let file = File::open(path).map_err(error)?;
+        let len = file.metadata().map_err(error)?.len() as usize;
+        if len == 0 {
+            return Ok(Mapping {
+                ptr: NonNull::dangling(),
+                len,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(format!(
+                "failed to map {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(Mapping {
+            ptr: NonNull::new(ptr as *mut u8).expect("mmap doesn't map at 0"),
+            len,
+        })
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len) };
+        }
+    }
+}
+
+impl Debug for Mapping {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Mapping({} bytes at {:p})", self.len, self.ptr)
+    }
+}
@@ -0,0 +1,99 @@
+//! Fault-tolerant reads of our own address space.
+//!
+//! A wrong signature or a stale struct layout hands us garbage pointers, and
+//! dereferencing one of those kills the whole dump with a SIGSEGV. Going
+//! through `/proc/self/mem` lets the kernel perform the access instead: an
+//! unmapped address comes back as an error we can report and skip.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, MaybeUninit};
+use std::os::unix::fs::FileExt;
+
+/// Strings longer than this are treated as garbage rather than names.
+pub const MAX_CSTR_LEN: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadError {
+    Null,
+    Unmapped { address: usize, len: usize },
+    Unterminated { address: usize },
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ReadError::Null => write!(f, "null pointer"),
+            ReadError::Unmapped { address, len } => {
+                write!(f, "unreadable memory at {:#X} ({} bytes)", address, len)
+            }
+            ReadError::Unterminated { address } => write!(
+                f,
+                "string at {:#X} is not terminated within {} bytes",
+                address, MAX_CSTR_LEN
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+pub struct Memory {
+    file: File,
+}
+
+impl Memory {
+    pub fn open() -> io::Result<Self> {
+        Ok(Memory {
+            file: File::open("/proc/self/mem")?,
+        })
+    }
+
+    pub fn read_bytes(&self, address: usize, buf: &mut [u8]) -> Result<(), ReadError> {
+        // The first page is never mapped, so don't bother asking the kernel
+        if address < 0x1000 {
+            return Err(ReadError::Null);
+        }
+        self.file
+            .read_exact_at(buf, address as u64)
+            .map_err(|_| ReadError::Unmapped {
+                address,
+                len: buf.len(),
+            })
+    }
+
+    /// Reads a `T` from `address`.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be valid for any bit pattern, which is why the structures in
+    /// [`sdk`](crate::sdk) avoid `bool` and non-nullable function pointers.
+    pub unsafe fn read<T: Copy>(&self, address: usize) -> Result<T, ReadError> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let buf = std::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>());
+        self.read_bytes(address, buf)?;
+        Ok(value.assume_init())
+    }
+
+    /// Reads a NUL-terminated string of at most [`MAX_CSTR_LEN`] bytes,
+    /// without the terminator.
+    pub fn read_cstr(&self, address: usize) -> Result<Vec<u8>, ReadError> {
+        let mut result = Vec::new();
+        let mut cursor = address;
+        while result.len() < MAX_CSTR_LEN {
+            // Never read across a page boundary we don't need: the string may
+            // end right before an unmapped page.
+            let chunk = (0x1000 - cursor % 0x1000).min(MAX_CSTR_LEN - result.len());
+            let mut buf = vec![0u8; chunk];
+            self.read_bytes(cursor, &mut buf)?;
+            if let Some(end) = buf.iter().position(|&b| b == 0) {
+                result.extend_from_slice(&buf[..end]);
+                return Ok(result);
+            }
+            result.extend_from_slice(&buf);
+            cursor += chunk;
+        }
+        Err(ReadError::Unterminated { address })
+    }
+}
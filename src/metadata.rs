@@ -0,0 +1,100 @@
+//! Where a dump came from, written at the top of structured output so an
+//! archived dump can still be traced back to the build it was made from.
+
+use crate::build::Build;
+use crate::cache;
+use crate::gamedir::Game;
+use crate::sha256;
+use crate::signature::Signature;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub tool_version: String,
+    /// When the dump was made, or taken from the cache, in UTC.
+    pub timestamp: String,
+    pub gamedir: PathBuf,
+    pub client: File,
+    /// The other modules that were scanned.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<File>,
+    /// Every signature that was looked for, as it would be given to
+    /// `--signature`, prefixed with its module if it isn't the client.
+    pub signatures: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<Build>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct File {
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl File {
+    pub fn describe(path: &Path) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("failed to read {}: {}", path.display(), e);
+        let size = std::fs::metadata(path).map_err(error)?.len();
+        let digest = cache::hash_file(path).map_err(error)?;
+        Ok(File {
+            path: path.to_path_buf(),
+            sha256: sha256::hex(&digest),
+            size,
+        })
+    }
+}
+
+impl Metadata {
+    pub fn collect(game: &Game, signatures: &[Signature]) -> Result<Self, String> {
+        Ok(Metadata {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: rfc3339(SystemTime::now()),
+            gamedir: game.dir.clone(),
+            client: File::describe(&game.client)?,
+            modules: game
+                .modules
+                .iter()
+                .map(|module| File::describe(module))
+                .collect::<Result<_, _>>()?,
+            signatures: signatures
+                .iter()
+                .map(|signature| match &signature.module {
+                    Some(module) => format!("{}:{}", module, signature),
+                    None => signature.to_string(),
+                })
+                .collect(),
+            build: Some(Build::identify(game)).filter(|build| !build.is_empty()),
+        })
+    }
+}
+
+/// `time` as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Howard Hinnant's civil_from_days, for days since 1970-01-01
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,244 @@
+use crate::memory::Memory;
+use crate::pattern::{Pattern, Scanner};
+use libc::{c_void, dl_iterate_phdr, dl_phdr_info};
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::ops::Range;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub address: usize,
+    /// Lossily converted, a module with an odd path is still a module.
+    pub name: String,
+    /// The `PT_LOAD` segments in address order. Whatever lies between them
+    /// isn't part of the module and usually isn't mapped at all.
+    pub segments: Vec<Segment>,
+}
+
+/// One mapped `PT_LOAD` segment, as the loader set it up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Absolute and page aligned.
+    pub range: Range<usize>,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+#[derive(Debug, Clone)]
+struct CallbackContext {
+    modules: Vec<Module>,
+    pagesize: u64,
+}
+
+impl CallbackContext {
+    fn new() -> Self {
+        CallbackContext {
+            modules: Vec::new(),
+            pagesize: pagesize(),
+        }
+    }
+}
+
+pub fn pagesize() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) }
+        .try_into()
+        .expect("page size doesn't fit into u64! This should *never* happen.")
+}
+
+impl Module {
+    pub fn new(info: &dl_phdr_info, pagesize: u64) -> Option<Self> {
+        let name = unsafe { CStr::from_ptr(info.dlpi_name) }.to_bytes();
+        let address = info.dlpi_addr as usize;
+        let mut segments: Vec<_> = (0..info.dlpi_phnum)
+            .filter_map(|i| unsafe { info.dlpi_phdr.add(i as usize).as_ref() })
+            .filter(|e| e.p_type == libc::PT_LOAD && e.p_memsz > 0)
+            .map(|e| {
+                // The mapping starts at the page holding p_vaddr and is
+                // rounded up to a whole page at the end
+                // https://github.com/lattera/glibc/blob/master/elf/dl-load.c#L1085
+                let start = e.p_vaddr & !(pagesize - 1);
+                let end = (e.p_vaddr + e.p_memsz + pagesize - 1) & !(pagesize - 1);
+                Segment {
+                    range: address + start as usize..address + end as usize,
+                    read: e.p_flags & libc::PF_R != 0,
+                    write: e.p_flags & libc::PF_W != 0,
+                    execute: e.p_flags & libc::PF_X != 0,
+                }
+            })
+            .collect();
+        if segments.is_empty() {
+            return None;
+        }
+        segments.sort_by_key(|s| s.range.start);
+        Some(Module {
+            address,
+            name: String::from_utf8_lossy(name).into_owned(),
+            segments,
+        })
+    }
+
+    /// The file this module was loaded from.
+    pub fn path(&self) -> PathBuf {
+        // The main executable is listed without a name
+        if self.name.is_empty() {
+            PathBuf::from("/proc/self/exe")
+        } else {
+            PathBuf::from(&self.name)
+        }
+    }
+
+    /// The segment `address` lies in, if any.
+    pub fn segment(&self, address: usize) -> Option<&Segment> {
+        self.segments.iter().find(|s| s.range.contains(&address))
+    }
+
+    /// Finds every match of each of `patterns` in the code of this module in
+    /// a single pass, returning absolute addresses in ascending order per
+    /// pattern.
+    ///
+    /// Only executable segments are scanned: signatures describe code, and
+    /// data sections are where they'd match by accident. Each segment is
+    /// still copied out page by page and every contiguous readable run is
+    /// scanned on its own, in case part of it isn't mapped after all.
+    pub fn scan(&self, memory: &Memory, patterns: &[Pattern]) -> Vec<Vec<usize>> {
+        let scanner = Scanner::new(patterns);
+        let mut matches = vec![Vec::new(); patterns.len()];
+        for (start, bytes) in self.code(memory) {
+            for (all, found) in matches.iter_mut().zip(scanner.scan(&bytes)) {
+                all.extend(found.into_iter().map(|offset| start + offset));
+            }
+        }
+        matches
+    }
+
+    /// Copies of the readable runs of this module's executable segments,
+    /// with their absolute addresses.
+    pub fn code(&self, memory: &Memory) -> Vec<(usize, Vec<u8>)> {
+        self.segments
+            .iter()
+            .filter(|s| s.execute)
+            .flat_map(|s| readable_runs(memory, s.range.clone()))
+            .collect()
+    }
+
+    /// Turns `range`, given as offsets from the module's base, into absolute
+    /// addresses, or `None` if it runs past the end of the last segment.
+    pub fn absolute(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        let end = self.segments.last()?.range.end;
+        let start = self.address.checked_add(range.start)?;
+        Some(start..self.address.checked_add(range.end)?).filter(|r| r.end <= end)
+    }
+
+    /// Like [`Module::scan`], but only looks at `range`, given as offsets
+    /// from the module's base, whatever segment that is in. Nothing matches
+    /// in a range that isn't within the module.
+    pub fn scan_range(
+        &self,
+        memory: &Memory,
+        patterns: &[Pattern],
+        range: Range<usize>,
+    ) -> Vec<Vec<usize>> {
+        let scanner = Scanner::new(patterns);
+        let mut matches = vec![Vec::new(); patterns.len()];
+        let range = match self.absolute(&range) {
+            Some(range) => range,
+            None => return matches,
+        };
+        for (start, bytes) in readable_runs(memory, range) {
+            for (all, found) in matches.iter_mut().zip(scanner.scan(&bytes)) {
+                all.extend(found.into_iter().map(|offset| start + offset));
+            }
+        }
+        matches
+    }
+}
+
+/// Copies the readable parts of `range` out of memory, merging adjacent
+/// pages into runs.
+fn readable_runs(memory: &Memory, range: Range<usize>) -> Vec<(usize, Vec<u8>)> {
+    let pagesize = pagesize() as usize;
+    let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut page = vec![0u8; pagesize];
+    let mut contiguous = false;
+
+    // Reads are done whole pages at a time, so whether a page is there
+    // doesn't depend on where in it the range starts
+    let first = range.start & !(pagesize - 1);
+    for address in (first..range.end).step_by(pagesize) {
+        if memory.read_bytes(address, &mut page).is_err() {
+            contiguous = false;
+            continue;
+        }
+        match runs.last_mut() {
+            Some((_, bytes)) if contiguous => bytes.extend_from_slice(&page),
+            _ => runs.push((address, page.clone())),
+        }
+        contiguous = true;
+    }
+
+    // Trim the runs back down to the range that was asked for
+    runs.into_iter()
+        .filter_map(|(start, mut bytes)| {
+            let from = range.start.saturating_sub(start).min(bytes.len());
+            bytes.truncate(range.end - start);
+            bytes.drain(..from);
+            (!bytes.is_empty()).then(|| (start + from, bytes))
+        })
+        .collect()
+}
+
+extern "C" fn callback(info: *mut dl_phdr_info, size: usize, data: *mut c_void) -> i32 {
+    let context =
+        unsafe { (data as *mut CallbackContext).as_mut() }.expect("Modulelist was invalid!");
+    let pagesize = context.pagesize;
+
+    // (In)sanity check: Assert that the page size is a power of 2
+    // i.e. 8: 1000 & 0111 = 0000
+    debug_assert_eq!((pagesize & (pagesize - 1)), 0);
+    // (In)sanity check: Have the bindings been generated properly?
+    debug_assert_eq!(std::mem::size_of::<dl_phdr_info>(), size);
+
+    let info = unsafe { info.as_ref() }.expect("Invalid module pointer passed!");
+
+    if let Some(module) = Module::new(info, pagesize) {
+        context.modules.insert(0, module);
+    }
+    // Non-zero return values cause dl_iterate_phdr to abort, and one odd
+    // module is no reason to lose all the ones after it
+    0
+}
+
+/// Lists every module currently mapped into the process.
+pub fn modules() -> Vec<Module> {
+    let mut context = CallbackContext::new();
+    unsafe {
+        dl_iterate_phdr(Some(callback), &mut context as *mut _ as *mut c_void);
+    }
+    context.modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_stays_within_the_segments() {
+        let module = Module {
+            address: 0x10000,
+            name: "libtest.so".to_string(),
+            segments: vec![Segment {
+                range: 0x10000..0x13000,
+                read: true,
+                write: false,
+                execute: true,
+            }],
+        };
+        assert_eq!(module.absolute(&(0x1000..0x2000)), Some(0x11000..0x12000));
+        assert_eq!(module.absolute(&(0x2000..0x3000)), Some(0x12000..0x13000));
+        assert_eq!(module.absolute(&(0x2000..0x3001)), None);
+        assert_eq!(module.absolute(&(0x1000..usize::MAX)), None);
+        assert_eq!(module.absolute(&(usize::MAX - 1..usize::MAX)), None);
+    }
+}
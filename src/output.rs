@@ -0,0 +1,163 @@
+//! Writing dumps in the formats selected with `--format`.
+
+use crate::metadata::Metadata;
+use crate::report::Problem;
+use crate::signature::SignatureMatch;
+use crate::template;
+use crate::walk::{Class, Dump, Table};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The indented tree we always printed.
+    Text,
+    Json,
+    /// One JSON object per line, see [`Line`].
+    Jsonl,
+    /// An 010 Editor template, see [`crate::template`].
+    Template,
+}
+
+/// A line of `--format jsonl`, e.g. `{"class": {...}}`. Classes come first,
+/// in the order they were read, so they can be written as they arrive;
+/// everything else follows once the dump is done.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Line<'a> {
+    Class(&'a Class),
+    Signature(&'a SignatureMatch),
+    Problem(&'a Problem),
+    Netvars(&'a BTreeMap<String, i64>),
+    Offsets(&'a BTreeMap<String, usize>),
+    Metadata(&'a Metadata),
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            "010" => Ok(Format::Template),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+pub fn write(out: &mut impl Write, dump: &Dump, format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => write_text(out, dump),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, dump)?;
+            writeln!(out)
+        }
+        Format::Jsonl => {
+            for class in &dump.classes {
+                write_line(out, &Line::Class(class))?;
+            }
+            write_rest(out, dump)
+        }
+        Format::Template => template::write(out, dump),
+    }
+}
+
+pub fn write_line(out: &mut impl Write, line: &Line) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    writeln!(out)
+}
+
+/// The lines for everything in `dump` but its classes.
+pub fn write_rest(out: &mut impl Write, dump: &Dump) -> io::Result<()> {
+    for signature in &dump.signatures {
+        write_line(out, &Line::Signature(signature))?;
+    }
+    for problem in &dump.problems {
+        write_line(out, &Line::Problem(problem))?;
+    }
+    if !dump.netvars.is_empty() {
+        write_line(out, &Line::Netvars(&dump.netvars))?;
+    }
+    if !dump.offsets.is_empty() {
+        write_line(out, &Line::Offsets(&dump.offsets))?;
+    }
+    if let Some(metadata) = &dump.metadata {
+        write_line(out, &Line::Metadata(metadata))?;
+    }
+    Ok(())
+}
+
+fn write_text(out: &mut impl Write, dump: &Dump) -> io::Result<()> {
+    for class in &dump.classes {
+        if let Some(table) = &class.table {
+            write!(out, "{} ({}) -> {}", class.name, class.id, table.name)?;
+            if class.confidence < 1.0 {
+                write!(out, " [confidence {:.2}]", class.confidence)?;
+            }
+            writeln!(out)?;
+            write_table(out, table, 1)?;
+        }
+    }
+    for signature in &dump.signatures {
+        let matches: Vec<_> = signature
+            .matches
+            .iter()
+            .map(|m| format!("+{:#X}", m))
+            .collect();
+        write!(
+            out,
+            "{} ({}): {}",
+            signature.name,
+            signature.module,
+            matches.join(", ")
+        )?;
+        if !signature.targets.is_empty() {
+            let targets: Vec<_> = signature
+                .targets
+                .iter()
+                .map(|t| format!("+{:#X}", t))
+                .collect();
+            write!(out, " -> {}", targets.join(", "))?;
+        }
+        if let Some(repaired) = &signature.repaired {
+            write!(out, " [repaired: {}]", repaired)?;
+        }
+        writeln!(out)?;
+    }
+    for (name, offset) in &dump.netvars {
+        writeln!(out, "{}: {:#X}", name, offset)?;
+    }
+    Ok(())
+}
+
+fn write_table(out: &mut impl Write, table: &Table, depth: usize) -> io::Result<()> {
+    for prop in &table.props {
+        match &prop.table {
+            Some(child) => {
+                writeln!(
+                    out,
+                    "{:indent$}{} @ {:#X} -> {}",
+                    "",
+                    prop.name,
+                    prop.offset,
+                    child.name,
+                    indent = depth * 4
+                )?;
+                write_table(out, child, depth + 1)?;
+            }
+            None => writeln!(
+                out,
+                "{:indent$}{} -> {:#X}",
+                "",
+                prop.name,
+                prop.offset,
+                indent = depth * 4
+            )?,
+        }
+    }
+    Ok(())
+}
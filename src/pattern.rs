@@ -0,0 +1,460 @@
+//! Byte patterns with wildcards, e.g. `91 48 8B 05 ? ? ? ? 8B 53 14`. See
+//! [`Pattern`]'s `FromStr` for the other formats that are understood.
+//!
+//! Scanning is Boyer-Moore-Horspool with wildcard support: a wildcard matches
+//! every byte, so it caps how far any mismatch may skip ahead. Patterns whose
+//! wildcards sit at the start (the usual "opcode, then operand" shape) still
+//! skip almost the full pattern length per step.
+//!
+//! When several patterns need to be found in the same image, [`Scanner`]
+//! does it in one pass: an Aho-Corasick automaton over each pattern's longest
+//! run of fixed bytes finds candidates, which are then checked against the
+//! whole pattern.
+//!
+//! Large haystacks are split into chunks that are scanned in parallel. Each
+//! chunk reads up to one pattern length past its end so matches straddling a
+//! boundary are still found, but only keeps the matches that start inside it.
+
+use aho_corasick::AhoCorasick;
+use rayon::prelude::*;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<Option<u8>>,
+    /// Horspool shift for each possible byte at the end of the window.
+    skip: Box<[usize; 256]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    Empty,
+    /// A token that's neither two hex digits nor `?`/`??`.
+    InvalidToken {
+        token: String,
+        position: usize,
+    },
+    /// Nothing but wildcards, which matches everywhere.
+    NoFixedBytes,
+    /// Code given without the mask that goes with it.
+    MissingMask,
+    MaskLength {
+        bytes: usize,
+        mask: usize,
+    },
+    /// A mask character that's neither `x` nor `?`.
+    InvalidMask {
+        character: char,
+        position: usize,
+    },
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PatternError::Empty => write!(f, "pattern is empty"),
+            PatternError::InvalidToken { token, position } => write!(
+                f,
+                "invalid byte {:?} at position {}, expected two hex digits or ?",
+                token, position
+            ),
+            PatternError::NoFixedBytes => write!(f, "pattern consists only of wildcards"),
+            PatternError::MissingMask => write!(f, "expected code followed by a mask"),
+            PatternError::MaskLength { bytes, mask } => write!(
+                f,
+                "code has {} bytes but the mask has {} characters",
+                bytes, mask
+            ),
+            PatternError::InvalidMask {
+                character,
+                position,
+            } => write!(
+                f,
+                "invalid mask character {:?} at position {}, expected x or ?",
+                character, position
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Accepts the formats signatures usually get passed around in:
+///
+/// - IDA style, space separated hex with `?` or `??` for wildcards:
+///   `48 8B 05 ? ? ? ?`
+/// - The same without spaces, as some plugins print them: `488B05????????`
+/// - Code and mask, as found in C sources: `"\x48\x8B\x05\x00" "xxx?"`,
+///   where the quotes are optional and `?` in the mask marks a wildcard
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let bytes = if s.starts_with("\\x") || s.starts_with("\"\\x") {
+            parse_code_mask(s)?
+        } else if !s.is_empty() && !s.contains(char::is_whitespace) && s.len() > 2 {
+            parse_compact(s)?
+        } else {
+            parse_spaced(s)?
+        };
+        Pattern::new(bytes)
+    }
+}
+
+fn parse_byte(token: &str, position: usize) -> Result<Option<u8>, PatternError> {
+    let invalid = || PatternError::InvalidToken {
+        token: token.to_string(),
+        position,
+    };
+    match token {
+        "?" | "??" => Ok(None),
+        _ if token.len() == 2 => u8::from_str_radix(token, 16)
+            .map(Some)
+            .map_err(|_| invalid()),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_spaced(s: &str) -> Result<Vec<Option<u8>>, PatternError> {
+    s.split_whitespace()
+        .enumerate()
+        .map(|(position, token)| parse_byte(token, position))
+        .collect()
+}
+
+fn parse_compact(s: &str) -> Result<Vec<Option<u8>>, PatternError> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return Err(PatternError::InvalidToken {
+            token: s.to_string(),
+            position: 0,
+        });
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| parse_byte(&s[i..i + 2], i / 2))
+        .collect()
+}
+
+fn parse_code_mask(s: &str) -> Result<Vec<Option<u8>>, PatternError> {
+    let mut parts = s
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.trim_matches('"'));
+    let (code, mask) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(code), Some(mask), None) => (code, mask),
+        _ => return Err(PatternError::MissingMask),
+    };
+
+    let code = code
+        .split("\\x")
+        .skip(1)
+        .enumerate()
+        .map(|(position, hex)| {
+            u8::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 2)
+                .ok_or_else(|| PatternError::InvalidToken {
+                    token: format!("\\x{}", hex),
+                    position,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if code.len() != mask.chars().count() {
+        return Err(PatternError::MaskLength {
+            bytes: code.len(),
+            mask: mask.chars().count(),
+        });
+    }
+    code.into_iter()
+        .zip(mask.chars())
+        .enumerate()
+        .map(|(position, (byte, mask))| match mask {
+            'x' | 'X' => Ok(Some(byte)),
+            '?' => Ok(None),
+            _ => Err(PatternError::InvalidMask {
+                character: mask,
+                position,
+            }),
+        })
+        .collect()
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match byte {
+                Some(byte) => write!(f, "{:02X}", byte)?,
+                None => write!(f, "?")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Pattern {
+    pub fn new(bytes: Vec<Option<u8>>) -> Result<Self, PatternError> {
+        if bytes.is_empty() {
+            return Err(PatternError::Empty);
+        }
+        if bytes.iter().all(Option::is_none) {
+            return Err(PatternError::NoFixedBytes);
+        }
+
+        let last = bytes.len() - 1;
+        // Everything after the last wildcard (not counting the final byte,
+        // which is what we look up) decides the shift; the wildcard itself
+        // matches anything, so nothing may skip past it.
+        let wildcard = bytes[..last].iter().rposition(Option::is_none);
+        let default = match wildcard {
+            Some(i) => last - i,
+            None => bytes.len(),
+        };
+        let mut skip = Box::new([default; 256]);
+        let start = wildcard.map_or(0, |i| i + 1);
+        for (i, byte) in bytes.iter().enumerate().take(last).skip(start) {
+            if let Some(byte) = byte {
+                skip[*byte as usize] = last - i;
+            }
+        }
+
+        Ok(Pattern { bytes, skip })
+    }
+
+    // Patterns are never empty, `new` rejects that
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn matches_at(&self, haystack: &[u8], position: usize) -> bool {
+        haystack
+            .get(position..position + self.len())
+            .is_some_and(|window| {
+                window
+                    .iter()
+                    .zip(&self.bytes)
+                    .all(|(b, p)| p.is_none_or(|p| p == *b))
+            })
+    }
+
+    /// Offset and length of the longest run of fixed bytes.
+    fn anchor(&self) -> (usize, usize) {
+        let mut best = (0, 0);
+        let mut start = 0;
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if byte.is_none() {
+                start = i + 1;
+            } else if i + 1 - start > best.1 {
+                best = (start, i + 1 - start);
+            }
+        }
+        best
+    }
+
+    /// Offsets of every (possibly overlapping) match in `haystack`, ascending.
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let last = self.len() - 1;
+        let mut position = 0;
+        std::iter::from_fn(move || {
+            while position + last < haystack.len() {
+                let current = position;
+                position += self.skip[haystack[current + last] as usize];
+                if self.matches_at(haystack, current) {
+                    return Some(current);
+                }
+            }
+            None
+        })
+    }
+}
+
+/// How much of the haystack one parallel task scans.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Finds any number of patterns in a single pass over the haystack.
+pub struct Scanner<'a> {
+    patterns: &'a [Pattern],
+    /// Where each pattern's anchor starts within the pattern.
+    anchors: Vec<usize>,
+    /// How far a match can reach past the chunk it starts in.
+    overlap: usize,
+    automaton: AhoCorasick,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(patterns: &'a [Pattern]) -> Self {
+        let (anchors, literals): (Vec<_>, Vec<_>) = patterns
+            .iter()
+            .map(|pattern| {
+                let (start, len) = pattern.anchor();
+                let literal: Vec<u8> = pattern.bytes[start..start + len]
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .collect();
+                (start, literal)
+            })
+            .unzip();
+        let automaton =
+            AhoCorasick::new(literals).expect("anchors are short enough for any automaton");
+        let overlap = patterns.iter().map(Pattern::len).max().unwrap_or(1) - 1;
+        Scanner {
+            patterns,
+            anchors,
+            overlap,
+            automaton,
+        }
+    }
+
+    /// Offsets of every match of every pattern in `haystack`, indexed like
+    /// the patterns, each list ascending.
+    pub fn scan(&self, haystack: &[u8]) -> Vec<Vec<usize>> {
+        let chunks: Vec<_> = (0..haystack.len())
+            .step_by(CHUNK_SIZE)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|start| {
+                let end = (start + CHUNK_SIZE).min(haystack.len());
+                let window = &haystack[start..(end + self.overlap).min(haystack.len())];
+                (start, self.scan_chunk(window, end - start))
+            })
+            .collect();
+
+        // Chunks come back in order, so concatenating keeps every list sorted
+        let mut matches = vec![Vec::new(); self.patterns.len()];
+        for (start, found) in chunks {
+            for (all, found) in matches.iter_mut().zip(found) {
+                all.extend(found.into_iter().map(|offset| start + offset));
+            }
+        }
+        matches
+    }
+
+    /// Like `scan`, but only keeps matches starting before `limit`.
+    fn scan_chunk(&self, haystack: &[u8], limit: usize) -> Vec<Vec<usize>> {
+        // Horspool beats the automaton when there's only one pattern
+        if let [pattern] = self.patterns {
+            return vec![pattern
+                .find_iter(haystack)
+                .take_while(|&start| start < limit)
+                .collect()];
+        }
+        let mut matches = vec![Vec::new(); self.patterns.len()];
+        for candidate in self.automaton.find_overlapping_iter(haystack) {
+            let index = candidate.pattern().as_usize();
+            let start = match candidate.start().checked_sub(self.anchors[index]) {
+                Some(start) if start < limit => start,
+                _ => continue,
+            };
+            if self.patterns[index].matches_at(haystack, start) {
+                matches[index].push(start);
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> Pattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn formats_parse_alike() {
+        let spaced = pattern("48 8B 05 ? ?? 90");
+        assert_eq!(spaced.to_string(), "48 8B 05 ? ? 90");
+        assert_eq!(pattern("488B05????90"), spaced);
+        assert_eq!(pattern(r#""\x48\x8B\x05\x00\x00\x90" "xxx??x""#), spaced);
+        assert_eq!(pattern(r"\x48\x8B\x05\x00\x00\x90, xxx??x"), spaced);
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        let error = |s: &str| s.parse::<Pattern>().unwrap_err();
+        assert_eq!(error(""), PatternError::Empty);
+        assert_eq!(error("? ??"), PatternError::NoFixedBytes);
+        assert_eq!(
+            error("48 8G"),
+            PatternError::InvalidToken {
+                token: "8G".to_string(),
+                position: 1
+            }
+        );
+        assert!(matches!(error("488B0"), PatternError::InvalidToken { .. }));
+        assert_eq!(error(r"\x48\x8B"), PatternError::MissingMask);
+        assert_eq!(
+            error(r"\x48\x8B x"),
+            PatternError::MaskLength { bytes: 2, mask: 1 }
+        );
+        assert_eq!(
+            error(r"\x48\x8B x-"),
+            PatternError::InvalidMask {
+                character: '-',
+                position: 1
+            }
+        );
+    }
+
+    #[test]
+    fn finds_overlapping_matches() {
+        let haystack = [0xAA, 0xAA, 0xBB, 0xAA, 0xAA, 0xAA, 0xAA];
+        let found: Vec<_> = pattern("AA ? AA").find_iter(&haystack).collect();
+        assert_eq!(found, [1, 3, 4]);
+        let found: Vec<_> = pattern("AA AA AA").find_iter(&haystack).collect();
+        assert_eq!(found, [3, 4]);
+        assert_eq!(pattern("AA BB CC").find_iter(&haystack).count(), 0);
+        assert_eq!(pattern("AA").find_iter(&[]).count(), 0);
+    }
+
+    #[test]
+    fn finds_wildcards_at_the_start() {
+        let haystack = [0x00, 0x48, 0x8B, 0x05, 0x11, 0x48, 0x8B, 0x05];
+        let found: Vec<_> = pattern("? 48 8B 05").find_iter(&haystack).collect();
+        assert_eq!(found, [0, 4]);
+    }
+
+    /// A haystack of three chunks with `bytes` at each of `at`.
+    fn planted(at: &[usize], bytes: &[u8]) -> Vec<u8> {
+        let mut haystack = vec![0; 3 * CHUNK_SIZE];
+        for &at in at {
+            haystack[at..at + bytes.len()].copy_from_slice(bytes);
+        }
+        haystack
+    }
+
+    #[test]
+    fn scans_across_chunk_boundaries() {
+        let bytes = [0x48, 0x8B, 0x05, 0x11, 0x22];
+        let at = [
+            0,
+            CHUNK_SIZE - 2,
+            CHUNK_SIZE + 3,
+            2 * CHUNK_SIZE - 5,
+            3 * CHUNK_SIZE - 5,
+        ];
+        let haystack = planted(&at, &bytes);
+        let single = [pattern("48 8B 05 ? 22")];
+        assert_eq!(Scanner::new(&single).scan(&haystack), [at.to_vec()]);
+
+        let several = [pattern("48 8B 05 ? 22"), pattern("05 11"), pattern("99")];
+        let found = Scanner::new(&several).scan(&haystack);
+        assert_eq!(found[0], at);
+        assert_eq!(found[1], at.iter().map(|at| at + 2).collect::<Vec<_>>());
+        assert!(found[2].is_empty());
+    }
+
+    #[test]
+    fn scans_nothing() {
+        let patterns = [pattern("48 8B"), pattern("05")];
+        let found = Scanner::new(&patterns).scan(&[]);
+        assert!(found.len() == 2 && found.iter().all(Vec::is_empty));
+    }
+}
@@ -0,0 +1,82 @@
+//! Directories only the user running us can get at, for what's written
+//! somewhere shared like `/tmp` and read back or loaded later. Anyone can
+//! create a directory there first under a name that's known in advance, so
+//! what we create is made to fail if it exists and checked before use.
+
+use std::ffi::{CString, OsString};
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Creates the directory `path`, which mustn't exist yet, for us alone.
+pub fn create(path: &Path) -> Result<(), String> {
+    DirBuilder::new()
+        .mode(0o700)
+        .create(path)
+        .map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+    check(path)
+}
+
+/// Makes sure `path` is a directory, not a link to one, that's ours and
+/// nobody else can read or write.
+pub fn check(path: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(path)
+        .map_err(|e| format!("failed to look at {}: {}", path.display(), e))?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.file_type().is_dir() {
+        Err(format!("{} isn't a directory", path.display()))
+    } else if metadata.uid() != uid {
+        Err(format!(
+            "{} belongs to uid {}, not us ({})",
+            path.display(),
+            metadata.uid(),
+            uid
+        ))
+    } else if metadata.permissions().mode() & 0o077 != 0 {
+        Err(format!(
+            "{} is open to others (mode {:o})",
+            path.display(),
+            metadata.permissions().mode() & 0o777
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// A directory with a name nobody could guess, created by `mkdtemp` in the
+/// temporary directory and removed with everything in it on drop.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Result<Self, String> {
+        let template = std::env::temp_dir().join(format!("{}-XXXXXX", prefix));
+        let error = |e: io::Error| format!("failed to create {}: {}", template.display(), e);
+        let template = CString::new(template.as_os_str().as_bytes())
+            .map_err(|e| error(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let raw = template.into_raw();
+        let created = unsafe { libc::mkdtemp(raw) };
+        // Back into a CString either way, so it's freed
+        let template = unsafe { CString::from_raw(raw) };
+        if created.is_null() {
+            return Err(error(io::Error::last_os_error()));
+        }
+        let path = PathBuf::from(OsString::from_vec(template.into_bytes()));
+        check(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
@@ -0,0 +1,436 @@
+//! Uploading dumps to where whoever uses them fetches them from, with curl.
+//!
+//! Targets are given like URLs:
+//!
+//! - `https://host/path/`, `put:https://...` PUTs every file there, under
+//!   its name if the URL ends in `/`, and `post:https://...` POSTs them,
+//!   e.g. to a webhook.
+//! - `s3://bucket/prefix` PUTs them into an S3 or S3-compatible bucket,
+//!   with the credentials in `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`,
+//!   the region in `AWS_REGION` and, for other services than Amazon's, the
+//!   endpoint in `AWS_ENDPOINT_URL`.
+//! - `gist:` creates a secret gist of them, `gist:<id>` updates one.
+//! - `github-release:<owner>/<repo>@<tag>` attaches them to a release,
+//!   replacing assets of the same name.
+//!
+//! GitHub wants a token in `GITHUB_TOKEN`. Secrets are handed to curl on
+//! its standard input rather than its command line, where anyone could
+//! read them.
+
+use crate::compress::Compression;
+use crate::private::TempDir;
+use serde_json::{json, Value};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+const GITHUB_API: &str = "https://api.github.com";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Put,
+    Post,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Http { method: Method, url: String },
+    S3 { bucket: String, prefix: String },
+    Gist { id: Option<String> },
+    Release { repo: String, tag: String },
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |expected: &str| format!("invalid target {:?}, expected {}", s, expected);
+        let http = |method, url: &str| {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                Ok(Target::Http {
+                    method,
+                    url: url.to_string(),
+                })
+            } else {
+                Err(error("an http:// or https:// URL"))
+            }
+        };
+        if let Some(url) = s.strip_prefix("put:") {
+            http(Method::Put, url)
+        } else if let Some(url) = s.strip_prefix("post:") {
+            http(Method::Post, url)
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            http(Method::Put, s)
+        } else if let Some(path) = s.strip_prefix("s3://") {
+            let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+            if bucket.is_empty() {
+                return Err(error("s3://<bucket>/<prefix>"));
+            }
+            Ok(Target::S3 {
+                bucket: bucket.to_string(),
+                prefix: prefix.trim_matches('/').to_string(),
+            })
+        } else if let Some(id) = s.strip_prefix("gist:") {
+            Ok(Target::Gist {
+                id: Some(id.to_string()).filter(|id| !id.is_empty()),
+            })
+        } else if let Some(release) = s.strip_prefix("github-release:") {
+            match release.split_once('@') {
+                Some((repo, tag)) if repo.contains('/') && !tag.is_empty() => Ok(Target::Release {
+                    repo: repo.to_string(),
+                    tag: tag.to_string(),
+                }),
+                _ => Err(error("github-release:<owner>/<repo>@<tag>")),
+            }
+        } else {
+            Err(error("a URL, s3://, gist: or github-release:"))
+        }
+    }
+}
+
+impl Target {
+    /// Uploads `files`, returning where they ended up.
+    pub fn publish(&self, files: &[PathBuf]) -> Result<Vec<String>, String> {
+        match self {
+            Target::Http { method, url } => files
+                .iter()
+                .map(|file| {
+                    let url = file_url(url, file);
+                    let method = match method {
+                        Method::Put => "PUT",
+                        Method::Post => "POST",
+                    };
+                    let content_type = content_type(file);
+                    let args = [
+                        "--request",
+                        method,
+                        "--header",
+                        &content_type,
+                        "--data-binary",
+                    ];
+                    curl(&args, Some(file), &url, "")?;
+                    Ok(url)
+                })
+                .collect(),
+            Target::S3 { bucket, prefix } => {
+                let credentials = |name: &str| {
+                    env::var(name).map_err(|_| format!("{} isn't set, S3 needs it", name))
+                };
+                let user = format!(
+                    "{}:{}",
+                    credentials("AWS_ACCESS_KEY_ID")?,
+                    credentials("AWS_SECRET_ACCESS_KEY")?
+                );
+                let region = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+                let endpoint = env::var("AWS_ENDPOINT_URL")
+                    .unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region));
+                let sigv4 = format!("aws:amz:{}:s3", region);
+                files
+                    .iter()
+                    .map(|file| {
+                        let mut url = format!("{}/{}/", endpoint.trim_end_matches('/'), bucket);
+                        if !prefix.is_empty() {
+                            url += &format!("{}/", prefix);
+                        }
+                        let url = file_url(&url, file);
+                        let config = format!("user = {}\n", quote(&user));
+                        curl(
+                            &["--aws-sigv4", &sigv4, "--upload-file"],
+                            Some(file),
+                            &url,
+                            &config,
+                        )?;
+                        Ok(url)
+                    })
+                    .collect()
+            }
+            Target::Gist { id } => {
+                // Gists only hold text, every file is checked before any is
+                // uploaded
+                let mut contents = serde_json::Map::new();
+                for file in files {
+                    if Compression::of(file).is_some() {
+                        return Err(format!(
+                            "{} is compressed, gists only hold text",
+                            file.display()
+                        ));
+                    }
+                    let bytes = fs::read(file)
+                        .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+                    let text = String::from_utf8(bytes).map_err(|_| {
+                        format!("{} isn't text, gists only hold text", file.display())
+                    })?;
+                    contents.insert(file_name(file), json!({ "content": text }));
+                }
+                let body = json!({
+                    "description": "netvars-rs dump",
+                    "public": false,
+                    "files": contents,
+                });
+                let (method, url) = match id {
+                    Some(id) => ("PATCH", format!("{}/gists/{}", github_api(), id)),
+                    None => ("POST", format!("{}/gists", github_api())),
+                };
+                let response = github(method, &url, Some(Body::Json(&body)))?;
+                Ok(vec![response["html_url"]
+                    .as_str()
+                    .unwrap_or(&url)
+                    .to_string()])
+            }
+            Target::Release { repo, tag } => {
+                let url = format!("{}/repos/{}/releases/tags/{}", github_api(), repo, tag);
+                let release = github("GET", &url, None)?;
+                let upload = release["upload_url"]
+                    .as_str()
+                    .ok_or_else(|| format!("no release {} in {}", tag, repo))?;
+                // A template like .../assets{?name,label}
+                let upload = upload.split('{').next().unwrap_or(upload);
+                let assets = release["assets"].as_array().cloned().unwrap_or_default();
+                files
+                    .iter()
+                    .map(|file| {
+                        let name = file_name(file);
+                        let existing = assets.iter().find(|asset| asset["name"] == name.as_str());
+                        if let Some(asset) = existing.and_then(|asset| asset["url"].as_str()) {
+                            github("DELETE", asset, None)?;
+                        }
+                        let url = format!("{}?name={}", upload, encode(&name));
+                        let response = github("POST", &url, Some(Body::File(file)))?;
+                        Ok(response["browser_download_url"]
+                            .as_str()
+                            .unwrap_or(&url)
+                            .to_string())
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+enum Body<'a> {
+    Json(&'a Value),
+    File(&'a Path),
+}
+
+/// `GITHUB_API_URL`, which GitHub Actions sets for GitHub Enterprise, or
+/// github.com's.
+fn github_api() -> String {
+    env::var("GITHUB_API_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| GITHUB_API.to_string())
+}
+
+/// Calls GitHub's REST API, returning the JSON it answered with.
+fn github(method: &str, url: &str, body: Option<Body>) -> Result<Value, String> {
+    let token = env::var("GITHUB_TOKEN").map_err(|_| "GITHUB_TOKEN isn't set".to_string())?;
+    let config = format!(
+        "header = {}\n",
+        quote(&format!("Authorization: Bearer {}", token))
+    );
+    let mut args = vec![
+        "--request",
+        method,
+        "--header",
+        "Accept: application/vnd.github+json",
+    ];
+    // curl reads JSON bodies from a file like any other, in a directory of
+    // our own so nobody can have put a link where it's written
+    let mut written = None;
+    let content_type;
+    let file = match body {
+        Some(Body::Json(value)) => {
+            let dir = written.insert(TempDir::new("netvars-rs")?);
+            let path = dir.path().join("body.json");
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(value.to_string().as_bytes()))
+                .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+            Some(path)
+        }
+        Some(Body::File(file)) => Some(file.to_path_buf()),
+        None => None,
+    };
+    if let Some(file) = &file {
+        content_type = self::content_type(file);
+        args.extend(["--header", &content_type, "--data-binary"]);
+    }
+    let response = curl(&args, file.as_deref(), url, &config)?;
+    drop(written);
+    if response.is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_slice(&response).map_err(|e| format!("unexpected answer from {}: {}", url, e))
+}
+
+/// Runs curl with `args`, followed by `@file` if there is one, and `url`.
+/// `config` goes in through its standard input.
+fn curl(args: &[&str], file: Option<&Path>, url: &str, config: &str) -> Result<Vec<u8>, String> {
+    let mut command = Command::new("curl");
+    command
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail-with-body",
+            "--config",
+            "-",
+        ])
+        .args(args);
+    if let Some(file) = file {
+        // --upload-file takes the path as it is, the others want an @
+        let path = file.display().to_string();
+        if args.last() == Some(&"--upload-file") {
+            command.arg(path);
+        } else {
+            command.arg(format!("@{}", path));
+        }
+    }
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run curl: {}", e))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(config.as_bytes())
+        .map_err(|e| format!("failed to configure curl: {}", e))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run curl: {}", e))?;
+    if !output.status.success() {
+        let mut message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let body = String::from_utf8_lossy(&output.stdout);
+        if !body.trim().is_empty() {
+            message = format!("{}: {}", message, body.trim());
+        }
+        return Err(format!("uploading to {} failed: {}", url, message));
+    }
+    Ok(output.stdout)
+}
+
+/// `url` with the file's name appended if it ends in `/`.
+fn file_url(url: &str, file: &Path) -> String {
+    if url.ends_with('/') {
+        format!("{}{}", url, encode(&file_name(file)))
+    } else {
+        url.to_string()
+    }
+}
+
+/// A `Content-Type` header for the file at `path`.
+fn content_type(path: &Path) -> String {
+    let kind = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => "application/json",
+        Some("txt" | "log") => "text/plain",
+        Some("gz") => "application/gzip",
+        Some("zst") => "application/zstd",
+        _ => "application/octet-stream",
+    };
+    format!("Content-Type: {}", kind)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Percent-encodes everything but what's unreserved in URLs.
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// A string as curl's config files want it.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_targets() {
+        let http = |method, url: &str| Target::Http {
+            method,
+            url: url.to_string(),
+        };
+        let parse = |s: &str| s.parse::<Target>();
+        assert_eq!(
+            parse("https://example.com/dumps/"),
+            Ok(http(Method::Put, "https://example.com/dumps/"))
+        );
+        assert_eq!(
+            parse("post:http://example.com/upload"),
+            Ok(http(Method::Post, "http://example.com/upload"))
+        );
+        assert!(parse("put:ftp://example.com/").is_err());
+        assert_eq!(
+            parse("s3://bucket/csgo/dumps/"),
+            Ok(Target::S3 {
+                bucket: "bucket".to_string(),
+                prefix: "csgo/dumps".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("s3://bucket"),
+            Ok(Target::S3 {
+                bucket: "bucket".to_string(),
+                prefix: String::new(),
+            })
+        );
+        assert!(parse("s3:///dumps").is_err());
+        assert_eq!(parse("gist:"), Ok(Target::Gist { id: None }));
+        assert_eq!(
+            parse("gist:abc123"),
+            Ok(Target::Gist {
+                id: Some("abc123".to_string())
+            })
+        );
+        assert_eq!(
+            parse("github-release:owner/repo@v1"),
+            Ok(Target::Release {
+                repo: "owner/repo".to_string(),
+                tag: "v1".to_string(),
+            })
+        );
+        assert!(parse("github-release:repo@v1").is_err());
+        assert!(parse("github-release:owner/repo@").is_err());
+        assert!(parse("dumps/").is_err());
+    }
+
+    #[test]
+    fn file_urls() {
+        let file = Path::new("/tmp/netvars dump.json");
+        assert_eq!(
+            file_url("https://example.com/", file),
+            "https://example.com/netvars%20dump.json"
+        );
+        assert_eq!(
+            file_url("https://example.com/latest.json", file),
+            "https://example.com/latest.json"
+        );
+    }
+
+    #[test]
+    fn encodes() {
+        assert_eq!(encode("netvars-1.0_final~.json"), "netvars-1.0_final~.json");
+        assert_eq!(encode("a b/c?d&é"), "a%20b%2Fc%3Fd%26%C3%A9");
+        assert_eq!(quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+    }
+}
@@ -0,0 +1,267 @@
+//! Finds a signature again after an update broke it, using the build it
+//! last matched in.
+//!
+//! The code around the old match is cut into short runs of instructions,
+//! with the bytes that change between builds wildcarded as `make-sig` does.
+//! Every run that still matches exactly once in the new build votes for
+//! where the old match moved to. An update rarely touches more than a few
+//! instructions, so most of the neighbourhood agrees, and a fresh pattern
+//! is made at the spot with the most votes.
+
+use crate::elf::ElfFile;
+use crate::makesig::{self, Code, Instruction};
+use crate::pattern::Pattern;
+use crate::resolve::Rel32;
+use std::collections::HashMap;
+
+/// How far around the old match to look for runs, in bytes.
+const CONTEXT: usize = 256;
+/// Runs are whole instructions adding up to at least this many bytes.
+const RUN_LEN: usize = 16;
+/// Runs with fewer fixed bytes than this match by accident too easily.
+const MIN_FIXED: usize = 8;
+/// Runs that have to agree before a spot is trusted.
+const MIN_VOTES: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct Repair {
+    /// Where the old match is now, relative to the module's base.
+    pub start: usize,
+    /// Matches only at `start` in the new build.
+    pub pattern: Pattern,
+    /// The signature's `rel32`, adjusted to the new instructions.
+    pub resolve: Option<Rel32>,
+    /// Runs that agreed on `start`, out of how many were tried.
+    pub votes: (usize, usize),
+}
+
+/// Finds where `pattern` matched in `previous` and where that code is in
+/// `current`, and makes a new pattern of at most `max_len` bytes there.
+pub fn repair(
+    previous: &ElfFile,
+    current: &ElfFile,
+    pattern: &Pattern,
+    resolve: Option<Rel32>,
+    max_len: usize,
+) -> Result<Repair, String> {
+    let old = Code::new(previous);
+    let new = Code::new(current);
+    repair_code(&old, &new, pattern, resolve, max_len)
+}
+
+/// [`repair`] for the code of both builds.
+fn repair_code(
+    old: &Code,
+    new: &Code,
+    pattern: &Pattern,
+    resolve: Option<Rel32>,
+    max_len: usize,
+) -> Result<Repair, String> {
+    let old_match = match old.find(pattern, 2)[..] {
+        [old_match] => old_match,
+        [] => return Err("doesn't match the previous build either".to_string()),
+        _ => return Err("matches more than once in the previous build".to_string()),
+    };
+
+    let instructions = context(old, old_match);
+    let runs: Vec<_> = (0..instructions.len())
+        .filter_map(|i| run(&instructions[i..]).map(|p| (instructions[i].start, p)))
+        .collect();
+    let patterns: Vec<_> = runs.iter().map(|(_, p)| p.clone()).collect();
+    let mut votes: HashMap<usize, usize> = HashMap::new();
+    for ((start, _), found) in runs.iter().zip(new.scan(&patterns)) {
+        if let [at] = found[..] {
+            // Where the old match would be if this run moved with it
+            if let Some(moved) = (at + old_match).checked_sub(*start) {
+                *votes.entry(moved).or_default() += 1;
+            }
+        }
+    }
+
+    let mut ranked: Vec<_> = votes.into_iter().collect();
+    ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let (start, count) = match ranked[..] {
+        [] => {
+            return Err(format!(
+                "none of the {} runs around it are left",
+                runs.len()
+            ))
+        }
+        [(_, best), (_, second), ..] if best == second => {
+            return Err(format!(
+                "the code around it moved to more than one place, {} runs each",
+                best
+            ))
+        }
+        [(start, count), ..] => (start, count),
+    };
+    if count < MIN_VOTES {
+        return Err(format!(
+            "only {} of the {} runs around it are left",
+            count,
+            runs.len()
+        ));
+    }
+
+    let resolve = match resolve {
+        Some(rel32) => Some(adjust(&instructions, old_match, new, start, rel32)?),
+        None => None,
+    };
+    Ok(Repair {
+        start,
+        pattern: makesig::grow(new, start, max_len)?,
+        resolve,
+        votes: (count, runs.len()),
+    })
+}
+
+/// The instructions within [`CONTEXT`] bytes of `at` on either side.
+///
+/// Decoding backwards isn't possible, so decoding starts as far back as it
+/// can while still landing on `at`.
+fn context(code: &Code, at: usize) -> Vec<Instruction> {
+    let end = at + CONTEXT;
+    (1..=CONTEXT.min(at))
+        .rev()
+        .map(|back| at - back)
+        .filter(|&start| code.contains(start))
+        .map(|start| {
+            code.instructions(start)
+                .take_while(|i| i.start < end)
+                .collect::<Vec<_>>()
+        })
+        .find(|instructions| instructions.iter().any(|i| i.start == at))
+        .unwrap_or_else(|| {
+            code.instructions(at)
+                .take_while(|i| i.start < end)
+                .collect()
+        })
+}
+
+/// A pattern for the first instructions of `instructions`, if there's
+/// enough to them.
+fn run(instructions: &[Instruction]) -> Option<Pattern> {
+    let mut bytes = Vec::new();
+    for instruction in instructions {
+        bytes.extend(instruction.bytes.iter().copied());
+        if bytes.len() >= RUN_LEN {
+            break;
+        }
+    }
+    if bytes.len() < RUN_LEN || bytes.iter().flatten().count() < MIN_FIXED {
+        return None;
+    }
+    let end = bytes.iter().rposition(Option::is_some)? + 1;
+    bytes.truncate(end);
+    Pattern::new(bytes).ok()
+}
+
+/// Moves `rel32` to the same instruction in the new build, counting
+/// instructions from the match since their lengths may have changed.
+fn adjust(
+    instructions: &[Instruction],
+    old_match: usize,
+    new: &Code,
+    new_match: usize,
+    rel32: Rel32,
+) -> Result<Rel32, String> {
+    let displacement = old_match + rel32.disp_offset;
+    let (index, _) = instructions
+        .iter()
+        .skip_while(|i| i.start < old_match)
+        .enumerate()
+        .find(|(_, i)| match i.rip_relative {
+            Some((offset, _)) => i.start + offset == displacement,
+            None => false,
+        })
+        .ok_or_else(|| {
+            format!(
+                "{} doesn't point at a RIP-relative operand in the previous build",
+                rel32
+            )
+        })?;
+
+    let instruction = new
+        .instructions(new_match)
+        .nth(index)
+        .ok_or("the code at the new match doesn't decode")?;
+    let (offset, _) = instruction
+        .rip_relative
+        .ok_or("the instruction rel32 pointed at isn't RIP-relative anymore")?;
+    let start = instruction.start - new_match;
+    Ok(Rel32 {
+        disp_offset: start + offset,
+        instr_len: start + instruction.bytes.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `mov eax, imm32` with a different immediate each.
+    fn movs(range: std::ops::Range<u32>) -> Vec<u8> {
+        range
+            .flat_map(|i| std::iter::once(0xB8).chain((0x1111_0000 + i).to_le_bytes()))
+            .collect()
+    }
+
+    /// Thirty `mov`s, the signature's `mov eax, 0x22220000; lea rax,
+    /// [rip + 0x1000]` and ten more.
+    fn old_build() -> Vec<u8> {
+        let mut code = movs(0..30);
+        code.extend([0xB8, 0x00, 0x00, 0x22, 0x22]);
+        code.extend([0x48, 0x8D, 0x05, 0x00, 0x10, 0x00, 0x00]);
+        code.extend(movs(30..40));
+        code
+    }
+
+    fn signature() -> Pattern {
+        let bytes = [0xB8, 0x00, 0x00, 0x22, 0x22, 0x48, 0x8D, 0x05];
+        Pattern::new(bytes.iter().copied().map(Some).collect()).unwrap()
+    }
+
+    #[test]
+    fn repairs_moved_code() {
+        let old = old_build();
+        // Moved by some code added in front, and the first instruction of
+        // the signature made longer
+        let mut new = vec![0x90; 7];
+        new.extend(movs(0..30));
+        new.extend([0x48, 0xC7, 0xC0, 0x00, 0x00, 0x22, 0x22]);
+        new.extend([0x48, 0x8D, 0x05, 0x00, 0x20, 0x00, 0x00]);
+        new.extend(movs(30..40));
+
+        let old = Code::from_segments(vec![(0x1000, &old[..])], Vec::new());
+        let new = Code::from_segments(vec![(0x1000, &new[..])], Vec::new());
+        let rel32 = Rel32 {
+            disp_offset: 8,
+            instr_len: 12,
+        };
+        let repair = repair_code(&old, &new, &signature(), Some(rel32), 64).unwrap();
+        assert_eq!(repair.start, 0x1000 + 7 + 150);
+        assert_eq!(repair.pattern.to_string(), "48 C7 C0 00 00 22 22");
+        assert_eq!(
+            repair.resolve,
+            Some(Rel32 {
+                disp_offset: 10,
+                instr_len: 14,
+            })
+        );
+        assert!(repair.votes.0 >= MIN_VOTES);
+        assert!(repair.votes.0 < repair.votes.1);
+    }
+
+    #[test]
+    fn needs_the_previous_match() {
+        let old = old_build();
+        let old = Code::from_segments(vec![(0x1000, &old[..])], Vec::new());
+        let unknown = Pattern::new(vec![Some(0xCC), Some(0xCC)]).unwrap();
+        assert!(repair_code(&old, &old, &unknown, None, 64).is_err());
+
+        // Nothing around it is left
+        let new = [0x90; 64];
+        let new = Code::from_segments(vec![(0x1000, &new[..])], Vec::new());
+        assert!(repair_code(&old, &new, &signature(), None, 64).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Problems found while dumping, and the exit codes they map to.
+//!
+//! Outside of `--strict` problems are warnings and the dump is printed anyway.
+//! With it, the first kind of problem decides the exit code and only a JSON
+//! error report is written, so a pipeline never publishes a dump we had
+//! doubts about.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// `verify-signature` found a file its signature doesn't match, like
+/// `openssl` itself.
+pub const EXIT_BAD_SIGNATURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+/// The worker reported an error, e.g. the signature didn't match.
+pub const EXIT_FAILED: i32 = 3;
+/// The worker crashed or exited on its own.
+pub const EXIT_CRASHED: i32 = 4;
+pub const EXIT_TIMED_OUT: i32 = 5;
+/// `self-test` dumped the fixture, but not what's in it.
+pub const EXIT_SELF_TEST_FAILED: i32 = 6;
+/// `doctor` found a reason the client won't load.
+pub const EXIT_DOCTOR_FOUND_PROBLEMS: i32 = 7;
+/// `batch` dumped every game, but not every dump succeeded.
+pub const EXIT_BATCH_FAILED: i32 = 8;
+/// `fetch-and-dump` couldn't download the build.
+pub const EXIT_FETCH_FAILED: i32 = 9;
+pub const EXIT_UNREADABLE_MEMORY: i32 = 10;
+pub const EXIT_INVALID_PROP_TYPE: i32 = 11;
+pub const EXIT_BROKEN_STRUCTURE: i32 = 12;
+pub const EXIT_AMBIGUOUS_SIGNATURE: i32 = 13;
+pub const EXIT_DUPLICATE_PROP: i32 = 14;
+pub const EXIT_OVERLAPPING_PROPS: i32 = 15;
+pub const EXIT_IMPLAUSIBLE_VALUE: i32 = 16;
+pub const EXIT_SIGNATURE_NOT_FOUND: i32 = 17;
+pub const EXIT_SIGNATURE_REPAIRED: i32 = 18;
+pub const EXIT_UNRESOLVED_SYMBOLS: i32 = 19;
+pub const EXIT_NETVAR_NOT_FOUND: i32 = 20;
+pub const EXIT_ANCHOR_FAILED: i32 = 21;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    UnreadableMemory,
+    InvalidPropType,
+    /// Loops in the class list, tables containing themselves.
+    BrokenStructure,
+    /// A signature matched more than once, so we may have picked the wrong
+    /// spot.
+    AmbiguousSignature,
+    /// A name shows up at different offsets within one flattened class.
+    DuplicateProp,
+    OverlappingProps,
+    /// A field that read fine but can't be right, like a table with a
+    /// million props.
+    ImplausibleValue,
+    /// One of the user's signatures didn't match at all.
+    SignatureNotFound,
+    /// A signature didn't match, but was found again by comparing with the
+    /// previous build. The match is likely right, the signature needs
+    /// updating.
+    SignatureRepaired,
+    /// A library loaded lazily, but needs symbols nothing provides. Code
+    /// calling them crashes, so the build is broken rather than the
+    /// signatures.
+    UnresolvedSymbols,
+    /// A netvar from the config isn't in the dump.
+    NetvarNotFound,
+    /// A table or prop every build has is missing or somewhere it can't be,
+    /// so the whole dump is likely read with a wrong layout.
+    AnchorFailed,
+}
+
+impl ProblemKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProblemKind::UnreadableMemory => "unreadable_memory",
+            ProblemKind::InvalidPropType => "invalid_prop_type",
+            ProblemKind::BrokenStructure => "broken_structure",
+            ProblemKind::AmbiguousSignature => "ambiguous_signature",
+            ProblemKind::DuplicateProp => "duplicate_prop",
+            ProblemKind::OverlappingProps => "overlapping_props",
+            ProblemKind::ImplausibleValue => "implausible_value",
+            ProblemKind::SignatureNotFound => "signature_not_found",
+            ProblemKind::SignatureRepaired => "signature_repaired",
+            ProblemKind::UnresolvedSymbols => "unresolved_symbols",
+            ProblemKind::NetvarNotFound => "netvar_not_found",
+            ProblemKind::AnchorFailed => "anchor_failed",
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ProblemKind::UnreadableMemory => EXIT_UNREADABLE_MEMORY,
+            ProblemKind::InvalidPropType => EXIT_INVALID_PROP_TYPE,
+            ProblemKind::BrokenStructure => EXIT_BROKEN_STRUCTURE,
+            ProblemKind::AmbiguousSignature => EXIT_AMBIGUOUS_SIGNATURE,
+            ProblemKind::DuplicateProp => EXIT_DUPLICATE_PROP,
+            ProblemKind::OverlappingProps => EXIT_OVERLAPPING_PROPS,
+            ProblemKind::ImplausibleValue => EXIT_IMPLAUSIBLE_VALUE,
+            ProblemKind::SignatureNotFound => EXIT_SIGNATURE_NOT_FOUND,
+            ProblemKind::SignatureRepaired => EXIT_SIGNATURE_REPAIRED,
+            ProblemKind::UnresolvedSymbols => EXIT_UNRESOLVED_SYMBOLS,
+            ProblemKind::NetvarNotFound => EXIT_NETVAR_NOT_FOUND,
+            ProblemKind::AnchorFailed => EXIT_ANCHOR_FAILED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Path to whatever we were reading, e.g. `CCSPlayer/DT_CSPlayer/m_iHealth`.
+    pub location: String,
+    pub reason: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.reason)
+    }
+}
+
+/// What `--strict` prints instead of a dump.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport<'a> {
+    /// Machine-readable kind of the failure, e.g. `timed_out`.
+    pub error: &'a str,
+    pub message: String,
+    pub exit_code: i32,
+    pub problems: &'a [Problem],
+}
@@ -0,0 +1,211 @@
+//! Turning signature matches into the addresses they refer to.
+
+use crate::memory::{Memory, ReadError};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// The target of a RIP-relative instruction (`lea`, `mov`, `call`, ...)
+/// found at `match_addr`.
+///
+/// `disp_offset` is where the signed 32 bit displacement starts and
+/// `instr_len` where the instruction ends, both counted from `match_addr`,
+/// since the displacement is relative to the next instruction. For
+/// `91 48 8B 05 ? ? ? ?` that's 4 and 8: the match starts one byte before
+/// the `mov`.
+pub fn resolve_rel32(
+    memory: &Memory,
+    match_addr: usize,
+    disp_offset: usize,
+    instr_len: usize,
+) -> Result<usize, ReadError> {
+    let displacement = unsafe { memory.read::<i32>(match_addr + disp_offset) }?;
+    Ok((match_addr + instr_len).wrapping_add(displacement as isize as usize))
+}
+
+/// [`resolve_rel32`]'s parameters, as given after a signature's pattern:
+/// `rel32(4, 8)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rel32 {
+    pub disp_offset: usize,
+    pub instr_len: usize,
+}
+
+impl Rel32 {
+    pub fn resolve(self, memory: &Memory, match_addr: usize) -> Result<usize, ReadError> {
+        resolve_rel32(memory, match_addr, self.disp_offset, self.instr_len)
+    }
+}
+
+impl FromStr for Rel32 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "expected rel32(<disp offset>, <instruction length>), got {:?}",
+                s
+            )
+        };
+        let arguments = s
+            .trim()
+            .strip_prefix("rel32(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+        let (disp_offset, instr_len) = arguments.split_once(',').ok_or_else(invalid)?;
+        let number = |value: &str| {
+            let value = value.trim();
+            match value.strip_prefix("0x") {
+                Some(hex) => usize::from_str_radix(hex, 16).ok(),
+                None => value.parse().ok(),
+            }
+        };
+        let rel32 = Rel32 {
+            disp_offset: number(disp_offset).ok_or_else(invalid)?,
+            instr_len: number(instr_len).ok_or_else(invalid)?,
+        };
+        // Both are whatever a signature file says, so mind the overflow
+        if rel32
+            .disp_offset
+            .checked_add(4)
+            .is_none_or(|end| end > rel32.instr_len)
+        {
+            return Err(format!(
+                "{}: the displacement doesn't fit before the end of the instruction",
+                s.trim()
+            ));
+        }
+        Ok(rel32)
+    }
+}
+
+impl Display for Rel32 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "rel32({}, {})", self.disp_offset, self.instr_len)
+    }
+}
+
+/// What to do next on the way from a signature's match to the address or
+/// value it's after, given after its pattern like `rel32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Step {
+    /// `add(N)`, moves by `N` bytes, which may be negative.
+    Add(i64),
+    /// `rel32(DISP, LEN)`, to where a RIP-relative instruction here points.
+    Rel32(Rel32),
+    /// `read`, to where the pointer stored here points.
+    Read,
+    /// `read32`, to the unsigned 32 bit number stored here, like the
+    /// displacement of `mov eax, [rdi + disp32]`.
+    Read32,
+    /// `value`, only allowed last: what we have is a number, like a struct
+    /// offset, rather than an address in the module.
+    Value,
+}
+
+/// Takes `steps` from `address`.
+pub fn follow(memory: &Memory, address: usize, steps: &[Step]) -> Result<usize, ReadError> {
+    steps.iter().try_fold(address, |address, step| match *step {
+        Step::Add(n) => Ok(address.wrapping_add(n as isize as usize)),
+        Step::Rel32(rel32) => rel32.resolve(memory, address),
+        Step::Read => unsafe { memory.read::<usize>(address) },
+        Step::Read32 => unsafe { memory.read::<u32>(address) }.map(|n| n as usize),
+        Step::Value => Ok(address),
+    })
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s {
+            "read" => return Ok(Step::Read),
+            "read32" => return Ok(Step::Read32),
+            "value" => return Ok(Step::Value),
+            _ => {}
+        }
+        if s.starts_with("rel32(") {
+            return s.parse().map(Step::Rel32);
+        }
+        let n = s
+            .strip_prefix("add(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .map(str::trim)
+            .ok_or_else(|| {
+                format!(
+                    "expected add(<bytes>), rel32(<disp offset>, <instruction length>), read, \
+                     read32 or value, got {:?}",
+                    s
+                )
+            })?;
+        let (negative, digits) = match n.strip_prefix('-') {
+            Some(digits) => (true, digits.trim()),
+            None => (false, n),
+        };
+        let magnitude = match digits.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => digits.parse(),
+        }
+        .map_err(|_| format!("invalid number of bytes in {:?}", s))?;
+        Ok(Step::Add(if negative { -magnitude } else { magnitude }))
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Step::Add(n) if *n < 0 => write!(f, "add(-{:#X})", n.unsigned_abs()),
+            Step::Add(n) => write!(f, "add({:#X})", n),
+            Step::Rel32(rel32) => write!(f, "{}", rel32),
+            Step::Read => write!(f, "read"),
+            Step::Read32 => write!(f, "read32"),
+            Step::Value => write!(f, "value"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_steps() {
+        let step = |s: &str| s.parse::<Step>().unwrap();
+        assert_eq!(step("read"), Step::Read);
+        assert_eq!(step(" read32 "), Step::Read32);
+        assert_eq!(step("value"), Step::Value);
+        assert_eq!(step("add(8)"), Step::Add(8));
+        assert_eq!(step("add( -0x10 )"), Step::Add(-0x10));
+        assert_eq!(
+            step("rel32(3, 0x7)"),
+            Step::Rel32(Rel32 {
+                disp_offset: 3,
+                instr_len: 7
+            })
+        );
+        for s in [
+            "add(-0x10)",
+            "add(0x8)",
+            "rel32(3, 7)",
+            "read",
+            "read32",
+            "value",
+        ] {
+            assert_eq!(step(s).to_string().parse::<Step>(), Ok(step(s)));
+        }
+    }
+
+    #[test]
+    fn invalid_steps() {
+        let error = |s: &str| s.parse::<Step>().unwrap_err();
+        assert!(error("jump").starts_with("expected add(<bytes>)"));
+        assert_eq!(error("add(x)"), "invalid number of bytes in \"add(x)\"");
+        assert!(error("rel32(3)").starts_with("expected rel32("));
+        assert_eq!(
+            error("rel32(4, 7)"),
+            "rel32(4, 7): the displacement doesn't fit before the end of the instruction"
+        );
+        assert!(error("rel32(18446744073709551615, 0)").ends_with("end of the instruction"));
+    }
+}
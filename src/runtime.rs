@@ -0,0 +1,196 @@
+//! The Steam Linux Runtime, which the game's libraries were built against
+//! and which a stock distribution may not match.
+//!
+//! The original runtime, scout, is a directory of libraries meant to go on
+//! `LD_LIBRARY_PATH`, so its libraries can simply be preferred over the
+//! system's. Its successors, soldier and sniper, are containers with a
+//! glibc of their own that can't be mixed with the host's, so the only way
+//! to use them is to run the whole dump inside through their entry point.
+
+use crate::steam;
+use std::env;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Set inside the container so the process there doesn't try to enter it
+/// again.
+const INSIDE: &str = "NETVARS_RS_IN_STEAM_RUNTIME";
+/// Newest first, that's what current builds expect.
+const CONTAINERS: &[(&str, Kind)] = &[
+    ("SteamLinuxRuntime_sniper", Kind::Sniper),
+    ("SteamLinuxRuntime_soldier", Kind::Soldier),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Scout,
+    Soldier,
+    Sniper,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Scout => "scout",
+            Kind::Soldier => "soldier",
+            Kind::Sniper => "sniper",
+        }
+    }
+}
+
+/// How to use the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Prefer scout's libraries over the system's.
+    Libraries,
+    /// Run everything inside the runtime.
+    Container,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "libraries" => Ok(Mode::Libraries),
+            "container" => Ok(Mode::Container),
+            _ => Err(format!(
+                "unknown Steam Runtime mode {:?}, expected libraries or container",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Runtime {
+    pub kind: Kind,
+    pub root: PathBuf,
+}
+
+impl Runtime {
+    /// Recognizes the runtime at `root`, be it scout's `steam-runtime` or a
+    /// `SteamLinuxRuntime_*` directory.
+    pub fn at(root: &Path) -> Option<Self> {
+        let kind = if root.join("run.sh").is_file() && root.join("amd64").is_dir() {
+            Kind::Scout
+        } else {
+            let name = root.file_name()?.to_string_lossy();
+            CONTAINERS
+                .iter()
+                .find(|(container, _)| name == *container)
+                .map(|&(_, kind)| kind)
+                .filter(|_| root.join("run").is_file())?
+        };
+        Some(Runtime {
+            kind,
+            root: root.to_path_buf(),
+        })
+    }
+
+    /// The runtimes installed next to `gamedir` or wherever Steam is,
+    /// containers first.
+    pub fn detect(gamedir: &Path) -> Vec<Self> {
+        let mut roots = Vec::new();
+        // Containers are installed like games, into the same library
+        let common = gamedir.canonicalize().ok().and_then(|dir| {
+            dir.ancestors()
+                .find(|d| d.ends_with("steamapps/common"))
+                .map(Path::to_path_buf)
+        });
+        let steam_roots = steam::roots();
+        for common in common
+            .into_iter()
+            .chain(steam_roots.iter().map(|root| root.join("steamapps/common")))
+        {
+            roots.extend(CONTAINERS.iter().map(|(name, _)| common.join(name)));
+        }
+        roots.extend(env::var_os("STEAM_RUNTIME").map(PathBuf::from));
+        roots.extend(
+            steam_roots
+                .iter()
+                .map(|root| root.join("ubuntu12_32/steam-runtime")),
+        );
+
+        let mut found: Vec<Runtime> = Vec::new();
+        for root in roots {
+            let root = root.canonicalize().unwrap_or(root);
+            if found.iter().any(|r| r.root == root) {
+                continue;
+            }
+            found.extend(Runtime::at(&root));
+        }
+        found.sort_by_key(|r| r.kind == Kind::Scout);
+        found
+    }
+
+    /// The runtime at `dir`, or the best one installed for `mode`.
+    pub fn find(mode: Mode, dir: Option<&Path>, gamedir: &Path) -> Result<Self, String> {
+        let found = match dir {
+            Some(dir) => {
+                let runtime = Runtime::at(dir)
+                    .ok_or_else(|| format!("no Steam Runtime at {}", dir.display()))?;
+                vec![runtime]
+            }
+            None => Runtime::detect(gamedir),
+        };
+        let mut usable = found
+            .into_iter()
+            .filter(|r| mode == Mode::Container || r.kind == Kind::Scout);
+        usable.next().ok_or_else(|| match mode {
+            Mode::Libraries => "no scout Steam Runtime found, soldier and sniper only work \
+                                as a container"
+                .to_string(),
+            Mode::Container => "no Steam Runtime found, pass --steam-runtime-dir".to_string(),
+        })
+    }
+
+    /// Scout's library directories, pinned libraries first like its own
+    /// `run.sh` does. Empty for the containers.
+    pub fn library_dirs(&self) -> Vec<PathBuf> {
+        if self.kind != Kind::Scout {
+            return Vec::new();
+        }
+        [
+            "pinned_libs_64",
+            "amd64/lib/x86_64-linux-gnu",
+            "amd64/lib",
+            "amd64/usr/lib/x86_64-linux-gnu",
+            "amd64/usr/lib",
+        ]
+        .iter()
+        .map(|dir| self.root.join(dir))
+        .filter(|dir| dir.is_dir())
+        .collect()
+    }
+
+    /// Re-executes the current process inside the runtime, unless it's
+    /// running there already. Only returns on failure, or if there was
+    /// nothing to do.
+    pub fn enter(&self) -> Result<(), String> {
+        if env::var_os(INSIDE).is_some() {
+            return Ok(());
+        }
+        let exe = env::current_exe().map_err(|e| format!("can't tell where we are: {}", e))?;
+        let mut command = match self.kind {
+            Kind::Scout => Command::new(self.root.join("run.sh")),
+            Kind::Soldier | Kind::Sniper => {
+                let mut command = Command::new(self.root.join("run"));
+                command.arg("--");
+                command
+            }
+        };
+        let error = command
+            .arg(exe)
+            .args(env::args_os().skip(1))
+            .env(INSIDE, "1")
+            .exec();
+        Err(format!(
+            "failed to enter the Steam Runtime at {}: {}",
+            self.root.display(),
+            error
+        ))
+    }
+}
@@ -0,0 +1,529 @@
+//! Confines the worker before the game library gets to run any code.
+//!
+//! With namespaces, the worker moves into new user, mount and network
+//! namespaces. The network namespace has nothing but a loopback device
+//! that's down, and the directories that matter, the game's and the user's
+//! home, are mounted read-only. Whatever the library's constructors try,
+//! they can neither phone home nor change anything we'd care about.
+//!
+//! Confinement goes further, and works where namespaces are disabled:
+//! Landlock rules out writing anywhere but a few directories, and a seccomp
+//! filter refuses to execute programs or open sockets other than Unix
+//! ones.
+
+use std::ffi::{CString, OsString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    pub namespaces: bool,
+    /// Made read-only in the new mount namespace, along with whatever is
+    /// mounted below them at the time.
+    pub read_only: Vec<PathBuf>,
+    /// Apply Landlock and seccomp.
+    pub confine: bool,
+    /// The only places writes are allowed with `confine`, besides
+    /// `/dev/null`.
+    pub writable: Vec<PathBuf>,
+}
+
+const AUDIT_ARCH_X86_64: u32 = 0xC000_003E;
+/// Set on syscall numbers of the x32 ABI, which would dodge the filter.
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+/// Offsets into `struct seccomp_data`.
+const SECCOMP_NR: u32 = 0;
+const SECCOMP_ARCH: u32 = 4;
+const SECCOMP_ARG0: u32 = 16;
+
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+const LANDLOCK_ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+/// `REMOVE_DIR` through `MAKE_SYM`, everything that changes a directory.
+const LANDLOCK_ACCESS_FS_CHANGE_DIR: u64 = 0x1FF0;
+const LANDLOCK_ACCESS_FS_REFER: u64 = 1 << 13;
+const LANDLOCK_ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+#[repr(C)]
+struct LandlockRulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct LandlockPathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+impl Sandbox {
+    /// Moves the calling process into the sandbox. Must be called while
+    /// it's single threaded, e.g. right after forking.
+    pub fn enter(&self) -> Result<(), String> {
+        if self.namespaces {
+            self.unshare()?;
+        }
+        if self.confine {
+            landlock(&self.writable)?;
+            seccomp()?;
+        }
+        Ok(())
+    }
+
+    fn unshare(&self) -> Result<(), String> {
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        let flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET;
+        if unsafe { libc::unshare(flags) } != 0 {
+            return Err(format!(
+                "failed to create namespaces, are unprivileged user namespaces disabled? {}",
+                io::Error::last_os_error()
+            ));
+        }
+
+        // Stay who we were, otherwise files we own turn into nobody's
+        let map = |path: &str, contents: String| {
+            fs::write(path, contents).map_err(|e| format!("failed to write {}: {}", path, e))
+        };
+        map("/proc/self/setgroups", "deny".to_string())?;
+        map("/proc/self/uid_map", format!("{} {} 1", uid, uid))?;
+        map("/proc/self/gid_map", format!("{} {} 1", gid, gid))?;
+
+        // Or the read-only mounts would leak back out to the host
+        mount(None, Path::new("/"), libc::MS_REC | libc::MS_PRIVATE)?;
+        for path in &self.read_only {
+            mount(Some(path), path, libc::MS_BIND | libc::MS_REC)?;
+            read_only(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes the mount at `path` and every one below it read-only.
+///
+/// `mount_setattr` does it in one go and leaves the other flags alone.
+/// Before Linux 5.12 each mount has to be remounted on its own, and with
+/// the flags it already has: the ones it came into the namespace with are
+/// locked, and a remount that would clear them fails with `EPERM`.
+fn read_only(path: &Path) -> Result<(), String> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| format!("invalid path: {}", path.display()))?;
+    let attr = MountAttr {
+        attr_set: MOUNT_ATTR_RDONLY,
+        attr_clr: 0,
+        propagation: 0,
+        userns_fd: 0,
+    };
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            libc::AT_RECURSIVE,
+            &attr,
+            std::mem::size_of::<MountAttr>(),
+        )
+    };
+    if result == 0 {
+        return Ok(());
+    }
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() != Some(libc::ENOSYS) {
+        return Err(format!(
+            "failed to make {} read-only: {}",
+            path.display(),
+            error
+        ));
+    }
+
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|e| format!("failed to read /proc/self/mountinfo: {}", e))?;
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    for mount_point in mounts_beneath(&mountinfo, &path) {
+        let flags = locked_flags(statvfs(&mount_point)?);
+        mount(
+            None,
+            &mount_point,
+            libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+        )?;
+    }
+    Ok(())
+}
+
+/// The mount points in `mountinfo` that are `path` or below it.
+fn mounts_beneath(mountinfo: &str, path: &Path) -> Vec<PathBuf> {
+    mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|field| PathBuf::from(unescape(field)))
+        .filter(|mount_point| mount_point.starts_with(path))
+        .collect()
+}
+
+/// Undoes the octal escapes mountinfo uses for spaces, tabs, newlines and
+/// backslashes.
+fn unescape(field: &str) -> OsString {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match octal {
+            Some(byte) if bytes[i] == b'\\' => {
+                out.push(byte);
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    OsString::from_vec(out)
+}
+
+/// The mount flags to pass to keep what `f_flag` from `statvfs` says a
+/// mount has.
+fn locked_flags(f_flag: libc::c_ulong) -> libc::c_ulong {
+    let mut flags = 0;
+    for (st, ms) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if f_flag & st != 0 {
+            flags |= ms;
+        }
+    }
+    // Without either, a remount defaults to relatime rather than keeping
+    // strictatime
+    if f_flag & (libc::ST_NOATIME | libc::ST_RELATIME) == 0 {
+        flags |= libc::MS_STRICTATIME;
+    }
+    flags
+}
+
+fn statvfs(path: &Path) -> Result<libc::c_ulong, String> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| format!("invalid path: {}", path.display()))?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(format!(
+            "failed to look at {}: {}",
+            path.display(),
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(unsafe { stat.assume_init() }.f_flag)
+}
+
+/// Restricts writes to beneath `writable` and `/dev/null`.
+fn landlock(writable: &[PathBuf]) -> Result<(), String> {
+    let syscall_error = |what: &str| format!("{}: {}", what, io::Error::last_os_error());
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<LandlockRulesetAttr>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    if abi < 1 {
+        return Err(syscall_error(
+            "Landlock isn't available, it needs Linux 5.13",
+        ));
+    }
+    let mut file = LANDLOCK_ACCESS_FS_WRITE_FILE;
+    if abi >= 3 {
+        file |= LANDLOCK_ACCESS_FS_TRUNCATE;
+    }
+    let mut handled = file | LANDLOCK_ACCESS_FS_CHANGE_DIR;
+    if abi >= 2 {
+        handled |= LANDLOCK_ACCESS_FS_REFER;
+    }
+
+    let attr = LandlockRulesetAttr {
+        handled_access_fs: handled,
+    };
+    let ruleset = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr,
+            std::mem::size_of::<LandlockRulesetAttr>(),
+            0,
+        )
+    } as i32;
+    if ruleset < 0 {
+        return Err(syscall_error("failed to create a Landlock ruleset"));
+    }
+    let dev_null = PathBuf::from("/dev/null");
+    let rules = writable
+        .iter()
+        .map(|dir| (dir, handled))
+        .chain(std::iter::once((&dev_null, file)));
+    for (path, allowed_access) in rules {
+        let fd = open_path(path)?;
+        let rule = LandlockPathBeneathAttr {
+            allowed_access,
+            parent_fd: fd,
+        };
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset,
+                LANDLOCK_RULE_PATH_BENEATH,
+                &rule,
+                0,
+            )
+        };
+        unsafe { libc::close(fd) };
+        if result != 0 {
+            return Err(syscall_error(&format!(
+                "failed to allow writes to {}",
+                path.display()
+            )));
+        }
+    }
+
+    let result = unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            -1
+        } else {
+            libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0)
+        }
+    };
+    unsafe { libc::close(ruleset) };
+    if result != 0 {
+        return Err(syscall_error("failed to apply the Landlock ruleset"));
+    }
+    Ok(())
+}
+
+fn open_path(path: &Path) -> Result<i32, String> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| format!("invalid path: {}", path.display()))?;
+    let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(format!(
+            "failed to open {}: {}",
+            path.display(),
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(fd)
+}
+
+/// Refuses executing programs and creating sockets other than Unix ones,
+/// with `EPERM` rather than a kill so the library gets a chance to cope.
+fn seccomp() -> Result<(), String> {
+    let mut program = filter();
+    let filter = libc::sock_fprog {
+        len: program.len() as u16,
+        filter: program.as_mut_ptr(),
+    };
+    let result = unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            -1
+        } else {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &filter as *const libc::sock_fprog,
+            )
+        }
+    };
+    if result != 0 {
+        return Err(format!(
+            "failed to apply the seccomp filter: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// The BPF program [`seccomp`] loads.
+fn filter() -> Vec<libc::sock_filter> {
+    let denied = [libc::SYS_execve, libc::SYS_execveat];
+    let load = |offset| bpf(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0, 0, offset);
+    let ret = |value| bpf(libc::BPF_RET | libc::BPF_K, 0, 0, value);
+    let jeq = |value, jt, jf| bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, jt, jf, value);
+    let jge = |value, jt, jf| bpf(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, jt, jf, value);
+
+    let mut program = vec![
+        load(SECCOMP_ARCH),
+        jeq(AUDIT_ARCH_X86_64, 1, 0),
+        ret(libc::SECCOMP_RET_KILL_PROCESS),
+        load(SECCOMP_NR),
+    ];
+    // A match jumps past the checks after it and the four instructions of
+    // the tail below that allow (everything else, then Unix sockets),
+    // landing on the deny at its end
+    let checks = denied.len() + 2;
+    let to_deny = |check: usize| (checks - 1 - check + 4) as u8;
+    program.push(jge(X32_SYSCALL_BIT, to_deny(0), 0));
+    for (i, &nr) in denied.iter().enumerate() {
+        program.push(jeq(nr as u32, to_deny(i + 1), 0));
+    }
+    program.push(jeq(libc::SYS_socket as u32, 1, 0));
+    program.extend_from_slice(&[
+        ret(libc::SECCOMP_RET_ALLOW),
+        load(SECCOMP_ARG0),
+        jeq(libc::AF_UNIX as u32, 0, 1),
+        ret(libc::SECCOMP_RET_ALLOW),
+        ret(libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+    ]);
+    program
+}
+
+fn bpf(code: u32, jt: u8, jf: u8, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+fn mount(source: Option<&Path>, target: &Path, flags: libc::c_ulong) -> Result<(), String> {
+    let c_path = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| format!("invalid path: {}", path.display()))
+    };
+    let target_c = c_path(target)?;
+    let source_c = source.map(c_path).transpose()?;
+    let result = unsafe {
+        libc::mount(
+            source_c.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+            target_c.as_ptr(),
+            std::ptr::null(),
+            flags,
+            std::ptr::null(),
+        )
+    };
+    if result != 0 {
+        return Err(format!(
+            "failed to mount {}: {}",
+            target.display(),
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `filter` returns for a syscall, run the way the kernel runs it
+    /// on `struct seccomp_data`.
+    fn run(filter: &[libc::sock_filter], arch: u32, nr: u32, arg0: u32) -> u32 {
+        let mut accumulator = 0;
+        let mut pc = 0;
+        loop {
+            let instruction = filter[pc];
+            let code = u32::from(instruction.code);
+            pc += 1;
+            if code == libc::BPF_LD | libc::BPF_W | libc::BPF_ABS {
+                accumulator = match instruction.k {
+                    SECCOMP_NR => nr,
+                    SECCOMP_ARCH => arch,
+                    SECCOMP_ARG0 => arg0,
+                    k => panic!("load from offset {}", k),
+                };
+            } else if code == libc::BPF_RET | libc::BPF_K {
+                return instruction.k;
+            } else if code & !0xF0 == libc::BPF_JMP | libc::BPF_K {
+                let taken = match code & 0xF0 {
+                    jump if jump == libc::BPF_JEQ => accumulator == instruction.k,
+                    jump if jump == libc::BPF_JGE => accumulator >= instruction.k,
+                    jump => panic!("unexpected jump {:#x}", jump),
+                };
+                pc += usize::from(if taken {
+                    instruction.jt
+                } else {
+                    instruction.jf
+                });
+            } else {
+                panic!("unexpected instruction {:#x}", code);
+            }
+        }
+    }
+
+    #[test]
+    fn filters_syscalls() {
+        let filter = filter();
+        let syscall = |nr: libc::c_long, arg0: libc::c_int| {
+            run(&filter, AUDIT_ARCH_X86_64, nr as u32, arg0 as u32)
+        };
+        let deny = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+        assert_eq!(syscall(libc::SYS_execve, 0), deny);
+        assert_eq!(syscall(libc::SYS_execveat, 0), deny);
+        assert_eq!(syscall(libc::SYS_socket, libc::AF_INET), deny);
+        assert_eq!(syscall(libc::SYS_socket, libc::AF_INET6), deny);
+        assert_eq!(
+            syscall(libc::SYS_socket, libc::AF_UNIX),
+            libc::SECCOMP_RET_ALLOW
+        );
+        assert_eq!(syscall(libc::SYS_openat, 0), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(
+            syscall(libc::SYS_mmap, libc::AF_INET),
+            libc::SECCOMP_RET_ALLOW
+        );
+        // Anything through the x32 ABI
+        assert_eq!(syscall(X32_SYSCALL_BIT as libc::c_long | 59, 0), deny);
+        // i386's execve is 11, and the wrong arch dies
+        assert_eq!(
+            run(&filter, 0x4000_0003, 11, 0),
+            libc::SECCOMP_RET_KILL_PROCESS
+        );
+    }
+
+    #[test]
+    fn finds_mounts_beneath() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 8:2 / /home rw,relatime shared:2 - ext4 /dev/sda2 rw
+24 23 8:3 / /home/a\\040b rw,nosuid shared:3 - ext4 /dev/sda3 rw
+25 23 0:4 / /home/a\\134c rw shared:4 - tmpfs tmpfs rw
+26 22 8:4 / /homer rw shared:5 - ext4 /dev/sda4 rw
+";
+        assert_eq!(
+            mounts_beneath(mountinfo, Path::new("/home")),
+            [
+                PathBuf::from("/home"),
+                PathBuf::from("/home/a b"),
+                PathBuf::from("/home/a\\c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_locked_flags() {
+        assert_eq!(
+            locked_flags(libc::ST_NOSUID | libc::ST_NODEV | libc::ST_RELATIME),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_RELATIME
+        );
+        assert_eq!(
+            locked_flags(libc::ST_NOEXEC | libc::ST_NOATIME),
+            libc::MS_NOEXEC | libc::MS_NOATIME
+        );
+        assert_eq!(locked_flags(0), libc::MS_STRICTATIME);
+    }
+}
@@ -0,0 +1,106 @@
+//! Mirrors of the Source engine structures we walk.
+//!
+//! Every one of these is read out of the game library through
+//! [`Memory`](crate::memory::Memory) rather than dereferenced, so all fields
+//! have to be valid for any bit pattern: function pointers are nullable and
+//! `bool`s are kept as plain bytes.
+
+use libc::c_void;
+use serde::{Deserialize, Serialize};
+use std::os::raw::c_char;
+
+pub type CreateClientClassFn = Option<unsafe extern "C" fn(i32, i32) -> *mut c_void>;
+pub type CreateEventFn = Option<unsafe extern "C" fn() -> *mut c_void>;
+
+#[allow(non_snake_case)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RecvTable {
+    pub m_pProps: *const RecvProp,
+    pub m_nProps: i32,
+    pub m_pDecoder: *const c_void,
+    pub m_pNetTableName: *const c_char,
+    pub m_bInitialized: u8,
+    pub m_bInMainList: u8,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ClientClass {
+    pub m_pCreateFn: CreateClientClassFn,
+    pub m_pCreateEventFn: CreateEventFn,
+    pub m_pNetworkName: *const c_char,
+    pub m_pRecvTable: *const RecvTable,
+    pub m_pNext: *const ClientClass,
+    pub m_ClassID: i32,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RecvProp {
+    pub m_pVarName: *const c_char,
+    pub m_RecvType: i32,
+    pub m_Flags: i32,
+    pub m_StringBufferSize: i32,
+    pub m_bInsideArray: u8,
+    pub m_pExtraData: *const c_void,
+    pub m_pArrayProp: *const RecvProp,
+    pub m_ArrayLengthProxy: *const c_void,
+    pub m_ProxyFn: *const c_void, /* RecvVarProxyFn */
+    pub m_DataTableProxyFn: *const c_void,
+    pub m_pDataTable: *const RecvTable,
+    pub m_Offset: i32,
+    pub m_ElementStride: i32,
+    pub m_nElements: i32,
+    pub m_pParentArrayPropName: *const c_char,
+}
+
+/// `SendPropType`, what `RecvProp::m_RecvType` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PropType {
+    Int,
+    Float,
+    Vector,
+    VectorXY,
+    String,
+    Array,
+    DataTable,
+    Int64,
+}
+
+impl PropType {
+    pub fn from_raw(raw: i32) -> Option<Self> {
+        Some(match raw {
+            0 => PropType::Int,
+            1 => PropType::Float,
+            2 => PropType::Vector,
+            3 => PropType::VectorXY,
+            4 => PropType::String,
+            5 => PropType::Array,
+            6 => PropType::DataTable,
+            7 => PropType::Int64,
+            _ => return None,
+        })
+    }
+
+    /// The smallest number of bytes a prop of this type occupies. Ints are
+    /// decoded into anything from a `bool` to an `int` depending on their
+    /// proxy, so they only count as a single byte.
+    pub fn min_size(self, prop: &RecvProp) -> u32 {
+        match self {
+            PropType::Int => 1,
+            PropType::Float => 4,
+            PropType::Vector => 12,
+            PropType::VectorXY => 8,
+            PropType::Int64 => 8,
+            PropType::String => prop.m_StringBufferSize.max(0) as u32,
+            PropType::Array => {
+                (prop.m_ElementStride.max(0) as u32).saturating_mul(prop.m_nElements.max(0) as u32)
+            }
+            // The table's own props describe what's in there
+            PropType::DataTable => 0,
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! `self-test`: dumps the bundled fixture library and compares the result to
+//! what we know is in there.
+//!
+//! The fixture (see `fixture/src/lib.rs`) goes through the same load, scan
+//! and walk path as a real client library, so a pass means this build and
+//! platform read the structures correctly.
+
+use crate::dumper::{self, Locators, Strategy, DEFAULT_STRATEGIES};
+use crate::flatten::flatten;
+use crate::report;
+use crate::sdk::PropType;
+use crate::validate;
+use crate::worker;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+struct Expected {
+    class: &'static str,
+    id: i32,
+    table: &'static str,
+    /// Flattened props in table order.
+    props: &'static [(&'static str, i64, PropType)],
+}
+
+const EXPECTED: &[Expected] = &[
+    Expected {
+        class: "CFixturePlayer",
+        id: 1,
+        table: "DT_FixturePlayer",
+        props: &[
+            ("m_iHealth", 0x100, PropType::Int),
+            ("m_vecOrigin", 0x138, PropType::Vector),
+            ("m_nTickBase", 0x304, PropType::Int),
+            ("m_flFallVelocity", 0x308, PropType::Float),
+            ("m_szLastPlaceName", 0x400, PropType::String),
+            ("m_iAmmo.000", 0x500, PropType::Int),
+            ("m_iAmmo.001", 0x504, PropType::Int),
+        ],
+    },
+    Expected {
+        class: "CFixtureWorld",
+        id: 0,
+        table: "DT_FixtureWorld",
+        props: &[
+            ("m_flWaveHeight", 0x10, PropType::Float),
+            ("m_bColdWorld", 0x14, PropType::Int),
+        ],
+    },
+];
+
+/// Class, flattened prop and the demangled name of its proxy.
+const EXPECTED_PROXIES: &[(&str, &str, &str)] = &[(
+    "CFixtureWorld",
+    "m_flWaveHeight",
+    "C_FixtureWorld::RecvProxy_WaveHeight(void const*, void*, void*)",
+)];
+
+pub const FIXTURE_NAME: &str = "libnetvars_fixture.so";
+
+/// Where the fixture ends up when built in the same workspace.
+fn default_fixture() -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.parent()?.join(FIXTURE_NAME))
+}
+
+/// Runs the self-test and returns the exit code.
+pub fn run(fixture: Option<&Path>, timeout: Option<Duration>) -> i32 {
+    let fixture = match fixture.map(Path::to_path_buf).or_else(default_fixture) {
+        Some(fixture) => fixture,
+        None => {
+            eprintln!("error: can't tell where the fixture is, pass --fixture");
+            return report::EXIT_USAGE;
+        }
+    };
+    let path = fixture.to_string_lossy().into_owned();
+
+    // Once through each way of finding the class list, so none of them can
+    // cover for the others
+    let mut exit_code = 0;
+    for &strategy in DEFAULT_STRATEGIES {
+        let result = check(&path, timeout, strategy);
+        if exit_code == 0 {
+            exit_code = result;
+        }
+    }
+    exit_code
+}
+
+/// Dumps the fixture and compares it to [`EXPECTED`], printing the result
+/// and returning the exit code.
+fn check(path: &str, timeout: Option<Duration>, strategy: Strategy) -> i32 {
+    let method = strategy.name();
+    let locators = Locators {
+        signatures: &[],
+        rules: None,
+        strategies: &[strategy],
+        previous: None,
+    };
+    let mut outcome = match worker::run(timeout, |sender| {
+        dumper::load(sender, path)?;
+        dumper::dump_module(sender, FIXTURE_NAME, &locators)
+    }) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("error: failed to start the worker process: {}", e);
+            return report::EXIT_FAILED;
+        }
+    };
+    if let Some(error) = outcome.error {
+        println!("FAIL: {}: {}", method, error);
+        return error.exit_code();
+    }
+    let conflicts = validate::conflicts(&outcome.dump);
+    outcome.dump.problems.extend(conflicts);
+
+    let mut failures = Vec::new();
+    for problem in &outcome.dump.problems {
+        failures.push(format!("unexpected problem: {}", problem));
+    }
+    let classes = &outcome.dump.classes;
+    if classes.len() != EXPECTED.len() {
+        failures.push(format!(
+            "expected {} classes, got {}",
+            EXPECTED.len(),
+            classes.len()
+        ));
+    }
+    for (expected, class) in EXPECTED.iter().zip(classes) {
+        if (class.name.as_str(), class.id) != (expected.class, expected.id) {
+            failures.push(format!(
+                "expected class {} ({}), got {} ({})",
+                expected.class, expected.id, class.name, class.id
+            ));
+            continue;
+        }
+        if class.confidence < 1.0 {
+            failures.push(format!(
+                "{} has confidence {:.2}",
+                class.name, class.confidence
+            ));
+        }
+        let table = match &class.table {
+            Some(table) if table.name == expected.table => table,
+            _ => {
+                failures.push(format!("{} is missing {}", class.name, expected.table));
+                continue;
+            }
+        };
+        let props: Vec<_> = flatten(table)
+            .iter()
+            .map(|p| (p.name.clone(), p.offset, p.prop.kind))
+            .collect();
+        let wanted: Vec<_> = expected
+            .props
+            .iter()
+            .map(|&(name, offset, kind)| (name.to_string(), offset, Some(kind)))
+            .collect();
+        if props != wanted {
+            failures.push(format!(
+                "{} props differ:\n  expected {:?}\n  got      {:?}",
+                class.name, wanted, props
+            ));
+        }
+        for &(_, prop, proxy) in EXPECTED_PROXIES.iter().filter(|p| p.0 == expected.class) {
+            let got = flatten(table)
+                .into_iter()
+                .find(|p| p.name == prop)
+                .and_then(|p| p.prop.proxy.clone());
+            if got.as_deref() != Some(proxy) {
+                failures.push(format!(
+                    "{}.{} should have proxy {}, got {:?}",
+                    class.name, prop, proxy, got
+                ));
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        println!(
+            "ok: {} classes dumped as expected from {} using {}",
+            classes.len(),
+            path,
+            method
+        );
+        0
+    } else {
+        for failure in &failures {
+            println!("FAIL: {}: {}", method, failure);
+        }
+        report::EXIT_SELF_TEST_FAILED
+    }
+}
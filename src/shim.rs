@@ -0,0 +1,150 @@
+//! Stand-ins for libraries an archived build needs but that are gone, so
+//! the client can still be loaded.
+//!
+//! Every missing library gets a stub with its name, compiled with the
+//! system's `cc`. The first one also defines every symbol nothing else
+//! does: functions that abort with the symbol's name when called, and
+//! zeroed data. Dumping never calls into them, but a constructor might,
+//! which the worker then reports as a crash.
+
+use crate::cache;
+use crate::doctor::{self, Analysis};
+use crate::elf::Symbol;
+use crate::gamedir::Game;
+use crate::private;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// For when no library is missing, only symbols.
+const SHIM_NAME: &str = "libnetvars_shim.so";
+/// Size of stubbed data. Undefined symbols don't say how big they are, and
+/// too much costs nothing.
+const DATA_SIZE: usize = 256;
+
+/// One stub library and the symbols it stands in for.
+#[derive(Debug, Clone)]
+pub struct Stub {
+    pub library: String,
+    pub symbols: Vec<String>,
+}
+
+/// Where stubs are built, the same every time so it can go on the library
+/// path before they exist: `$XDG_RUNTIME_DIR/netvars-rs-shims`, or
+/// `shims` in the cache directory. Never somewhere shared like `/tmp`,
+/// where someone else could put libraries of theirs for us to load.
+pub fn dir() -> Result<PathBuf, String> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join("netvars-rs-shims"))
+        .or_else(|| cache::default_dir().map(|dir| dir.join("shims")))
+        .ok_or_else(|| {
+            "neither XDG_RUNTIME_DIR nor HOME is set, nowhere to build stubs".to_string()
+        })
+}
+
+/// Builds stubs in `dir` for whatever `game` is missing and has `game`
+/// load them. `dir` mustn't count as part of the game while looking, or
+/// the stubs of an earlier run would hide what's missing.
+pub fn generate(game: &mut Game, dir: &Path) -> Result<Vec<Stub>, String> {
+    let mut real = game.clone();
+    real.library_dirs.retain(|d| d != dir);
+    let Analysis {
+        missing,
+        unresolved,
+        ..
+    } = doctor::analyze(&real);
+
+    // Created anew, so nothing but what's built here is in it
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(format!(
+                "failed to remove the stubs of an earlier run in {}: {}",
+                dir.display(),
+                e
+            ))
+        }
+        _ => {}
+    }
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+    }
+    private::create(dir)?;
+
+    let mut symbols: Vec<Symbol> = unresolved.into_iter().flat_map(|(_, s)| s).collect();
+    symbols.sort_by(|a, b| a.name.cmp(&b.name));
+    symbols.dedup_by(|a, b| a.name == b.name);
+
+    let mut libraries: Vec<String> = missing.into_iter().map(|(name, _)| name).collect();
+    if libraries.is_empty() && !symbols.is_empty() {
+        libraries.push(SHIM_NAME.to_string());
+        game.preload.push(dir.join(SHIM_NAME));
+    }
+
+    let mut stubs = Vec::new();
+    for (index, library) in libraries.into_iter().enumerate() {
+        let defined = if index == 0 { &symbols[..] } else { &[] };
+        build(dir, &library, defined)?;
+        stubs.push(Stub {
+            library,
+            symbols: defined.iter().map(|s| s.name.clone()).collect(),
+        });
+    }
+    Ok(stubs)
+}
+
+/// Compiles a library called `name` defining `symbols`.
+fn build(dir: &Path, name: &str, symbols: &[Symbol]) -> Result<(), String> {
+    let source = dir.join(format!("{}.c", name));
+    fs::write(&source, stub_source(symbols))
+        .map_err(|e| format!("failed to write {}: {}", source.display(), e))?;
+    let output = Command::new("cc")
+        .args(["-shared", "-fPIC", "-w", "-o"])
+        .arg(dir.join(name))
+        .arg(format!("-Wl,-soname,{}", name))
+        .arg(&source)
+        .output()
+        .map_err(|e| format!("failed to run cc, is a C compiler installed? {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "failed to build a stub for {}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// C defining each of `symbols` under its exact name, mangled or not.
+fn stub_source(symbols: &[Symbol]) -> String {
+    let mut source = String::from(
+        "#include <stdio.h>\n\
+         #include <stdlib.h>\n\n\
+         static void netvars_stub_called(const char *name) {\n\
+         \x20   fprintf(stderr, \"netvars-rs: stubbed %s was called\\n\", name);\n\
+         \x20   abort();\n\
+         }\n\n",
+    );
+    for (index, symbol) in symbols.iter().enumerate() {
+        let name = symbol.name.replace('\\', "\\\\").replace('"', "\\\"");
+        if symbol.function {
+            let _ = writeln!(
+                source,
+                "void stub_{0}(void) __asm__(\"{1}\");\n\
+                 void stub_{0}(void) {{ netvars_stub_called(\"{1}\"); }}",
+                index, name
+            );
+        } else {
+            let _ = writeln!(
+                source,
+                "char stub_{}[{}] __asm__(\"{}\") = {{ 0 }};",
+                index, DATA_SIZE, name
+            );
+        }
+    }
+    source
+}
@@ -0,0 +1,196 @@
+//! User-supplied signatures, found alongside the class list in the same scan.
+
+use crate::pattern::Pattern;
+use crate::resolve::{Rel32, Step};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub name: String,
+    /// File name of the library to search, `None` for the client.
+    pub module: Option<String>,
+    pub pattern: Pattern,
+    /// Where to look, `None` for all of the module's code.
+    pub region: Option<Region>,
+    /// How to get from a match to the address it refers to, if the match
+    /// itself isn't what's wanted.
+    pub resolve: Option<Rel32>,
+    /// Taken after `resolve`, or from the match if there's none.
+    pub steps: Vec<Step>,
+}
+
+/// Part of a module a signature is confined to, either to make the scan
+/// cheaper or to rule out matches elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// A section by name, e.g. `.text`.
+    Section(String),
+    /// Offsets from the module's base.
+    Range(Range<usize>),
+}
+
+/// `NAME[@REGION]=PATTERN[ -> STEP]...`, as given to `--signature`.
+impl FromStr for Signature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, pattern) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=PATTERN, got {:?}", s))?;
+        let mut parts = pattern.split("->");
+        let pattern = parts.next().unwrap_or_default();
+        let steps = parts.map(str::parse).collect::<Result<Vec<Step>, _>>()?;
+        let (name, region) = match name.split_once('@') {
+            Some((name, region)) => (name.trim(), Some(region.trim().parse()?)),
+            None => (name.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(format!("signature {:?} has no name", s));
+        }
+        Signature::new(name, pattern, region, steps)
+    }
+}
+
+impl Signature {
+    /// A signature for the client, a leading `rel32` step becoming
+    /// `resolve`.
+    pub fn new(
+        name: &str,
+        pattern: &str,
+        region: Option<Region>,
+        mut steps: Vec<Step>,
+    ) -> Result<Self, String> {
+        let resolve = match steps.first() {
+            Some(&Step::Rel32(rel32)) => {
+                steps.remove(0);
+                Some(rel32)
+            }
+            _ => None,
+        };
+        if steps.iter().rev().skip(1).any(|&step| step == Step::Value) {
+            return Err(format!("{}: value has to be the last step", name));
+        }
+        Ok(Signature {
+            name: name.to_string(),
+            module: None,
+            pattern: pattern
+                .parse()
+                .map_err(|e| format!("invalid signature {}: {}", name, e))?,
+            region,
+            resolve,
+            steps,
+        })
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(region) = &self.region {
+            write!(f, "@{}", region)?;
+        }
+        write!(f, "={}", self.pattern)?;
+        if let Some(resolve) = &self.resolve {
+            write!(f, " -> {}", resolve)?;
+        }
+        for step in &self.steps {
+            write!(f, " -> {}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// `.section` or `START-END` in hex, e.g. `0x1000-0x2000`.
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('.') {
+            return Ok(Region::Section(s.to_string()));
+        }
+        let hex = |value: &str| {
+            let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+            usize::from_str_radix(digits, 16).ok()
+        };
+        let range = s
+            .split_once('-')
+            .and_then(|(start, end)| Some(hex(start)?..hex(end)?))
+            .filter(|range| range.start < range.end)
+            .ok_or_else(|| format!("expected .section or START-END, got {:?}", s))?;
+        Ok(Region::Range(range))
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Region::Section(name) => write!(f, "{}", name),
+            Region::Range(range) => write!(f, "{:#X}-{:#X}", range.start, range.end),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignatureMatch {
+    pub name: String,
+    pub module: String,
+    /// Offsets of every match from the module's base, ascending.
+    pub matches: Vec<usize>,
+    /// What each match resolved to, as offsets from the module's base or,
+    /// if the last step is `value`, as is, if the signature asked for that.
+    /// Matches that couldn't be resolved are left out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<usize>,
+    /// A signature that matches this build, if the given one didn't and had
+    /// to be repaired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repaired: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_regions() {
+        assert_eq!(".text".parse(), Ok(Region::Section(".text".to_string())));
+        assert_eq!("0x1000-0X2000".parse(), Ok(Region::Range(0x1000..0x2000)));
+        assert_eq!("1000-2000".parse(), Ok(Region::Range(0x1000..0x2000)));
+        for invalid in ["text", "0x2000-0x1000", "0x1000", "0x1000-zz"] {
+            assert!(invalid.parse::<Region>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let signature: Signature = "dwLocalPlayer@.text = 48 8B 05 ? ? ? ? -> rel32(3, 7) -> read"
+            .parse()
+            .unwrap();
+        assert_eq!(signature.name, "dwLocalPlayer");
+        assert_eq!(signature.region, Some(Region::Section(".text".to_string())));
+        assert_eq!(
+            signature.resolve,
+            Some(Rel32 {
+                disp_offset: 3,
+                instr_len: 7
+            })
+        );
+        assert_eq!(signature.steps, [Step::Read]);
+        assert_eq!(
+            signature.to_string(),
+            "dwLocalPlayer@.text=48 8B 05 ? ? ? ? -> rel32(3, 7) -> read"
+        );
+
+        let error = |s: &str| s.parse::<Signature>().unwrap_err();
+        assert!(error("48 8B 05").starts_with("expected NAME=PATTERN"));
+        assert!(error("=48 8B 05").ends_with("has no name"));
+        assert_eq!(
+            error("a=48 -> value -> read"),
+            "a: value has to be the last step"
+        );
+        assert!(error("a=48 8G").starts_with("invalid signature a: "));
+    }
+}
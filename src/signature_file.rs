@@ -0,0 +1,313 @@
+//! `signatures.toml`, named addresses to look for without touching the
+//! code:
+//!
+//! ```toml
+//! [[signature]]
+//! name = "dwEntityList"
+//! module = "client_client.so"     # optional, the client by default
+//! pattern = "48 8D 05 ? ? ? ? 48 89 C7"
+//! steps = ["rel32(3, 7)", "read"] # optional
+//! region = ".text"                # optional
+//! ```
+//!
+//! `patterns = [...]` instead of `pattern` gives alternatives, tried in
+//! order until one matches. Only as much of TOML is understood as this
+//! needs: `[[signature]]` tables of strings and arrays of strings.
+
+use crate::resolve::Step;
+use crate::signature::Signature;
+use std::path::Path;
+
+const KEYS: &[&str] = &["name", "module", "pattern", "patterns", "region", "steps"];
+
+#[derive(Debug, Clone)]
+enum Value {
+    String(String),
+    Array(Vec<String>),
+}
+
+/// The signatures in the file at `path`, alternatives as several
+/// signatures of the same name.
+pub fn load(path: &Path) -> Result<Vec<Signature>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(text: &str) -> Result<Vec<Signature>, String> {
+    let mut signatures = Vec::new();
+    for (line, table) in tables(text)? {
+        let at = |e: String| format!("signature on line {}: {}", line, e);
+        let string = |key: &str| match table.iter().find(|(k, _)| k == key) {
+            None => Ok(None),
+            Some((_, Value::String(s))) => Ok(Some(s.clone())),
+            Some(_) => Err(at(format!("{} has to be a string", key))),
+        };
+        let array = |key: &str| match table.iter().find(|(k, _)| k == key) {
+            None => Ok(Vec::new()),
+            Some((_, Value::Array(a))) => Ok(a.clone()),
+            Some(_) => Err(at(format!("{} has to be an array of strings", key))),
+        };
+
+        let name = string("name")?.ok_or_else(|| at("missing name".to_string()))?;
+        let mut patterns = array("patterns")?;
+        patterns.splice(0..0, string("pattern")?);
+        if patterns.is_empty() {
+            return Err(at(format!("{} has no pattern", name)));
+        }
+        let steps = array("steps")?
+            .iter()
+            .map(|step| step.parse())
+            .collect::<Result<Vec<Step>, _>>()
+            .map_err(at)?;
+        let region = string("region")?
+            .map(|region| region.parse())
+            .transpose()
+            .map_err(at)?;
+        let module = string("module")?;
+        for pattern in patterns {
+            let mut signature =
+                Signature::new(&name, &pattern, region.clone(), steps.clone()).map_err(at)?;
+            signature.module = module.clone();
+            signatures.push(signature);
+        }
+    }
+    Ok(signatures)
+}
+
+/// The `[[signature]]` tables in `text` with the lines they start on.
+#[allow(clippy::type_complexity)]
+fn tables(text: &str) -> Result<Vec<(usize, Vec<(String, Value)>)>, String> {
+    let mut tables: Vec<(usize, Vec<(String, Value)>)> = Vec::new();
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+    while let Some((number, line)) = lines.next() {
+        let error = |e: &str| format!("line {}: {}", number, e);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            if line != "[[signature]]" {
+                return Err(error(&format!("unknown table {}", line)));
+            }
+            tables.push((number, Vec::new()));
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected key = value"))?;
+        let key = key.trim();
+        if !KEYS.contains(&key) {
+            return Err(error(&format!("unknown key {}", key)));
+        }
+        let (_, table) = tables
+            .last_mut()
+            .ok_or_else(|| error("keys go in a [[signature]] table"))?;
+        if table.iter().any(|(k, _)| k == key) {
+            return Err(error(&format!("{} given twice", key)));
+        }
+
+        // Arrays may go on for several lines
+        let mut value = value.trim().to_string();
+        if value.starts_with('[') {
+            while !closed(&value) {
+                let (_, next) = lines.next().ok_or_else(|| error("unterminated array"))?;
+                value.push(' ');
+                value.push_str(strip_comment(next).trim());
+            }
+        }
+        table.push((key.to_string(), parse_value(&value).map_err(|e| error(&e))?));
+    }
+    Ok(tables)
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    let mut rest = value;
+    if let Some(inner) = value.strip_prefix('[') {
+        let mut items = Vec::new();
+        rest = inner.trim_start();
+        loop {
+            if let Some(after) = rest.strip_prefix(']') {
+                rest = after;
+                break;
+            }
+            let (item, after) = parse_string(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            } else if !rest.starts_with(']') {
+                return Err(format!("expected , or ] in {}", value));
+            }
+        }
+        if !rest.trim().is_empty() {
+            return Err(format!("unexpected {} after the array", rest.trim()));
+        }
+        return Ok(Value::Array(items));
+    }
+    let (string, after) = parse_string(rest)?;
+    if !after.trim().is_empty() {
+        return Err(format!("unexpected {} after the string", after.trim()));
+    }
+    Ok(Value::String(string))
+}
+
+/// A basic ("...") or literal ('...') string at the start of `s`, and what
+/// follows it.
+fn parse_string(s: &str) -> Result<(String, &str), String> {
+    let mut chars = s.char_indices();
+    let quote = match chars.next() {
+        Some((_, quote @ ('"' | '\''))) => quote,
+        _ => return Err(format!("expected a string, got {}", s)),
+    };
+    let mut string = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            _ if c == quote => return Ok((string, &s[i + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some(other) => return Err(format!("unknown escape \\{}", other)),
+                None => break,
+            },
+            _ => string.push(c),
+        }
+    }
+    Err(format!("unterminated string {}", s))
+}
+
+/// Whether the brackets in `value` outside of strings balance.
+fn closed(value: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in value.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
+            },
+        }
+    }
+    depth <= 0
+}
+
+/// `line` up to a `#` that isn't inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '#' => return &line[..i],
+                _ => {}
+            },
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::Region;
+
+    #[test]
+    fn parses_signatures() {
+        let signatures = parse(
+            r#"
+            # Comments, also after values
+            [[signature]]
+            name = "dwEntityList"
+            module = "client_client.so"
+            pattern = "48 8D 05 ? ? ? ? 48 89 C7" # the lea
+            steps = ["rel32(3, 7)", "read"]
+            region = ".text"
+
+            [[signature]]
+            name = 'dwGlowObjectManager'
+            patterns = [
+                "48 8B 05 ? ? ? ?",  # newer builds
+                "48 8D 3D ? ? ? ?",
+            ]
+            steps = ["rel32(3, 7)", "add(0x10)", "value"]
+            region = "0x1000-0x2000"
+            "#,
+        )
+        .unwrap();
+        let shown: Vec<String> = signatures.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            shown,
+            [
+                "dwEntityList@.text=48 8D 05 ? ? ? ? 48 89 C7 -> rel32(3, 7) -> read",
+                "dwGlowObjectManager@0x1000-0x2000=48 8B 05 ? ? ? ? -> rel32(3, 7) -> add(0x10) -> value",
+                "dwGlowObjectManager@0x1000-0x2000=48 8D 3D ? ? ? ? -> rel32(3, 7) -> add(0x10) -> value",
+            ]
+        );
+        assert_eq!(signatures[0].module.as_deref(), Some("client_client.so"));
+        assert_eq!(signatures[0].region, Some(Region::Section(".text".into())));
+        assert_eq!(signatures[1].module, None);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let error = |text: &str| parse(text).unwrap_err();
+        assert_eq!(
+            error("name = \"a\""),
+            "line 1: keys go in a [[signature]] table"
+        );
+        assert_eq!(error("[signature]"), "line 1: unknown table [signature]");
+        assert_eq!(
+            error("[[signature]]\nnames = \"a\""),
+            "line 2: unknown key names"
+        );
+        assert_eq!(
+            error("[[signature]]\nname = \"a\"\nname = \"b\""),
+            "line 3: name given twice"
+        );
+        assert_eq!(
+            error("[[signature]]\nname = \"a\"\npatterns = [\"48\""),
+            "line 3: unterminated array"
+        );
+        assert_eq!(
+            error("[[signature]]\npattern = \"48\""),
+            "signature on line 1: missing name"
+        );
+        assert_eq!(
+            error("[[signature]]\nname = \"a\""),
+            "signature on line 1: a has no pattern"
+        );
+        assert_eq!(
+            error("[[signature]]\nname = [\"a\"]\npattern = \"48\""),
+            "signature on line 1: name has to be a string"
+        );
+        assert!(
+            error("[[signature]]\nname = \"a\"\npattern = \"48\"\nsteps = [\"jump\"]")
+                .starts_with("signature on line 1: expected add(<bytes>)")
+        );
+    }
+}
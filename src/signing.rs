@@ -0,0 +1,104 @@
+//! Ed25519 signatures over dump files, so whoever fetches published offsets
+//! can tell they came from the pipeline that holds the key.
+//!
+//! The signing itself is left to openssl, keys being the PEM files it
+//! makes. A file's signature is the 64 raw bytes in `<file>.sig` next to it.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Where the signature of `file` goes.
+pub fn signature_path(file: &Path) -> PathBuf {
+    let mut path = OsString::from(file.as_os_str());
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Signs `file` with the private key at `key`, returning where the
+/// signature went.
+pub fn sign(key: &Path, file: &Path) -> Result<PathBuf, String> {
+    check_key(key)?;
+    let signature = signature_path(file);
+    let output = run(openssl(&["pkeyutl", "-sign", "-rawin", "-inkey"])
+        .arg(key)
+        .arg("-in")
+        .arg(file)
+        .arg("-out")
+        .arg(&signature))?;
+    if !output.status.success() {
+        return Err(format!(
+            "failed to sign {}: {}",
+            file.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(signature)
+}
+
+/// Whether `<file>.sig` is the signature of `file` by the key at `key`,
+/// which may be the public key or the private one.
+pub fn verify(key: &Path, file: &Path) -> Result<bool, String> {
+    let public = check_key(key)?;
+    let signature = signature_path(file);
+    if !signature.is_file() {
+        return Err(format!(
+            "{} has no signature {}",
+            file.display(),
+            signature.display()
+        ));
+    }
+    let mut command = openssl(&["pkeyutl", "-verify", "-rawin"]);
+    if public {
+        command.arg("-pubin");
+    }
+    command
+        .arg("-inkey")
+        .arg(key)
+        .arg("-in")
+        .arg(file)
+        .arg("-sigfile")
+        .arg(&signature);
+    // Fails the same way for a bad signature as for anything else, but
+    // everything else was checked for beforehand
+    Ok(run(&mut command)?.status.success())
+}
+
+/// Makes sure the key at `key` is an Ed25519 key, and tells whether it's
+/// only the public one.
+fn check_key(key: &Path) -> Result<bool, String> {
+    let text = std::fs::read_to_string(key)
+        .map_err(|e| format!("failed to read {}: {}", key.display(), e))?;
+    let public = text.contains("BEGIN PUBLIC KEY");
+    let mut command = openssl(&["pkey", "-noout", "-text"]);
+    if public {
+        command.arg("-pubin");
+    }
+    let output = run(command.arg("-in").arg(key))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} isn't a key openssl can read: {}",
+            key.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    if !output.stdout.starts_with(b"ED25519") {
+        return Err(format!(
+            "{} isn't an Ed25519 key, make one with openssl genpkey -algorithm ed25519",
+            key.display()
+        ));
+    }
+    Ok(public)
+}
+
+fn openssl(args: &[&str]) -> Command {
+    let mut command = Command::new("openssl");
+    command.args(args);
+    command
+}
+
+fn run(command: &mut Command) -> Result<Output, String> {
+    command
+        .output()
+        .map_err(|e| format!("failed to run openssl: {}", e))
+}
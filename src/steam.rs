@@ -0,0 +1,176 @@
+//! What Steam knows about an installed game. Every library has an
+//! `appmanifest_<appid>.acf` in its `steamapps` for each game installed to
+//! `steamapps/common/<installdir>`, saying which build of which branch it is.
+//! Which libraries there are is in the main one's `libraryfolders.vdf`.
+
+use crate::vdf::{self, Table, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The branch everyone not opted into a beta is on.
+const PUBLIC: &str = "public";
+/// Where Steam keeps itself, relative to the home directory.
+const ROOTS: &[&str] = &[".steam/root", ".steam/steam", ".local/share/Steam"];
+/// Where the list of libraries is, relative to Steam's directory. The
+/// second is where older clients kept it.
+const LIBRARY_FOLDERS: &[&str] = &["steamapps/libraryfolders.vdf", "config/libraryfolders.vdf"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub path: PathBuf,
+    pub appid: u32,
+    /// The game's directory under `steamapps/common`.
+    pub installdir: String,
+    /// Steam's build id, the same for every depot of the app.
+    pub buildid: String,
+    pub branch: String,
+    /// The manifest id installed of each depot.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub depots: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let root = vdf::load(path)?;
+        let error = |e: &str| format!("{}: {}", path.display(), e);
+        let state = root.table("AppState").ok_or_else(|| error("no AppState"))?;
+        Manifest::from_state(path, state).ok_or_else(|| error("no appid, installdir or buildid"))
+    }
+
+    fn from_state(path: &Path, state: &Table) -> Option<Self> {
+        let branch = ["UserConfig", "MountedConfig"]
+            .iter()
+            .filter_map(|config| state.table(config)?.string("BetaKey"))
+            .find(|branch| !branch.is_empty())
+            .unwrap_or(PUBLIC);
+        let depots = state
+            .table("InstalledDepots")
+            .into_iter()
+            .flat_map(Table::tables)
+            .filter_map(|(depot, table)| {
+                Some((depot.to_string(), table.string("manifest")?.to_string()))
+            })
+            .collect();
+        Some(Manifest {
+            path: path.to_path_buf(),
+            appid: state.string("appid")?.parse().ok()?,
+            installdir: state.string("installdir")?.to_string(),
+            buildid: state.string("buildid")?.to_string(),
+            branch: branch.to_string(),
+            depots,
+        })
+    }
+
+    /// The manifest of the game installed at `gamedir`, if it is in a Steam
+    /// library, or was installed by SteamCMD with `force_install_dir`,
+    /// which puts the manifest into the game directory's own `steamapps`.
+    pub fn find(gamedir: &Path) -> Option<Self> {
+        let gamedir = gamedir.canonicalize().ok()?;
+        if let Some(manifest) = manifests(&gamedir.join("steamapps")).next() {
+            return Some(manifest);
+        }
+        let common = gamedir
+            .ancestors()
+            .find(|dir| dir.ends_with("steamapps/common"))?;
+        let installdir = gamedir.strip_prefix(common).ok()?.iter().next()?;
+        manifests(common.parent()?)
+            .find(|manifest| manifest.installdir == installdir.to_string_lossy())
+    }
+}
+
+/// The app manifests in `steamapps` that can be read.
+fn manifests(steamapps: &Path) -> impl Iterator<Item = Manifest> {
+    fs::read_dir(steamapps)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with("appmanifest_") && name.ends_with(".acf")
+        })
+        .filter_map(|path| Manifest::load(&path).ok())
+}
+
+/// Where Steam may be installed, whether or not it is.
+pub fn roots() -> Vec<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .iter()
+        .flat_map(|home| ROOTS.iter().map(move |root| home.join(root)))
+        .collect()
+}
+
+/// Every Steam library, each Steam installation's own first and then the
+/// ones it lists.
+pub fn libraries() -> Vec<PathBuf> {
+    let mut libraries: Vec<PathBuf> = Vec::new();
+    for root in roots() {
+        let root = match root.canonicalize() {
+            Ok(root) => root,
+            Err(_) => continue,
+        };
+        let listed = LIBRARY_FOLDERS
+            .iter()
+            .find_map(|file| vdf::load(&root.join(file)).ok())
+            .map(|folders| library_folders(&folders))
+            .unwrap_or_default();
+        for library in std::iter::once(root).chain(listed) {
+            let library = library.canonicalize().unwrap_or(library);
+            if library.join("steamapps").is_dir() && !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+    libraries
+}
+
+/// The paths in `libraryfolders.vdf`. Current clients give each library a
+/// numbered table with its `path`, older ones just the numbered path.
+fn library_folders(root: &Table) -> Vec<PathBuf> {
+    root.tables()
+        .flat_map(|(_, folders)| &folders.0)
+        .filter(|(key, _)| key.parse::<u32>().is_ok())
+        .filter_map(|(_, value)| match value {
+            Value::String(path) => Some(PathBuf::from(path)),
+            Value::Table(folder) => folder.string("path").map(PathBuf::from),
+        })
+        .collect()
+}
+
+/// The directory app `appid` is installed to, from the first library with
+/// a manifest for it.
+pub fn find_app(appid: u32) -> Result<PathBuf, String> {
+    let libraries = libraries();
+    if libraries.is_empty() {
+        return Err(format!(
+            "can't look for app {}, no Steam installation in ~/{}",
+            appid,
+            ROOTS.join(", ~/")
+        ));
+    }
+    for library in &libraries {
+        let path = library
+            .join("steamapps")
+            .join(format!("appmanifest_{}.acf", appid));
+        if !path.is_file() {
+            continue;
+        }
+        let manifest = Manifest::load(&path)?;
+        let dir = library.join("steamapps/common").join(&manifest.installdir);
+        if dir.is_dir() {
+            return Ok(dir);
+        }
+    }
+    Err(format!(
+        "app {} isn't installed in any Steam library, looked in {}",
+        appid,
+        libraries
+            .iter()
+            .map(|library| library.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
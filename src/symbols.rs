@@ -0,0 +1,53 @@
+//! Names for code addresses, from the dumped module's symbol table.
+
+use crate::elf::ElfFile;
+use crate::module::Module;
+use cpp_demangle::Symbol;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Default)]
+pub struct Symbolizer {
+    /// Absolute address ranges of functions, sorted by start, still
+    /// mangled. Demangling is left for the few we actually look up.
+    functions: Vec<(Range<usize>, String)>,
+}
+
+impl Symbolizer {
+    pub fn new(module: &Module, elf: &ElfFile) -> Self {
+        let mut functions: Vec<_> = elf
+            .symbols
+            .iter()
+            .filter(|s| s.function)
+            .map(|s| {
+                let start = module.address + s.address;
+                (start..start + s.size, s.name.clone())
+            })
+            .collect();
+        functions.sort_by_key(|(range, _)| range.start);
+        functions.dedup_by_key(|(range, _)| range.start);
+        Symbolizer { functions }
+    }
+
+    /// The demangled name of the function `address` belongs to, with the
+    /// offset into it unless that's 0.
+    pub fn name(&self, address: usize) -> Option<String> {
+        let index = self
+            .functions
+            .partition_point(|(range, _)| range.start <= address)
+            .checked_sub(1)?;
+        let (range, name) = &self.functions[index];
+        match address - range.start {
+            0 => Some(demangle(name)),
+            offset if range.contains(&address) => Some(format!("{}+{:#X}", demangle(name), offset)),
+            _ => None,
+        }
+    }
+}
+
+/// `name` in C++ syntax if it's an Itanium ABI name, as is.
+pub fn demangle(name: &str) -> String {
+    Symbol::new(name)
+        .ok()
+        .and_then(|symbol| symbol.demangle().ok())
+        .unwrap_or_else(|| name.to_string())
+}
@@ -0,0 +1,176 @@
+//! 010 Editor binary templates of the classes, to lay a dumped entity over
+//! a memory snapshot or savegame in the hex editor.
+//!
+//! Every class becomes a struct of its flattened props. Templates are read
+//! front to back, so each field seeks to its offset from the start of the
+//! struct first, which also copes with props that overlap. Ints are as wide
+//! as there's room for up to the next prop, since their proxy may decode
+//! them into anything from a `bool` to an `int`. Arrays are bytes, their
+//! element type isn't part of the flattened prop.
+
+use crate::flatten::flatten;
+use crate::sdk::PropType;
+use crate::walk::{Class, Dump};
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+pub fn write(out: &mut impl Write, dump: &Dump) -> io::Result<()> {
+    writeln!(out, "//------------------------------------------------")?;
+    writeln!(out, "//--- 010 Editor Binary Template")?;
+    writeln!(out, "//")?;
+    writeln!(out, "//   Purpose: Entity layouts dumped by netvars-rs")?;
+    writeln!(out, "//------------------------------------------------")?;
+    for class in &dump.classes {
+        writeln!(out)?;
+        write_class(out, class)?;
+    }
+    if let Some(class) = dump.classes.first() {
+        writeln!(out)?;
+        writeln!(out, "// Place one where an entity starts, e.g.")?;
+        writeln!(out, "// FSeek(0x1000); {} entity;", identifier(&class.name))?;
+    }
+    Ok(())
+}
+
+fn write_class(out: &mut impl Write, class: &Class) -> io::Result<()> {
+    let mut props = match &class.table {
+        Some(table) => flatten(table),
+        None => Vec::new(),
+    };
+    props.retain(|prop| prop.offset >= 0);
+    props.sort_by_key(|prop| prop.offset);
+    // The same prop from e.g. both the local and the non-local table
+    let mut seen = HashSet::new();
+    props.retain(|prop| seen.insert((identifier(&prop.name), prop.offset)));
+
+    let mut end = 0;
+    writeln!(out, "typedef struct {{")?;
+    writeln!(out, "    local int64 base <hidden=true> = FTell();")?;
+    for (i, flat) in props.iter().enumerate() {
+        let room = props[i + 1..]
+            .iter()
+            .map(|next| next.offset - flat.offset)
+            .find(|&room| room > 0)
+            .unwrap_or(4);
+        let name = identifier(&flat.name);
+        let size = i64::from(flat.prop.size.max(1));
+        let (field, size) = match flat.prop.kind {
+            Some(PropType::Int) => match room {
+                1 => (format!("ubyte {}", name), 1),
+                2 | 3 => (format!("short {}", name), 2),
+                _ => (format!("int {}", name), 4),
+            },
+            Some(PropType::Float) => (format!("float {}", name), 4),
+            Some(PropType::Vector) => (format!("float {}[3]", name), 12),
+            Some(PropType::VectorXY) => (format!("float {}[2]", name), 8),
+            Some(PropType::Int64) => (format!("int64 {}", name), 8),
+            Some(PropType::String) => (format!("char {}[{}]", name, size), size),
+            _ => (format!("ubyte {}[{}]", name, size), size),
+        };
+        write!(out, "    FSeek(base + {:#x}); {};", flat.offset, field)?;
+        if name != flat.name {
+            write!(out, " // {}", flat.name)?;
+        }
+        writeln!(out)?;
+        end = end.max(flat.offset + size);
+    }
+    writeln!(out, "    FSeek(base + {:#x});", end)?;
+    writeln!(out, "}} {};", identifier(&class.name))
+}
+
+/// `name` with everything but letters, digits and `_` replaced, so it can
+/// name a field or type.
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn prop(name: &str, offset: i32, kind: &str, size: i32, table: Value) -> Value {
+        json!({
+            "name": name,
+            "offset": offset,
+            "kind": kind,
+            "size": size,
+            "inside_array": false,
+            "table": table,
+        })
+    }
+
+    #[test]
+    fn writes_templates() {
+        let ammo = json!({
+            "name": "m_iAmmo",
+            "props": [
+                prop("000", 0, "Int", 4, Value::Null),
+                prop("001", 4, "Int", 4, Value::Null),
+            ],
+        });
+        let dump: Dump = serde_json::from_value(json!({
+            "classes": [{
+                "name": "CCSPlayer",
+                "id": 40,
+                "table": {
+                    "name": "DT_CSPlayer",
+                    "props": [
+                        prop("m_bSpotted", 0x104, "Int", 1, Value::Null),
+                        prop("m_fFlags", 0x100, "Int", 4, Value::Null),
+                        prop("m_bDucked", 0x105, "Int", 1, Value::Null),
+                        prop("m_nWaterLevel", 0x106, "Int", 2, Value::Null),
+                        prop("m_iHealth", 0x108, "Int", 4, Value::Null),
+                        prop("m_vecOrigin", 0x138, "Vector", 12, Value::Null),
+                        prop("m_szLastPlaceName", 0x150, "String", 18, Value::Null),
+                        prop("m_iAmmo", 0x200, "DataTable", 0, ammo),
+                        prop("m_unknown", -1, "Int", 4, Value::Null),
+                    ],
+                },
+                "confidence": 1.0,
+            }],
+            "signatures": [],
+            "problems": [],
+        }))
+        .unwrap();
+        let mut out = Vec::new();
+        write(&mut out, &dump).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let body = "\
+typedef struct {
+    local int64 base <hidden=true> = FTell();
+    FSeek(base + 0x100); int m_fFlags;
+    FSeek(base + 0x104); ubyte m_bSpotted;
+    FSeek(base + 0x105); ubyte m_bDucked;
+    FSeek(base + 0x106); short m_nWaterLevel;
+    FSeek(base + 0x108); int m_iHealth;
+    FSeek(base + 0x138); float m_vecOrigin[3];
+    FSeek(base + 0x150); char m_szLastPlaceName[18];
+    FSeek(base + 0x200); int m_iAmmo_000; // m_iAmmo.000
+    FSeek(base + 0x204); int m_iAmmo_001; // m_iAmmo.001
+    FSeek(base + 0x208);
+} CCSPlayer;
+
+// Place one where an entity starts, e.g.
+// FSeek(0x1000); CCSPlayer entity;
+";
+        assert!(out.starts_with("//---"), "{}", out);
+        assert!(out.ends_with(body), "{}", out);
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(identifier("m_iHealth"), "m_iHealth");
+        assert_eq!(identifier("m_hMyWeapons.000"), "m_hMyWeapons_000");
+        assert_eq!(identifier("\"player_array\""), "_player_array_");
+        assert_eq!(identifier("000"), "_000");
+        assert_eq!(identifier("ünicode"), "_nicode");
+    }
+}
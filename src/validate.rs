@@ -0,0 +1,163 @@
+//! Sanity checks over a finished dump.
+//!
+//! None of these are proof of anything on their own, but a broken signature
+//! or a wrong struct layout tends to trip several of them at once.
+
+use crate::flatten::{flatten, FlatProp};
+use crate::report::{Problem, ProblemKind};
+use crate::walk::{Class, Dump};
+use std::collections::HashMap;
+
+/// Flags props that share a name but not an offset, and props that overlap
+/// each other, within every flattened class.
+pub fn conflicts(dump: &Dump) -> Vec<Problem> {
+    dump.classes.iter().flat_map(class_conflicts).collect()
+}
+
+/// The conflicts within one class, for classes checked as they come in.
+pub fn class_conflicts(class: &Class) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if let Some(table) = &class.table {
+        let props = flatten(table);
+        duplicates(class, &props, &mut problems);
+        overlaps(class, &props, &mut problems);
+    }
+    problems
+}
+
+fn duplicates(class: &Class, props: &[FlatProp], problems: &mut Vec<Problem>) {
+    let mut offsets: HashMap<&str, Vec<i64>> = HashMap::new();
+    for prop in props {
+        let seen = offsets.entry(&prop.name).or_default();
+        // The same prop showing up in e.g. both the local and the non-local
+        // exclusive table is fine, as long as it's at the same place.
+        if !seen.contains(&prop.offset) {
+            seen.push(prop.offset);
+        }
+    }
+
+    let mut duplicates: Vec<_> = offsets.into_iter().filter(|(_, o)| o.len() > 1).collect();
+    duplicates.sort();
+    for (name, offsets) in duplicates {
+        let offsets: Vec<_> = offsets.iter().map(|o| format!("{:#X}", o)).collect();
+        problems.push(Problem {
+            kind: ProblemKind::DuplicateProp,
+            location: format!("{}/{}", class.name, name),
+            reason: format!("prop appears at several offsets: {}", offsets.join(", ")),
+        });
+    }
+}
+
+fn overlaps(class: &Class, props: &[FlatProp], problems: &mut Vec<Problem>) {
+    let mut sorted: Vec<_> = props.iter().filter(|p| p.prop.size > 0).collect();
+    sorted.sort_by_key(|p| p.offset);
+
+    for (i, a) in sorted.iter().enumerate() {
+        let end = a.offset + i64::from(a.prop.size);
+        for b in sorted[i + 1..].iter().take_while(|b| b.offset < end) {
+            if !related(&a.name, &b.name) {
+                problems.push(Problem {
+                    kind: ProblemKind::OverlappingProps,
+                    location: format!("{}/{}", class.name, a.name),
+                    reason: format!(
+                        "{} bytes at {:#X} overlap {} at {:#X}",
+                        a.prop.size, a.offset, b.name, b.offset
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Whether two props are views of the same field, like `m_vecOrigin` sent as
+/// a vector to some clients and as `m_vecOrigin` + `m_vecOrigin[2]` to others.
+fn related(a: &str, b: &str) -> bool {
+    fn base(name: &str) -> &str {
+        name.split('[').next().unwrap_or(name)
+    }
+    base(a) == base(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn prop(name: &str, offset: i32, size: u32) -> serde_json::Value {
+        json!({
+            "name": name,
+            "offset": offset,
+            "kind": "Int",
+            "size": size,
+            "inside_array": false,
+        })
+    }
+
+    fn class(props: Vec<serde_json::Value>) -> Class {
+        serde_json::from_value(json!({
+            "name": "CPlayer",
+            "id": 1,
+            "table": { "name": "DT_Player", "props": props },
+            "confidence": 1.0,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn clean_class() {
+        let local = json!({
+            "name": "m_Local",
+            "offset": 0x100,
+            "kind": "DataTable",
+            "size": 0,
+            "inside_array": false,
+            "table": {
+                "name": "DT_Local",
+                "props": [prop("m_iHealth", -0xF0, 4), prop("m_nTickBase", 4, 4)],
+            },
+        });
+        let class = class(vec![
+            prop("m_iHealth", 0x10, 4),
+            prop("m_vecOrigin", 0x20, 12),
+            prop("m_vecOrigin[2]", 0x28, 4),
+            local,
+        ]);
+        assert!(class_conflicts(&class).is_empty());
+    }
+
+    #[test]
+    fn duplicates_and_overlaps() {
+        let class = class(vec![
+            prop("m_iHealth", 0x10, 4),
+            prop("m_iHealth", 0x40, 4),
+            prop("m_fFlags", 0x12, 4),
+        ]);
+        let problems = class_conflicts(&class);
+        let found: Vec<_> = problems
+            .iter()
+            .map(|p| (p.kind, p.location.as_str(), p.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    ProblemKind::DuplicateProp,
+                    "CPlayer/m_iHealth",
+                    "prop appears at several offsets: 0x10, 0x40"
+                ),
+                (
+                    ProblemKind::OverlappingProps,
+                    "CPlayer/m_iHealth",
+                    "4 bytes at 0x10 overlap m_fFlags at 0x12"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn class_without_table() {
+        let mut class = class(Vec::new());
+        class.table = None;
+        assert!(class_conflicts(&class).is_empty());
+    }
+}
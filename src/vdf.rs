@@ -0,0 +1,229 @@
+//! Valve's text KeyValues, the format of Steam's `.acf` and `.vdf` files:
+//!
+//! ```text
+//! "AppState"
+//! {
+//!     "appid"     "730"
+//!     "UserConfig" { "betakey" "beta" }
+//! }
+//! ```
+//!
+//! Keys are matched regardless of case, like Steam does. `[$WIN32]`-style
+//! conditions after a value are skipped.
+
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Table(Table),
+}
+
+/// Keys and values in the order they were read. Keys may repeat.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table(pub Vec<(String, Value)>);
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn string(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Value::String(s) => Some(s),
+            Value::Table(_) => None,
+        }
+    }
+
+    pub fn table(&self, key: &str) -> Option<&Table> {
+        match self.get(key)? {
+            Value::Table(table) => Some(table),
+            Value::String(_) => None,
+        }
+    }
+
+    /// The entries that are tables themselves.
+    pub fn tables(&self) -> impl Iterator<Item = (&str, &Table)> {
+        self.0.iter().filter_map(|(key, value)| match value {
+            Value::Table(table) => Some((key.as_str(), table)),
+            Value::String(_) => None,
+        })
+    }
+}
+
+pub fn load(path: &Path) -> Result<Table, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(text: &str) -> Result<Table, String> {
+    parse_table(&mut Tokens { text, at: 0 }, false)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    String(String),
+    Condition,
+    Open,
+    Close,
+}
+
+struct Tokens<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl Tokens<'_> {
+    fn line(&self) -> usize {
+        self.text[..self.at].matches('\n').count() + 1
+    }
+
+    fn error(&self, e: &str) -> String {
+        format!("line {}: {}", self.line(), e)
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, String> {
+        loop {
+            let rest = &self.text[self.at..];
+            let trimmed = rest.trim_start();
+            self.at += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.at += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+        let rest = &self.text[self.at..];
+        let mut chars = rest.char_indices();
+        let token = match chars.next() {
+            None => return Ok(None),
+            Some((_, '{')) => {
+                self.at += 1;
+                Token::Open
+            }
+            Some((_, '}')) => {
+                self.at += 1;
+                Token::Close
+            }
+            Some((_, '[')) => {
+                let end = rest
+                    .find(']')
+                    .ok_or_else(|| self.error("unterminated condition"))?;
+                self.at += end + 1;
+                Token::Condition
+            }
+            Some((_, '"')) => {
+                let mut string = String::new();
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            end = Some(i + 1);
+                            break;
+                        }
+                        '\\' => match chars.next().map(|(_, c)| c) {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(c) => string.push(c),
+                            None => break,
+                        },
+                        _ => string.push(c),
+                    }
+                }
+                let end = end.ok_or_else(|| self.error("unterminated string"))?;
+                self.at += end;
+                Token::String(string)
+            }
+            Some(_) => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || "{}\"".contains(c))
+                    .unwrap_or(rest.len());
+                self.at += end;
+                Token::String(rest[..end].to_string())
+            }
+        };
+        Ok(Some(token))
+    }
+}
+
+/// Key and value pairs up to the closing brace if `nested`, or the end of
+/// the text otherwise.
+fn parse_table(tokens: &mut Tokens, nested: bool) -> Result<Table, String> {
+    let mut table = Table::default();
+    loop {
+        let key = match tokens.next()? {
+            Some(Token::String(key)) => key,
+            Some(Token::Condition) => continue,
+            Some(Token::Close) if nested => return Ok(table),
+            None if !nested => return Ok(table),
+            None => return Err(tokens.error("missing }")),
+            Some(Token::Close) => return Err(tokens.error("unexpected }")),
+            Some(Token::Open) => return Err(tokens.error("expected a key, got {")),
+        };
+        let value = match tokens.next()? {
+            Some(Token::String(value)) => Value::String(value),
+            Some(Token::Open) => Value::Table(parse_table(tokens, true)?),
+            _ => return Err(tokens.error(&format!("{} has no value", key))),
+        };
+        table.0.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_manifest() {
+        let table = parse(
+            r#"
+            "AppState"
+            {
+                // Comments go to the end of the line
+                "appid"     "730"
+                "buildid"   "7051517"   [$LINUX]
+                "installdir" "Counter-Strike \"Global\" Offensive"
+                "UserConfig" { "BetaKey" "1.38.2.2" }
+                unquoted value
+            }
+            "#,
+        )
+        .unwrap();
+        let state = table.table("appstate").unwrap();
+        assert_eq!(state.string("AppID"), Some("730"));
+        assert_eq!(state.string("buildid"), Some("7051517"));
+        assert_eq!(
+            state.string("installdir"),
+            Some("Counter-Strike \"Global\" Offensive")
+        );
+        assert_eq!(
+            state.table("userconfig").and_then(|c| c.string("betakey")),
+            Some("1.38.2.2")
+        );
+        assert_eq!(state.string("unquoted"), Some("value"));
+        assert_eq!(state.string("UserConfig"), None);
+        assert_eq!(state.tables().count(), 1);
+    }
+
+    #[test]
+    fn keys_may_repeat() {
+        let table = parse(r#""a" "1" "A" "2""#).unwrap();
+        assert_eq!(table.0.len(), 2);
+        assert_eq!(table.string("a"), Some("1"));
+    }
+
+    #[test]
+    fn invalid_text() {
+        let error = |text| parse(text).unwrap_err();
+        assert_eq!(error("\"a\" {\n\"b\" \"c\""), "line 2: missing }");
+        assert_eq!(error("\"a\" \"b\" }"), "line 1: unexpected }");
+        assert_eq!(error("{"), "line 1: expected a key, got {");
+        assert_eq!(error("\"a\""), "line 1: a has no value");
+        assert_eq!(error("\"a\" \"b"), "line 1: unterminated string");
+        assert_eq!(error("\"a\" \"b\" [$X"), "line 1: unterminated condition");
+    }
+}
@@ -0,0 +1,216 @@
+//! Anchors: tables every build has and props whose offsets can only be in
+//! some range. A dump read with a wrong layout is missing the tables or has
+//! the props all over the place, while still looking fine otherwise.
+//!
+//! Anchors are given one per line, `#` starting a comment:
+//!
+//! ```text
+//! DT_BasePlayer                   # has to exist
+//! DT_BasePlayer.m_iHealth 1..0x4000
+//! ```
+//!
+//! The prop is looked for like a config's netvars are, and its offset has
+//! to be at least the first number and below the second.
+
+use crate::compress;
+use crate::report::{self, Problem, ProblemKind};
+use crate::walk::Dump;
+use std::path::{Path, PathBuf};
+
+/// CS:GO's, see [`parse`].
+pub const BUILTIN: &str = "\
+DT_BaseEntity
+DT_BasePlayer
+DT_CSPlayer
+DT_BaseEntity.m_iTeamNum 1..0x1000
+DT_BaseEntity.m_vecOrigin 1..0x1000
+DT_BasePlayer.m_iHealth 1..0x4000
+DT_BasePlayer.m_fFlags 1..0x4000
+DT_BasePlayer.m_lifeState 1..0x4000
+DT_CSPlayer.m_ArmorValue 1..0x20000
+";
+
+#[derive(Debug, Clone)]
+pub struct Anchor {
+    pub table: String,
+    pub prop: Option<String>,
+    /// Where the prop's offset has to be, from the first up to the second.
+    pub range: Option<(i64, i64)>,
+}
+
+impl Anchor {
+    /// Why `dump` doesn't hold up to this anchor, if it doesn't.
+    pub fn check(&self, dump: &Dump) -> Option<String> {
+        let table = match dump.table(&self.table) {
+            Some(table) => table,
+            None => return Some(format!("no table {}", self.table)),
+        };
+        let prop = self.prop.as_ref()?;
+        let offset = match table.find(prop) {
+            Some(offset) => offset,
+            None => return Some(format!("no prop {} in {}", prop, self.table)),
+        };
+        match self.range {
+            Some((min, max)) if offset < min || offset >= max => {
+                Some(format!("at {:#x}, expected {:#x}..{:#x}", offset, min, max))
+            }
+            _ => None,
+        }
+    }
+
+    fn location(&self) -> String {
+        match &self.prop {
+            Some(prop) => format!("{}.{}", self.table, prop),
+            None => self.table.clone(),
+        }
+    }
+}
+
+/// The built-in anchors if `builtin`, and those in `files`.
+pub fn anchors(builtin: bool, files: &[PathBuf]) -> Result<Vec<Anchor>, String> {
+    let mut anchors = if builtin {
+        parse(BUILTIN).expect("the built-in anchors parse")
+    } else {
+        Vec::new()
+    };
+    for file in files {
+        anchors.extend(load(file)?);
+    }
+    Ok(anchors)
+}
+
+pub fn load(path: &Path) -> Result<Vec<Anchor>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(text: &str) -> Result<Vec<Anchor>, String> {
+    let mut anchors = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let (table, prop) = match name.split_once('.') {
+            Some((table, prop)) => (table, Some(prop.to_string())),
+            None => (name, None),
+        };
+        let range = words.next().map(parse_range).transpose().map_err(error)?;
+        if let Some(extra) = words.next() {
+            return Err(error(format!("unexpected {}", extra)));
+        }
+        if range.is_some() && prop.is_none() {
+            return Err(error(format!(
+                "{} is a table, only props have offsets",
+                name
+            )));
+        }
+        anchors.push(Anchor {
+            table: table.to_string(),
+            prop,
+            range,
+        });
+    }
+    Ok(anchors)
+}
+
+/// `<min>..<max>`, decimal or `0x`-prefixed hex.
+fn parse_range(s: &str) -> Result<(i64, i64), String> {
+    let number = |n: &str| match n.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => n.parse().ok(),
+    };
+    s.split_once("..")
+        .and_then(|(min, max)| Some((number(min)?, number(max)?)))
+        .filter(|(min, max)| min < max)
+        .ok_or_else(|| format!("invalid range {:?}, expected <min>..<max>", s))
+}
+
+/// A problem for every anchor `dump` doesn't hold up to.
+pub fn check(dump: &Dump, anchors: &[Anchor]) -> Vec<Problem> {
+    anchors
+        .iter()
+        .filter_map(|anchor| {
+            Some(Problem {
+                kind: ProblemKind::AnchorFailed,
+                location: anchor.location(),
+                reason: anchor.check(dump)?,
+            })
+        })
+        .collect()
+}
+
+/// Checks the JSON dump at `path`, printing how it scored.
+pub fn run(path: &Path, anchors: &[Anchor]) -> i32 {
+    let dump: Dump = match compress::read(path).and_then(|json| {
+        serde_json::from_slice(&json)
+            .map_err(|e| format!("{} isn't a JSON dump: {}", path.display(), e))
+    }) {
+        Ok(dump) => dump,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return report::EXIT_FAILED;
+        }
+    };
+    let failed = check(&dump, anchors);
+    for problem in &failed {
+        println!("FAIL: {}", problem);
+    }
+    let confidence = if dump.classes.is_empty() {
+        0.0
+    } else {
+        dump.classes
+            .iter()
+            .map(|class| class.confidence)
+            .sum::<f32>()
+            / dump.classes.len() as f32
+    };
+    println!(
+        "{} of {} anchors hold, classes are {:.0}% plausible on average",
+        anchors.len() - failed.len(),
+        anchors.len(),
+        confidence * 100.0
+    );
+    if failed.is_empty() {
+        println!("ok: the dump looks right");
+        0
+    } else {
+        println!("the dump looks like it was read with the wrong layout");
+        ProblemKind::AnchorFailed.exit_code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("1..0x4000"), Ok((1, 0x4000)));
+        assert_eq!(parse_range("0x10..32"), Ok((0x10, 32)));
+        for invalid in ["1", "4..4", "5..1", "0x..1", "a..b", "1..2..3"] {
+            assert!(parse_range(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_anchors() {
+        let anchors = parse(BUILTIN).unwrap();
+        assert_eq!(anchors.len(), 9);
+        assert!(anchors[..3].iter().all(|a| a.prop.is_none()));
+        assert_eq!(anchors[5].location(), "DT_BasePlayer.m_iHealth");
+        assert_eq!(anchors[5].range, Some((1, 0x4000)));
+
+        let error = |text: &str| parse(text).unwrap_err();
+        assert_eq!(
+            error("\n# a comment\nDT_A 1..2"),
+            "line 3: DT_A is a table, only props have offsets"
+        );
+        assert_eq!(error("DT_A.m_b 1..2 3"), "line 1: unexpected 3");
+        assert!(error("DT_A.m_b 2..1").starts_with("line 1: invalid range"));
+    }
+}
@@ -0,0 +1,413 @@
+//! Walks the `ClientClass` list into owned data.
+//!
+//! All reads go through [`Memory`], so a bad pointer anywhere in the chain
+//! only costs us the class, table or prop it belongs to. Whatever couldn't be
+//! read is recorded as a [`Problem`] next to the classes that could.
+//!
+//! Readable isn't the same as right, though: a mismatched layout reads just
+//! fine and produces garbage. Fields are checked for plausibility before
+//! they're trusted, and every class gets a confidence score from how many of
+//! those checks it passed.
+
+use crate::memory::{Memory, ReadError};
+use crate::metadata::Metadata;
+use crate::module::Module;
+use crate::report::{Problem, ProblemKind};
+use crate::sdk::{ClientClass, PropType, RecvProp, RecvTable};
+use crate::signature::SignatureMatch;
+use crate::symbols::Symbolizer;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::mem::size_of;
+
+/// More props than this in one table means we're not looking at a table.
+pub const MAX_PROPS: i32 = 4096;
+/// Offsets past this don't fit any entity we know of.
+pub const MAX_OFFSET: i32 = 0x10_0000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Class {
+    pub name: String,
+    /// The name's bytes, only present if they weren't valid UTF-8 and `name`
+    /// is a lossy conversion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_raw: Option<Vec<u8>>,
+    pub id: i32,
+    /// The symbol `m_pCreateFn` points to, if the module has one for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_fn: Option<String>,
+    pub table: Option<Table>,
+    /// Share of the plausibility checks on this class and everything below
+    /// it that passed, from 0 to 1.
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Table {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_raw: Option<Vec<u8>>,
+    pub props: Vec<Prop>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prop {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_raw: Option<Vec<u8>>,
+    pub offset: i32,
+    /// `None` if `m_RecvType` was out of range.
+    pub kind: Option<PropType>,
+    /// The smallest number of bytes this prop can occupy, see
+    /// [`PropType::min_size`].
+    pub size: u32,
+    /// Element template of an array prop rather than a field of its own.
+    pub inside_array: bool,
+    /// The symbol `m_ProxyFn` points to, if the module has one for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    pub table: Option<Table>,
+}
+
+impl Table {
+    /// The offset of the first prop called `name`, looking depth-first
+    /// through the tables embedded in this one.
+    pub fn find(&self, name: &str) -> Option<i64> {
+        self.props.iter().find_map(|prop| {
+            if prop.name == name {
+                return Some(i64::from(prop.offset));
+            }
+            let child = prop.table.as_ref()?;
+            child
+                .find(name)
+                .map(|offset| i64::from(prop.offset) + offset)
+        })
+    }
+
+    /// This table or the first one by `name` among those below it.
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.props
+            .iter()
+            .filter_map(|prop| prop.table.as_ref())
+            .find_map(|table| table.table(name))
+    }
+}
+
+/// Everything a dump produces, in the order it was found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Event {
+    Class(Class),
+    Problem(Problem),
+    Signature(SignatureMatch),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Dump {
+    /// Filled in once the dump is done, not by the worker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    pub classes: Vec<Class>,
+    pub signatures: Vec<SignatureMatch>,
+    pub problems: Vec<Problem>,
+    /// Offsets by the names a config gave them, see [`crate::hazedumper`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub netvars: BTreeMap<String, i64>,
+    /// What every signature found, by its name: the first target if it has
+    /// any, otherwise the first match.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub offsets: BTreeMap<String, usize>,
+}
+
+impl Dump {
+    /// The table called `name`, be it a class's or one embedded in one.
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        let tables = || self.classes.iter().filter_map(|class| class.table.as_ref());
+        tables()
+            .find(|table| table.name == name)
+            .or_else(|| tables().find_map(|table| table.table(name)))
+    }
+
+    pub fn push(&mut self, event: Event) {
+        match event {
+            Event::Class(class) => self.classes.push(class),
+            Event::Problem(problem) => self.problems.push(problem),
+            Event::Signature(signature) => {
+                let offset = signature.targets.first().or(signature.matches.first());
+                if let Some(&offset) = offset {
+                    self.offsets.insert(signature.name.clone(), offset);
+                }
+                self.signatures.push(signature)
+            }
+        }
+    }
+}
+
+struct Walker<'a> {
+    memory: &'a Memory,
+    /// Names are string literals, so they had better point into a readable
+    /// segment of one of these.
+    modules: &'a [Module],
+    symbols: &'a Symbolizer,
+    emit: &'a mut dyn FnMut(Event),
+    /// Checks performed and failed for the current class.
+    checks: u32,
+    failures: u32,
+    /// Tables on the current path, so a garbage self-reference can't recurse
+    /// forever.
+    stack: Vec<usize>,
+}
+
+/// Walks the list starting at `head`, handing every class to `emit` as soon
+/// as it has been read.
+pub fn walk(
+    memory: &Memory,
+    modules: &[Module],
+    symbols: &Symbolizer,
+    head: *const ClientClass,
+    emit: &mut dyn FnMut(Event),
+) {
+    Walker {
+        memory,
+        modules,
+        symbols,
+        emit,
+        checks: 0,
+        failures: 0,
+        stack: Vec::new(),
+    }
+    .classes(head as usize);
+}
+
+impl<'a> Walker<'a> {
+    fn report(&mut self, kind: ProblemKind, location: String, reason: impl ToString) {
+        self.checks += 1;
+        self.failures += 1;
+        (self.emit)(Event::Problem(Problem {
+            kind,
+            location,
+            reason: reason.to_string(),
+        }));
+    }
+
+    /// Reports a problem unless `ok` holds.
+    fn check(
+        &mut self,
+        ok: bool,
+        kind: ProblemKind,
+        location: &str,
+        reason: impl FnOnce() -> String,
+    ) -> bool {
+        if ok {
+            self.checks += 1;
+        } else {
+            self.report(kind, location.to_string(), reason());
+        }
+        ok
+    }
+
+    fn classes(&mut self, head: usize) {
+        let mut count = 0;
+        let mut seen = HashSet::new();
+        let mut address = head;
+
+        while address != 0 {
+            if !seen.insert(address) {
+                self.report(
+                    ProblemKind::BrokenStructure,
+                    format!("class #{}", count),
+                    "class list loops",
+                );
+                break;
+            }
+            let class = match unsafe { self.memory.read::<ClientClass>(address) } {
+                Ok(class) => class,
+                Err(e) => {
+                    // Without the node we don't know where the list continues
+                    self.report(
+                        ProblemKind::UnreadableMemory,
+                        format!("class #{} @ {:#X}", count, address),
+                        e,
+                    );
+                    break;
+                }
+            };
+            address = class.m_pNext as usize;
+            count += 1;
+            self.checks = 0;
+            self.failures = 0;
+
+            let location = format!("class #{}", class.m_ClassID);
+            let (name, name_raw) = match self.name(class.m_pNetworkName as usize, &location) {
+                Ok(name) => name,
+                Err(e) => {
+                    self.report(
+                        ProblemKind::UnreadableMemory,
+                        format!("class #{}", class.m_ClassID),
+                        e,
+                    );
+                    continue;
+                }
+            };
+            let table = match class.m_pRecvTable as usize {
+                0 => None,
+                table => self.table(table, &name),
+            };
+            let confidence = match self.checks {
+                0 => 1.0,
+                checks => (checks - self.failures) as f32 / checks as f32,
+            };
+            let create_fn = class
+                .m_pCreateFn
+                .and_then(|create| self.function(create as usize));
+            (self.emit)(Event::Class(Class {
+                name,
+                name_raw,
+                id: class.m_ClassID,
+                create_fn,
+                table,
+                confidence,
+            }));
+        }
+    }
+
+    fn table(&mut self, address: usize, parent: &str) -> Option<Table> {
+        let table = match unsafe { self.memory.read::<RecvTable>(address) } {
+            Ok(table) => table,
+            Err(e) => {
+                self.report(
+                    ProblemKind::UnreadableMemory,
+                    format!("{}/<table @ {:#X}>", parent, address),
+                    e,
+                );
+                return None;
+            }
+        };
+        let location = format!("{}/<table @ {:#X}>", parent, address);
+        let (name, name_raw) = match self.name(table.m_pNetTableName as usize, &location) {
+            Ok(name) => name,
+            Err(e) => {
+                self.report(ProblemKind::UnreadableMemory, location, e);
+                return None;
+            }
+        };
+        let path = format!("{}/{}", parent, name);
+
+        if self.stack.contains(&address) {
+            self.report(ProblemKind::BrokenStructure, path, "table contains itself");
+            return None;
+        }
+        let count = table.m_nProps;
+        if !self.check(
+            (0..=MAX_PROPS).contains(&count),
+            ProblemKind::ImplausibleValue,
+            &path,
+            || format!("table claims to have {} props", count),
+        ) {
+            return None;
+        }
+
+        self.stack.push(address);
+        let props = (0..table.m_nProps.max(0) as usize)
+            .filter_map(|i| self.prop(table.m_pProps as usize + i * size_of::<RecvProp>(), &path))
+            .collect();
+        self.stack.pop();
+
+        Some(Table {
+            name,
+            name_raw,
+            props,
+        })
+    }
+
+    fn prop(&mut self, address: usize, parent: &str) -> Option<Prop> {
+        let prop = match unsafe { self.memory.read::<RecvProp>(address) } {
+            Ok(prop) => prop,
+            Err(e) => {
+                self.report(
+                    ProblemKind::UnreadableMemory,
+                    format!("{}/<prop @ {:#X}>", parent, address),
+                    e,
+                );
+                return None;
+            }
+        };
+        let location = format!("{}/<prop @ {:#X}>", parent, address);
+        let (name, name_raw) = match self.name(prop.m_pVarName as usize, &location) {
+            Ok(name) => name,
+            Err(e) => {
+                self.report(ProblemKind::UnreadableMemory, location, e);
+                return None;
+            }
+        };
+        let path = format!("{}/{}", parent, name);
+        let kind = PropType::from_raw(prop.m_RecvType);
+        self.check(kind.is_some(), ProblemKind::InvalidPropType, &path, || {
+            format!("prop type {} is out of range", prop.m_RecvType)
+        });
+        self.check(
+            (0..MAX_OFFSET).contains(&prop.m_Offset),
+            ProblemKind::ImplausibleValue,
+            &path,
+            || format!("offset {:#X} is out of range", prop.m_Offset),
+        );
+        let table = match prop.m_pDataTable as usize {
+            0 => None,
+            table => self.table(table, &path),
+        };
+        Some(Prop {
+            name,
+            name_raw,
+            offset: prop.m_Offset,
+            kind,
+            size: kind.map_or(0, |kind| kind.min_size(&prop)),
+            inside_array: prop.m_bInsideArray != 0,
+            proxy: self.function(prop.m_ProxyFn as usize),
+            table,
+        })
+    }
+
+    fn function(&self, address: usize) -> Option<String> {
+        match address {
+            0 => None,
+            address => self.symbols.name(address),
+        }
+    }
+
+    fn name(
+        &mut self,
+        address: usize,
+        location: &str,
+    ) -> Result<(String, Option<Vec<u8>>), ReadError> {
+        let name = self.memory.read_cstr(address).map(decode_name)?;
+        let modules = self.modules;
+        let segment = modules
+            .iter()
+            .find_map(|m| m.segment(address).map(|s| (m, s)));
+        let (ok, problem) = match segment {
+            Some((module, segment)) => (
+                segment.read,
+                format!("lies in a segment of {} that isn't readable", module.name),
+            ),
+            None => (false, "isn't part of any module".to_string()),
+        };
+        self.check(ok, ProblemKind::ImplausibleValue, location, || {
+            format!("name {:?} at {:#X} {}", name.0, address, problem)
+        });
+        Ok(name)
+    }
+}
+
+/// Turns raw name bytes into a printable name, keeping the bytes around if
+/// that conversion had to lose anything.
+pub fn decode_name(bytes: Vec<u8>) -> (String, Option<Vec<u8>>) {
+    match String::from_utf8(bytes) {
+        Ok(name) => (name, None),
+        Err(e) => (
+            String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            Some(e.into_bytes()),
+        ),
+    }
+}
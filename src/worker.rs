@@ -0,0 +1,280 @@
+//! Runs the dump in a forked child process.
+//!
+//! `dlopen` runs the game library's constructors, and those are free to
+//! crash, call `exit()` or otherwise take the process down with them. The
+//! child does the loading and the walk and streams whatever it finds back
+//! over a pipe, one JSON message per line, so the parent always has a partial
+//! result and a reason to report.
+
+use crate::report;
+use crate::walk::{Class, Dump, Event};
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    Log(String),
+    Event(Event),
+    Failed(String),
+    Done,
+}
+
+/// The child's end of the pipe.
+pub struct Sender {
+    pipe: File,
+}
+
+impl Sender {
+    fn send(&mut self, message: &Message) {
+        // One write per message, so a crash can't leave half a line behind.
+        // If the parent is gone there's nobody left to tell, so errors are
+        // dropped.
+        if let Ok(mut line) = serde_json::to_vec(message) {
+            line.push(b'\n');
+            let _ = self.pipe.write_all(&line);
+        }
+    }
+
+    pub fn log(&mut self, message: impl Into<String>) {
+        self.send(&Message::Log(message.into()));
+    }
+
+    pub fn event(&mut self, event: Event) {
+        self.send(&Message::Event(event));
+    }
+}
+
+#[derive(Debug)]
+pub enum WorkerError {
+    /// The job returned an error or panicked.
+    Failed(String),
+    /// The child exited before finishing, e.g. a constructor called `exit()`.
+    Exited(i32),
+    Signaled(i32),
+    /// The worker was still busy when the timeout ran out and got killed.
+    TimedOut(Duration),
+    Io(io::Error),
+}
+
+impl Display for WorkerError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WorkerError::Failed(reason) => write!(f, "{}", reason),
+            WorkerError::Exited(code) => {
+                write!(f, "worker exited with status {} before finishing", code)
+            }
+            WorkerError::Signaled(signal) => {
+                let name = unsafe { CStr::from_ptr(libc::strsignal(*signal)) };
+                write!(
+                    f,
+                    "worker was killed by signal {} ({})",
+                    signal,
+                    name.to_string_lossy()
+                )
+            }
+            WorkerError::TimedOut(timeout) => write!(
+                f,
+                "worker didn't finish within {}s, the game library may be waiting on something \
+                 that isn't there",
+                timeout.as_secs_f64()
+            ),
+            WorkerError::Io(e) => write!(f, "lost contact with the worker: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WorkerError {}
+
+impl WorkerError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkerError::Failed(_) => "failed",
+            WorkerError::Exited(_) => "exited",
+            WorkerError::Signaled(_) => "crashed",
+            WorkerError::TimedOut(_) => "timed_out",
+            WorkerError::Io(_) => "io",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            WorkerError::Failed(_) => report::EXIT_FAILED,
+            WorkerError::Exited(_) | WorkerError::Signaled(_) | WorkerError::Io(_) => {
+                report::EXIT_CRASHED
+            }
+            WorkerError::TimedOut(_) => report::EXIT_TIMED_OUT,
+        }
+    }
+}
+
+/// What came back from the worker. `dump` holds everything received before
+/// the worker finished or died, `error` says why it ended early.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub dump: Dump,
+    pub error: Option<WorkerError>,
+}
+
+/// Runs `job` in a forked child and collects what it sends. If `timeout`
+/// passes before the child is done, it is killed and whatever arrived until
+/// then is returned.
+pub fn run(
+    timeout: Option<Duration>,
+    job: impl FnOnce(&mut Sender) -> Result<(), String>,
+) -> io::Result<Outcome> {
+    spawn(timeout, job, None)
+}
+
+/// Like [`run`], but hands every class to `on_class` as it arrives instead
+/// of collecting them, so the dump never has to hold all of them.
+pub fn stream(
+    timeout: Option<Duration>,
+    job: impl FnOnce(&mut Sender) -> Result<(), String>,
+    on_class: &mut dyn FnMut(Class),
+) -> io::Result<Outcome> {
+    spawn(timeout, job, Some(on_class))
+}
+
+fn spawn(
+    timeout: Option<Duration>,
+    job: impl FnOnce(&mut Sender) -> Result<(), String>,
+    on_class: Option<&mut dyn FnMut(Class)>,
+) -> io::Result<Outcome> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [read, write] = fds;
+
+    // Anything still buffered would otherwise be printed twice
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => unsafe {
+            libc::close(read);
+            // Whatever the game library prints must not end up in our output
+            libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO);
+
+            let mut sender = Sender {
+                pipe: File::from_raw_fd(write),
+            };
+            let message = match catch_unwind(AssertUnwindSafe(|| job(&mut sender))) {
+                Ok(Ok(())) => Message::Done,
+                Ok(Err(reason)) => Message::Failed(reason),
+                Err(_) => Message::Failed("worker panicked".to_string()),
+            };
+            sender.send(&message);
+            // Skip atexit handlers and destructors, those belong to the game
+            // library now.
+            libc::_exit(0)
+        },
+        child => {
+            unsafe { libc::close(write) };
+            let pipe = unsafe { File::from_raw_fd(read) };
+            Ok(collect(pipe, child, timeout, on_class))
+        }
+    }
+}
+
+fn collect(
+    mut pipe: File,
+    child: libc::pid_t,
+    timeout: Option<Duration>,
+    mut on_class: Option<&mut dyn FnMut(Class)>,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    let mut done = false;
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+
+    loop {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match wait_readable(&pipe, remaining) {
+                Ok(true) => {}
+                Ok(false) => {
+                    unsafe { libc::kill(child, libc::SIGKILL) };
+                    outcome.error = timeout.map(WorkerError::TimedOut);
+                    break;
+                }
+                Err(e) => {
+                    // Or the wait below outlasts the timeout
+                    unsafe { libc::kill(child, libc::SIGKILL) };
+                    outcome.error = Some(WorkerError::Io(e));
+                    break;
+                }
+            }
+        }
+        let read = match pipe.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                unsafe { libc::kill(child, libc::SIGKILL) };
+                outcome.error = Some(WorkerError::Io(e));
+                break;
+            }
+        };
+        buffer.extend_from_slice(&chunk[..read]);
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            match serde_json::from_slice(&line) {
+                Ok(Message::Log(message)) => eprintln!("{}", message),
+                Ok(Message::Event(event)) => match (event, &mut on_class) {
+                    (Event::Class(class), Some(on_class)) => on_class(class),
+                    (event, _) => outcome.dump.push(event),
+                },
+                Ok(Message::Failed(reason)) => outcome.error = Some(WorkerError::Failed(reason)),
+                Ok(Message::Done) => done = true,
+                Err(e) => eprintln!("warning: ignoring malformed worker message: {}", e),
+            }
+        }
+    }
+
+    let mut status = 0;
+    if unsafe { libc::waitpid(child, &mut status, 0) } == -1 {
+        outcome
+            .error
+            .get_or_insert(WorkerError::Io(io::Error::last_os_error()));
+    } else if !done && outcome.error.is_none() {
+        outcome.error = Some(if libc::WIFSIGNALED(status) {
+            WorkerError::Signaled(libc::WTERMSIG(status))
+        } else {
+            WorkerError::Exited(libc::WEXITSTATUS(status))
+        });
+    }
+    outcome
+}
+
+/// Waits up to `timeout` for `pipe` to become readable (or hung up).
+fn wait_readable(pipe: &File, timeout: Duration) -> io::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: pipe.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // A signal doesn't give us more time, so retries only wait out the rest
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // Round up so we never spin on a sub-millisecond remainder
+        let millis = remaining
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .min(i32::MAX as u128) as i32;
+        match unsafe { libc::poll(&mut fd, 1, millis) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            -1 => return Err(io::Error::last_os_error()),
+            ready => return Ok(ready > 0),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! YARA rules as locators, for teams that already keep rule sets for game
+//! builds. Each rule that matches is reported like a signature, at the
+//! offsets its strings matched.
+//!
+//! libyara is a native dependency, so this is behind the `yara` feature.
+//! Without it, asking for rules is an error.
+
+use std::path::PathBuf;
+
+#[cfg(feature = "yara")]
+pub struct RuleSet {
+    rules: yara::Rules,
+}
+
+/// Never constructed without the `yara` feature.
+#[cfg(not(feature = "yara"))]
+pub enum RuleSet {}
+
+#[cfg(feature = "yara")]
+impl RuleSet {
+    /// Compiles every rule in `paths` into one set.
+    pub fn load(paths: &[PathBuf]) -> Result<Self, String> {
+        let mut compiler =
+            yara::Compiler::new().map_err(|e| format!("failed to set up YARA: {}", e))?;
+        for path in paths {
+            compiler = compiler
+                .add_rules_file(path)
+                .map_err(|e| format!("failed to compile {}: {}", path.display(), e))?;
+        }
+        let rules = compiler
+            .compile_rules()
+            .map_err(|e| format!("failed to compile YARA rules: {}", e))?;
+        Ok(RuleSet { rules })
+    }
+
+    /// Every rule in the set, named like [`RuleSet::scan`] names them.
+    pub fn names(&self) -> Vec<String> {
+        self.rules
+            .get_rules()
+            .iter()
+            .map(|rule| name(rule.namespace, rule.identifier))
+            .collect()
+    }
+
+    /// The rules matching `bytes`, each with the offsets its strings matched
+    /// at in ascending order.
+    pub fn scan(&self, bytes: &[u8]) -> Result<Vec<(String, Vec<usize>)>, String> {
+        let matched = self
+            .rules
+            .scan_mem(bytes, 0)
+            .map_err(|e| format!("YARA scan failed: {}", e))?;
+        Ok(matched
+            .iter()
+            .map(|rule| {
+                let mut offsets: Vec<_> = rule
+                    .strings
+                    .iter()
+                    .flat_map(|string| string.matches.iter().map(|m| m.offset))
+                    .collect();
+                offsets.sort_unstable();
+                offsets.dedup();
+                (name(rule.namespace, rule.identifier), offsets)
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "yara")]
+fn name(namespace: &str, identifier: &str) -> String {
+    if namespace == "default" {
+        identifier.to_string()
+    } else {
+        format!("{}:{}", namespace, identifier)
+    }
+}
+
+#[cfg(not(feature = "yara"))]
+impl RuleSet {
+    pub fn load(_paths: &[PathBuf]) -> Result<Self, String> {
+        Err("this build has no YARA support, rebuild with --features yara".to_string())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        match *self {}
+    }
+
+    pub fn scan(&self, _bytes: &[u8]) -> Result<Vec<(String, Vec<usize>)>, String> {
+        match *self {}
+    }
+}